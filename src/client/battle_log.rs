@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use common::Position;
+
+/// Count of the latest lines kept in the battle log
+pub const MAX_LOG_LINES: usize = 100;
+
+/// Seconds a notification stays on screen
+pub const NOTIFICATION_DURATION: f64 = 4.0;
+
+/// Seconds an effect is animated
+pub const EFFECT_DURATION: f64 = 1.0;
+
+fn seconds_since(instant: Instant) -> f64 {
+    let elapsed = instant.elapsed();
+    elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+/// Expanding and fading circle drawn where something has happened
+pub struct Effect {
+    position: Position,
+    size: f32,
+    color: [f32; 4],
+    created_at: Instant
+}
+
+impl Effect {
+    pub fn new(position: Position, size: f32, color: [f32; 4]) -> Effect {
+        Effect {
+            position,
+            size,
+            color,
+            created_at: Instant::now()
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Fraction of the animation passed, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        (seconds_since(self.created_at) / EFFECT_DURATION).min(1.0) as f32
+    }
+
+    pub fn size(&self) -> f32 {
+        self.size * (0.5 + self.progress())
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        [self.color[0], self.color[1], self.color[2], self.color[3] * (1.0 - self.progress())]
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.progress() >= 1.0
+    }
+}
+
+pub struct BattleLog {
+    lines: VecDeque<String>,
+    notification: Option<(String, Instant)>
+}
+
+impl BattleLog {
+    pub fn new() -> BattleLog {
+        BattleLog {
+            lines: VecDeque::new(),
+            notification: None
+        }
+    }
+
+    pub fn add(&mut self, line: String) {
+        self.lines.push_back(line);
+
+        if self.lines.len() > MAX_LOG_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    /// Adds the line to the log and shows it as a notification
+    pub fn notify(&mut self, line: String) {
+        self.notification = Some((line.clone(), Instant::now()));
+        self.add(line);
+    }
+
+    pub fn last_lines(&self, count: usize) -> Vec<String> {
+        self.lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    pub fn notification(&self) -> Option<&String> {
+        match self.notification {
+            Some((ref notification, created_at)) if seconds_since(created_at) < NOTIFICATION_DURATION => Some(notification),
+            _ => None
+        }
+    }
+}
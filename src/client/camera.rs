@@ -1,10 +1,52 @@
-use vecmath;
+use std::f64::consts::PI;
+
+/// Zoom change of one mouse wheel line, the zoom is multiplied or divided by it
+const WHEEL_ZOOM_FACTOR: f64 = 1.15;
+
+/// Share of the remaining zoom change applied per second
+const ZOOM_SMOOTHNESS: f64 = 8.0;
+
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 2.0;
+
+/// World units per second of keyboard panning at zoom 1
+const KEY_PAN_SPEED: f64 = 750.0;
+
+/// Radians per second of keyboard rotation
+const ROTATION_SPEED: f64 = PI / 2.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PanDirection {
+    Left,
+    Right,
+    Up,
+    Down
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RotationDirection {
+    Clockwise,
+    Counterclockwise
+}
 
 pub struct Camera {
     x: f64,
     y: f64,
     zoom: f64,
+    /// Zoom the camera smoothly moves to
+    target_zoom: f64,
+    /// Window point which stays in place while zooming, the window center if not set
+    zoom_anchor: Option<(f64, f64)>,
+    /// Directions of the held panning keys
+    pan_directions: Vec<PanDirection>,
+    /// Last cursor position while the view is dragged
+    drag_position: Option<(f64, f64)>,
+    /// World area the window center is kept in
+    bounds: Option<((f64, f64), (f64, f64))>,
+    /// Counterclockwise rotation of the world on the screen in radians
     angle: f64,
+    /// Directions of the held rotation keys
+    rotation_directions: Vec<RotationDirection>,
     left: f32,
     right: f32,
     top: f32,
@@ -19,7 +61,13 @@ impl Camera {
             x: 0.0,
             y: 0.0,
             zoom: 1.0,
+            target_zoom: 1.0,
+            zoom_anchor: None,
+            pan_directions: vec![],
+            drag_position: None,
+            bounds: None,
             angle: 0.0,
+            rotation_directions: vec![],
             left: -500.0,
             right: 500.0,
             top: 500.0,
@@ -40,51 +88,51 @@ impl Camera {
 
         let (cursor_position_x, cursor_position_y) = cursor_position;
 
+        // The translation is applied after rotating, so edge scrolling follows the screen axes
         if cursor_position_x >= (window_width - EDGE_WIDTH) {
-            let mut speed = MAX_SPEED - (window_width - cursor_position_x) * speed_per_px;
-            speed *= dt / self.zoom;
-
-            self.x -= speed * self.angle.cos();
-            self.y += speed * self.angle.sin();
+            let speed = MAX_SPEED - (window_width - cursor_position_x) * speed_per_px;
+            self.x -= speed * dt / self.zoom;
         }
 
         if cursor_position_x <= EDGE_WIDTH {
-            let mut speed = MAX_SPEED - cursor_position_x * speed_per_px;
-            speed *= dt / self.zoom;
-
-            self.x += speed * self.angle.cos();
-            self.y -= speed * self.angle.sin();
+            let speed = MAX_SPEED - cursor_position_x * speed_per_px;
+            self.x += speed * dt / self.zoom;
         }
 
         if cursor_position_y >= (window_height - EDGE_WIDTH) {
-            let mut speed = MAX_SPEED - (window_height - cursor_position_y) * speed_per_px;
-            speed *= dt / self.zoom;
-
-            self.y += speed * self.angle.cos();
-            self.x += speed * self.angle.sin();
+            let speed = MAX_SPEED - (window_height - cursor_position_y) * speed_per_px;
+            self.y += speed * dt / self.zoom;
         }
 
         if cursor_position_y <= EDGE_WIDTH {
-            let mut speed = MAX_SPEED - cursor_position_y * speed_per_px;
-            speed *= dt / self.zoom;
-
-            self.y -= speed * self.angle.cos();
-            self.x -= speed * self.angle.sin();
+            let speed = MAX_SPEED - cursor_position_y * speed_per_px;
+            self.y -= speed * dt / self.zoom;
         }
+
+        self.update_key_pan(viewport, dt);
+        self.update_rotation(viewport, dt);
+        self.update_zoom(viewport, dt);
+        self.clamp(viewport);
     }
 
     pub fn zoom_in(&mut self) {
-        let zoom = self.zoom;
-        self.set_zoom(zoom + 0.1);
+        let zoom = self.target_zoom;
+        self.set_target_zoom(zoom + 0.1, None);
     }
 
     pub fn zoom_out(&mut self) {
-        let zoom = self.zoom;
-        self.set_zoom(zoom - 0.1);
+        let zoom = self.target_zoom;
+        self.set_target_zoom(zoom - 0.1, None);
+    }
+
+    /// Positive lines scroll away from the user and zoom closer to the point under the cursor
+    pub fn zoom_wheel(&mut self, lines: f64, cursor_position: (f64, f64)) {
+        let zoom = self.target_zoom * WHEEL_ZOOM_FACTOR.powf(-lines);
+        self.set_target_zoom(zoom, Some(cursor_position));
     }
 
     pub fn set_zoom(&mut self, zoom: f64) {
-        self.zoom = zoom.min(2.0).max(0.1);
+        self.zoom = zoom.min(MAX_ZOOM).max(MIN_ZOOM);
 
         self.left = -500.0 * self.zoom as f32;
         self.right = 500.0 * self.zoom as f32;
@@ -92,14 +140,104 @@ impl Camera {
         self.bottom = -500.0 * self.zoom as f32;
     }
 
+    /// Changes the zoom keeping the world point under the window position in place
+    pub fn zoom_at(&mut self, zoom: f64, window_position: (f64, f64), viewport: &[f64; 2]) {
+        let before = self.unproject(window_position, viewport);
+
+        self.set_zoom(zoom);
+
+        let after = self.unproject(window_position, viewport);
+
+        self.pan_world((after.0 as f64 - before.0 as f64, after.1 as f64 - before.1 as f64), viewport);
+    }
+
+    pub fn angle(&self) -> f64 {
+        self.angle
+    }
+
+    /// Turns the world keeping the point under the window position in place
+    pub fn rotate_at(&mut self, angle: f64, window_position: (f64, f64), viewport: &[f64; 2]) {
+        let before = self.unproject(window_position, viewport);
+
+        self.angle = angle % (2.0 * PI);
+
+        let after = self.unproject(window_position, viewport);
+
+        self.pan_world((after.0 as f64 - before.0 as f64, after.1 as f64 - before.1 as f64), viewport);
+    }
+
+    pub fn set_rotation(&mut self, direction: RotationDirection, is_active: bool) {
+        self.rotation_directions.retain(|&other| other != direction);
+
+        if is_active {
+            self.rotation_directions.push(direction);
+        }
+    }
+
+    /// Turns the world back so north is up, around the window center
+    pub fn reset_rotation(&mut self, viewport: &[f64; 2]) {
+        self.rotation_directions.clear();
+        self.rotate_at(0.0, (viewport[0] / 2.0, viewport[1] / 2.0), viewport);
+    }
+
+    pub fn set_pan(&mut self, direction: PanDirection, is_active: bool) {
+        self.pan_directions.retain(|&other| other != direction);
+
+        if is_active {
+            self.pan_directions.push(direction);
+        }
+    }
+
+    /// Forgets held panning and rotation keys
+    pub fn release_keys(&mut self) {
+        self.pan_directions.clear();
+        self.rotation_directions.clear();
+    }
+
+    pub fn start_drag(&mut self, cursor_position: (f64, f64)) {
+        self.drag_position = Some(cursor_position);
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag_position.is_some()
+    }
+
+    /// Moves the view so the world point grabbed by the cursor follows it
+    pub fn drag_to(&mut self, cursor_position: (f64, f64), viewport: &[f64; 2]) {
+        if let Some(drag_position) = self.drag_position {
+            let grabbed = self.unproject(drag_position, viewport);
+            let current = self.unproject(cursor_position, viewport);
+
+            self.pan_world((current.0 as f64 - grabbed.0 as f64, current.1 as f64 - grabbed.1 as f64), viewport);
+            self.clamp(viewport);
+
+            self.drag_position = Some(cursor_position);
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag_position = None;
+    }
+
+    /// World area the middle of the window may not leave, the whole map with a margin
+    pub fn set_bounds(&mut self, min: (f64, f64), max: (f64, f64)) {
+        self.bounds = Some((min, max));
+    }
+
+    /// Moves the camera so the world position is in the middle of the window
+    pub fn center_on(&mut self, position: (f64, f64), viewport: &[f64; 2]) {
+        let center = self.unproject((viewport[0] / 2.0, viewport[1] / 2.0), viewport);
+
+        self.pan_world((center.0 as f64 - position.0, center.1 as f64 - position.1), viewport);
+        self.clamp(viewport);
+    }
+
     pub fn view_matrix(&self, viewport: &[f64; 2]) -> [[f32; 4]; 4] {
-        let width = viewport[0];
-        let height = viewport[1];
+        let (lr, bt) = self.scale(viewport);
 
-        let aspect_ratio = height as f32 / width as f32;
+        let sin = self.angle.sin() as f32;
+        let cos = self.angle.cos() as f32;
 
-        let lr = aspect_ratio * 2.0 / (self.right - self.left);
-        let bt = 2.0 / (self.top - self.bottom);
         let nf = -2.0 / (self.far - self.near);
 
         let tx = - (self.right + self.left) / (self.right - self.left);
@@ -109,24 +247,37 @@ impl Camera {
         let x = self.x as f32;
         let y = self.y as f32;
 
+        // Scaling of the rotated world, the rotation happens before the window aspect stretches it
         [
-            [ lr, 0.0, 0.0,  tx],
-            [0.0,  bt, 0.0,  ty],
-            [0.0, 0.0,  nf,  tz],
-            [  x,   y, 0.0, 1.0],
+            [ lr * cos, bt * sin, 0.0,  tx],
+            [-lr * sin, bt * cos, 0.0,  ty],
+            [      0.0,      0.0,  nf,  tz],
+            [        x,        y, 0.0, 1.0],
         ]
     }
 
     pub fn unproject(&self, window_coordinates: (f64, f64), viewport: &[f64; 2]) -> (f32, f32) {
-        let mut ndc = self.get_ndc(window_coordinates, viewport);
+        let ndc = self.get_ndc(window_coordinates, viewport);
+        let view = self.view_matrix(viewport);
+
+        let x = ndc[0] - view[3][0];
+        let y = ndc[1] - view[3][1];
 
-        ndc[0] -= self.x as f32;
-        ndc[1] -= self.y as f32;
+        // The world lies in the z = 0 plane, so only the 2x2 part of the view needs inverting
+        let (a, b, c, d) = (view[0][0], view[1][0], view[0][1], view[1][1]);
+        let determinant = a * d - b * c;
 
-        let inv = vecmath::mat4_inv(self.view_matrix(viewport));
-        let result = vecmath::row_mat4_transform(inv, ndc);
+        ((d * x - b * y) / determinant, (a * y - c * x) / determinant)
+    }
+
+    /// Normalized device units per world unit along the window axes
+    fn scale(&self, viewport: &[f64; 2]) -> (f32, f32) {
+        let width = viewport[0];
+        let height = viewport[1];
+
+        let aspect_ratio = height as f32 / width as f32;
 
-        (result[0], result[1])
+        (aspect_ratio * 2.0 / (self.right - self.left), 2.0 / (self.top - self.bottom))
     }
 
     fn get_ndc(&self, window_coordinates: (f64, f64), viewport: &[f64; 2]) -> [f32; 4] {
@@ -142,4 +293,268 @@ impl Camera {
             1.0
         ]
     }
+
+    fn set_target_zoom(&mut self, zoom: f64, anchor: Option<(f64, f64)>) {
+        self.target_zoom = zoom.min(MAX_ZOOM).max(MIN_ZOOM);
+        self.zoom_anchor = anchor;
+    }
+
+    fn update_zoom(&mut self, viewport: &[f64; 2], dt: f64) {
+        if (self.target_zoom - self.zoom).abs() < 0.001 {
+            return;
+        }
+
+        let zoom = self.zoom + (self.target_zoom - self.zoom) * (ZOOM_SMOOTHNESS * dt).min(1.0);
+        let anchor = self.zoom_anchor.unwrap_or((viewport[0] / 2.0, viewport[1] / 2.0));
+
+        self.zoom_at(zoom, anchor, viewport);
+    }
+
+    fn update_rotation(&mut self, viewport: &[f64; 2], dt: f64) {
+        let speed = self.rotation_directions
+            .iter()
+            .fold(0.0, |speed, direction| {
+                match *direction {
+                    RotationDirection::Counterclockwise => speed + ROTATION_SPEED,
+                    RotationDirection::Clockwise => speed - ROTATION_SPEED
+                }
+            });
+
+        if speed != 0.0 {
+            let angle = self.angle + speed * dt;
+            self.rotate_at(angle, (viewport[0] / 2.0, viewport[1] / 2.0), viewport);
+        }
+    }
+
+    fn update_key_pan(&mut self, viewport: &[f64; 2], dt: f64) {
+        let distance = KEY_PAN_SPEED * dt * self.zoom;
+
+        // Moving the view left shifts the world right under it, along the window axes
+        let (dx, dy) = self.pan_directions
+            .iter()
+            .fold((0.0, 0.0), |(dx, dy), direction| {
+                match *direction {
+                    PanDirection::Left => (dx + distance, dy),
+                    PanDirection::Right => (dx - distance, dy),
+                    PanDirection::Up => (dx, dy - distance),
+                    PanDirection::Down => (dx, dy + distance)
+                }
+            });
+
+        if dx != 0.0 || dy != 0.0 {
+            let (lr, bt) = self.scale(viewport);
+
+            self.x += lr as f64 * dx;
+            self.y += bt as f64 * dy;
+        }
+    }
+
+    /// Shifts the shown world by the world distance
+    fn pan_world(&mut self, distance: (f64, f64), viewport: &[f64; 2]) {
+        let view = self.view_matrix(viewport);
+
+        self.x += view[0][0] as f64 * distance.0 + view[1][0] as f64 * distance.1;
+        self.y += view[0][1] as f64 * distance.0 + view[1][1] as f64 * distance.1;
+    }
+
+    fn clamp(&mut self, viewport: &[f64; 2]) {
+        if let Some(((min_x, min_y), (max_x, max_y))) = self.bounds {
+            let center = self.unproject((viewport[0] / 2.0, viewport[1] / 2.0), viewport);
+            let (x, y) = (center.0 as f64, center.1 as f64);
+
+            let clamped = (x.max(min_x).min(max_x), y.max(min_y).min(max_y));
+
+            if clamped != (x, y) {
+                self.pan_world((x - clamped.0, y - clamped.1), viewport);
+            }
+        }
+    }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::{Camera, RotationDirection};
+
+    const VIEWPORT: [f64; 2] = [1280.0, 800.0];
+
+    const POSITIONS: [(f64, f64); 4] = [(0.0, 0.0), (120.0, -340.0), (-800.0, 450.0), (1500.0, 1500.0)];
+
+    /// Window position of a world point, the inverse of the transform `unproject` undoes
+    fn project(camera: &Camera, position: (f64, f64), viewport: &[f64; 2]) -> (f64, f64) {
+        let view = camera.view_matrix(viewport);
+        let (x, y) = (position.0 as f32, position.1 as f32);
+
+        let clip_x = view[0][0] * x + view[1][0] * y + view[3][0];
+        let clip_y = view[0][1] * x + view[1][1] * y + view[3][1];
+        let clip_w = view[0][3] * x + view[1][3] * y + view[3][3];
+
+        (
+            ((clip_x / clip_w) as f64 + 1.0) * viewport[0] / 2.0,
+            (1.0 - (clip_y / clip_w) as f64) * viewport[1] / 2.0
+        )
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f64, f64)) {
+        let error = (actual.0 as f64 - expected.0).abs().max((actual.1 as f64 - expected.1).abs());
+        assert!(error < 0.1, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    fn assert_consistent(camera: &Camera, viewport: &[f64; 2]) {
+        for &position in POSITIONS.iter() {
+            let window_position = project(camera, position, viewport);
+            assert_near(camera.unproject(window_position, viewport), position);
+        }
+    }
+
+    #[test]
+    fn unproject_default() {
+        let camera = Camera::new();
+
+        assert_consistent(&camera, &VIEWPORT);
+        assert_near(camera.unproject((640.0, 400.0), &VIEWPORT), (0.0, 0.0));
+    }
+
+    #[test]
+    fn unproject_viewports() {
+        let camera = Camera::new();
+
+        for viewport in [[800.0, 800.0], [1920.0, 1080.0], [600.0, 1000.0]].iter() {
+            assert_consistent(&camera, viewport);
+        }
+    }
+
+    #[test]
+    fn unproject_centered() {
+        let mut camera = Camera::new();
+
+        for &position in POSITIONS.iter() {
+            camera.center_on(position, &VIEWPORT);
+
+            assert_consistent(&camera, &VIEWPORT);
+            assert_near(camera.unproject((640.0, 400.0), &VIEWPORT), position);
+        }
+    }
+
+    #[test]
+    fn unproject_zoomed() {
+        let mut camera = Camera::new();
+        camera.center_on((300.0, -200.0), &VIEWPORT);
+
+        for &zoom in [0.1, 0.35, 1.0, 1.7, 2.0].iter() {
+            camera.set_zoom(zoom);
+            assert_consistent(&camera, &VIEWPORT);
+        }
+    }
+
+    #[test]
+    fn unproject_zoomed_at_cursor() {
+        let mut camera = Camera::new();
+        let cursor = (200.0, 650.0);
+
+        for &zoom in [0.5, 1.8, 0.2].iter() {
+            let before = camera.unproject(cursor, &VIEWPORT);
+
+            camera.zoom_at(zoom, cursor, &VIEWPORT);
+
+            assert_consistent(&camera, &VIEWPORT);
+            assert_near(camera.unproject(cursor, &VIEWPORT), (before.0 as f64, before.1 as f64));
+        }
+    }
+
+    #[test]
+    fn unproject_smoothly_zoomed() {
+        let mut camera = Camera::new();
+        let cursor = (1000.0, 100.0);
+        let before = camera.unproject(cursor, &VIEWPORT);
+
+        camera.zoom_wheel(3.0, cursor);
+
+        for _ in 0..10 {
+            camera.update(cursor, &VIEWPORT, 0.05);
+        }
+
+        assert!(camera.zoom < 1.0);
+        assert_consistent(&camera, &VIEWPORT);
+        assert_near(camera.unproject(cursor, &VIEWPORT), (before.0 as f64, before.1 as f64));
+    }
+
+    #[test]
+    fn unproject_rotated() {
+        let mut camera = Camera::new();
+        camera.center_on((-250.0, 400.0), &VIEWPORT);
+        camera.set_zoom(1.4);
+
+        for &angle in [PI / 6.0, PI / 2.0, PI, -PI / 3.0, 5.0].iter() {
+            camera.rotate_at(angle, (300.0, 200.0), &VIEWPORT);
+            assert_consistent(&camera, &VIEWPORT);
+        }
+    }
+
+    #[test]
+    fn unproject_rotated_at_cursor() {
+        let mut camera = Camera::new();
+        let cursor = (900.0, 250.0);
+        let before = camera.unproject(cursor, &VIEWPORT);
+
+        camera.rotate_at(PI / 4.0, cursor, &VIEWPORT);
+
+        assert_near(camera.unproject(cursor, &VIEWPORT), (before.0 as f64, before.1 as f64));
+
+        // Quarter turn counterclockwise puts world east where north was
+        let center = camera.unproject((640.0, 400.0), &VIEWPORT);
+        camera.rotate_at(PI / 2.0, (640.0, 400.0), &VIEWPORT);
+
+        let above = camera.unproject((640.0, 300.0), &VIEWPORT);
+        assert!(above.0 > center.0 + 1.0 && (above.1 - center.1).abs() < 0.1, "{:?} {:?}", center, above);
+    }
+
+    #[test]
+    fn unproject_rotated_by_keys() {
+        let mut camera = Camera::new();
+        let center = camera.unproject((640.0, 400.0), &VIEWPORT);
+
+        camera.set_rotation(RotationDirection::Clockwise, true);
+
+        for _ in 0..10 {
+            camera.update((640.0, 400.0), &VIEWPORT, 0.05);
+        }
+
+        assert!(camera.angle() < 0.0);
+        assert_consistent(&camera, &VIEWPORT);
+        assert_near(camera.unproject((640.0, 400.0), &VIEWPORT), (center.0 as f64, center.1 as f64));
+
+        camera.reset_rotation(&VIEWPORT);
+
+        assert_eq!(camera.angle(), 0.0);
+        assert_consistent(&camera, &VIEWPORT);
+    }
+
+    #[test]
+    fn unproject_dragged() {
+        let mut camera = Camera::new();
+        camera.set_zoom(0.6);
+        camera.rotate_at(1.0, (640.0, 400.0), &VIEWPORT);
+
+        let grabbed = camera.unproject((500.0, 300.0), &VIEWPORT);
+
+        camera.start_drag((500.0, 300.0));
+        camera.drag_to((700.0, 450.0), &VIEWPORT);
+        camera.end_drag();
+
+        assert_consistent(&camera, &VIEWPORT);
+        assert_near(camera.unproject((700.0, 450.0), &VIEWPORT), (grabbed.0 as f64, grabbed.1 as f64));
+    }
+
+    #[test]
+    fn unproject_clamped() {
+        let mut camera = Camera::new();
+        camera.set_bounds((-1000.0, -1000.0), (1000.0, 1000.0));
+
+        camera.center_on((5000.0, -3000.0), &VIEWPORT);
+
+        assert_consistent(&camera, &VIEWPORT);
+        assert_near(camera.unproject((640.0, 400.0), &VIEWPORT), (1000.0, -1000.0));
+    }
+}
\ No newline at end of file
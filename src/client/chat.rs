@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use client::player::Player;
+use common::{PlayerId, Position};
+
+/// Seconds a received ping stays on screen
+pub const PING_DURATION: f64 = 3.0;
+
+/// Count of the latest messages kept for the chat panel
+pub const MAX_CHAT_LINES: usize = 50;
+
+pub struct ChatLine {
+    from: PlayerId,
+    channel: String,
+    text: String
+}
+
+impl ChatLine {
+    pub fn new(from: PlayerId, channel: String, text: String) -> ChatLine {
+        ChatLine {
+            from,
+            channel,
+            text
+        }
+    }
+
+    pub fn describe(&self, players: &HashMap<PlayerId, Player>) -> String {
+        let name = players.get(&self.from)
+            .map_or("Unknown".to_string(), |player| player.name().clone());
+
+        match self.channel.as_ref() {
+            "all" => format!("{}: {}", name, self.text),
+            channel => format!("[{}] {}: {}", channel, name, self.text)
+        }
+    }
+}
+
+pub struct Ping {
+    from: PlayerId,
+    position: Position,
+    received_at: Instant
+}
+
+impl Ping {
+    pub fn new(from: PlayerId, position: Position) -> Ping {
+        Ping {
+            from,
+            position,
+            received_at: Instant::now()
+        }
+    }
+
+    pub fn from(&self) -> PlayerId {
+        self.from
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Seconds since the ping has been received
+    pub fn age(&self) -> f64 {
+        let elapsed = self.received_at.elapsed();
+        elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.age() > PING_DURATION
+    }
+}
+
+/// Outgoing chat message typed by the player. `/team <text>` goes to teammates and allies,
+/// `/w <player name> <text>` is private, anything else is sent to everybody.
+pub struct ChatInput {
+    pub channel: &'static str,
+    pub player_id: Option<PlayerId>,
+    pub text: String
+}
+
+impl ChatInput {
+    pub fn parse(input: &str, players: &HashMap<PlayerId, Player>) -> Option<ChatInput> {
+        let input = input.trim();
+
+        if input.starts_with("/team ") {
+            return Some(ChatInput { channel: "team", player_id: None, text: input["/team ".len()..].to_string() });
+        }
+
+        if input.starts_with("/w ") {
+            let rest = &input["/w ".len()..];
+
+            return players
+                .values()
+                .find(|player| rest.starts_with(player.name().as_str()))
+                .map(|player| {
+                    ChatInput {
+                        channel: "private",
+                        player_id: Some(player.id()),
+                        text: rest[player.name().len()..].to_string()
+                    }
+                });
+        }
+
+        if input.is_empty() {
+            None
+        } else {
+            Some(ChatInput { channel: "all", player_id: None, text: input.to_string() })
+        }
+    }
+}
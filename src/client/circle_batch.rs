@@ -0,0 +1,146 @@
+use std::f32::consts::PI;
+
+use glium::{Display, DrawParameters, Program, Surface, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+
+const SECTORS_COUNT: u32 = 128;
+
+/// Smallest instance buffer, it grows twice when a frame needs more
+const MIN_INSTANCES_CAPACITY: usize = 1024;
+
+#[derive(Copy, Clone)]
+struct CircleVertex {
+    position: [f32; 2]
+}
+
+implement_vertex!(CircleVertex, position);
+
+#[derive(Copy, Clone)]
+struct CircleInstance {
+    center: [f32; 2],
+    scale: [f32; 2],
+    /// Part of the circle drawn as a sector starting from the right, 1 for the whole circle
+    progress: f32,
+    color: [f32; 4]
+}
+
+implement_vertex!(CircleInstance, center, scale, progress, color);
+
+/// Collects circles of a frame and draws all of them with a single instanced draw call
+pub struct CircleBatch {
+    mesh: VertexBuffer<CircleVertex>,
+    program: Program,
+    instances: Vec<CircleInstance>,
+    instance_buffer: VertexBuffer<CircleInstance>
+}
+
+impl CircleBatch {
+    pub fn new(display: &Display) -> Self {
+        // The fan starts with its center, so a prefix of it is a sector
+        let mut shape = (0..(SECTORS_COUNT + 1))
+            .map(|sector| (sector as f32) * 2_f32 * PI / (SECTORS_COUNT as f32))
+            .map(|angle| CircleVertex { position: [angle.cos(), angle.sin()] })
+            .collect::<Vec<_>>();
+
+        shape.insert(0, CircleVertex { position: [0_f32, 0_f32] });
+
+        let mesh = VertexBuffer::new(display, &shape).unwrap();
+
+        let program = program!(display,
+            140 => {
+                vertex: r#"
+                    #version 140
+
+                    in vec2 position;
+                    in vec2 center;
+                    in vec2 scale;
+                    in float progress;
+                    in vec4 color;
+                    out vec4 v_color;
+
+                    uniform mat4 view;
+                    uniform float sectors_count;
+
+                    void main() {
+                        // Vertices past the progress collapse into the center and leave a sector
+                        float is_visible = step(float(gl_VertexID), 1.0 + floor(progress * sectors_count));
+
+                        v_color = color;
+                        gl_Position = view * vec4(center + position * scale * is_visible, 0.0, 1.0);
+                    }
+                "#,
+                outputs_srgb: true,
+                fragment: r#"
+                    #version 140
+
+                    in vec4 v_color;
+                    out vec4 color;
+
+                    void main() {
+                        color = v_color;
+                    }
+                "#,
+            },
+        ).unwrap();
+
+        let instance_buffer = VertexBuffer::empty_dynamic(display, MIN_INSTANCES_CAPACITY).unwrap();
+
+        CircleBatch {
+            mesh,
+            program,
+            instances: vec![],
+            instance_buffer
+        }
+    }
+
+    pub fn push_circle(&mut self, center: [f32; 2], size: f32, color: [f32; 4]) {
+        self.push_sector(center, size, 1.0, color);
+    }
+
+    pub fn push_sector(&mut self, center: [f32; 2], size: f32, progress: f32, color: [f32; 4]) {
+        self.instances.push(CircleInstance {
+            center,
+            scale: [size, size],
+            progress: progress.max(0.0).min(1.0),
+            color
+        });
+    }
+
+    /// Circle stretched along the axes, for sizes given in pixels on a window that is not square
+    pub fn push_ellipse(&mut self, center: [f32; 2], scale: [f32; 2], color: [f32; 4]) {
+        self.instances.push(CircleInstance {
+            center,
+            scale,
+            progress: 1.0,
+            color
+        });
+    }
+
+    /// Draws the collected circles in the order they were pushed and starts a new batch
+    pub fn draw<S>(&mut self, display: &Display, surface: &mut S, view: [[f32; 4]; 4], params: &DrawParameters) where S: Surface {
+        let instances_count = self.instances.len();
+
+        if instances_count == 0 {
+            return;
+        }
+
+        if instances_count > self.instance_buffer.len() {
+            let capacity = instances_count.max(self.instance_buffer.len() * 2);
+            self.instance_buffer = VertexBuffer::empty_dynamic(display, capacity).unwrap();
+        }
+
+        let instances = self.instance_buffer.slice(0..instances_count).unwrap();
+        instances.write(&self.instances);
+
+        let uniforms = uniform! {
+            view: view,
+            sectors_count: SECTORS_COUNT as f32
+        };
+
+        let indices = NoIndices(PrimitiveType::TriangleFan);
+
+        surface.draw((&self.mesh, instances.per_instance().unwrap()), &indices, &self.program, &uniforms, params).unwrap();
+
+        self.instances.clear();
+    }
+}
\ No newline at end of file
@@ -1,26 +1,47 @@
+use std::rc::Rc;
 use std::thread;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 use std::sync::mpsc::{channel, Receiver as ChannelReceiver};
 
 use fps_counter::FPSCounter;
 use glium;
 use glium::Surface;
+use glium::glutin::{ElementState, VirtualKeyCode};
 use glium_text_rusttype as glium_text;
+use time;
 use vecmath;
-use ws::{connect, Sender};
+use ws::{connect, CloseCode, Sender};
 
+use client::battle_log::{BattleLog, Effect};
 use client::camera::Camera;
+use client::chat;
+use client::circle_batch::CircleBatch;
+use client::chat::{ChatInput, ChatLine, Ping};
 use client::command::Command;
+use client::controls::{Action, Chord, Controls, Input, DEFAULT_CONTROLS_PATH};
 use client::game_cursor::GameCursor;
 use client::game_event::GameEvent;
-use client::game_ui::GameUi;
+use client::game_ui::{GameUi, GameUiState, Hud, Lobby, LobbyColor, LobbyPlayer, Results, UiEvent};
 use client::input_mapping;
+use client::diplomacy::Diplomacy;
+use client::economy::Economy;
+use client::interpolation::SquadInterpolation;
 use client::json;
+use client::leaderboard::LeaderboardEntry;
+use client::match_event::MatchEvent;
+use client::match_info::MatchInfo;
+use client::minimap::Minimap;
+use client::palette::Palette;
 use client::player::Player;
-use client::squad::Squad;
+use client::research::ResearchItem;
+use client::rules::Rules;
+use client::selection::{Selection, SelectionMode};
+use client::squad::{Squad, SquadState};
+use client::standing::Standing;
 use client::waypoint::{Waypoint, WaypointType};
-use common::{Id, PlayerId, Position};
+use common::{Id, PlayerId, Position, SquadClass, PLAYER_COLORS_COUNT};
+use common::utils;
 use common::websocket_handler::WebsocketHandler;
 
 #[derive(Copy, Clone)]
@@ -30,32 +51,102 @@ struct Vertex {
 
 implement_vertex!(Vertex, position);
 
+/// Squad sizes with laid out label text kept between frames
+const MAX_CACHED_SQUAD_LABELS: usize = 1024;
+
+/// Labels drawn smaller than that many pixels are unreadable, so they are skipped
+const MIN_LABEL_HEIGHT: f32 = 6.0;
+
+/// World size of squad labels
+const LABEL_SIZE: f32 = 20.0;
+
+/// Main loop sleeps for what is left of the frame to keep 60 frames per second
+const FRAME_DURATION: f64 = 1.0 / 60.0;
+
+/// Weight of the latest frame in the average frame time
+const FRAME_TIME_SMOOTHING: f64 = 0.05;
+
 fn color_from_rgb(r: u8, g: u8, b: u8, alpha: f32) -> [f32; 4] {
     [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, alpha]
 }
 
+/// Normalized device coordinates of a window position in pixels
+fn window_to_ndc(window_position: (f64, f64), viewport: &[f64; 2]) -> [f32; 2] {
+    let (x, y) = window_position;
+
+    [(x * 2.0 / viewport[0] - 1.0) as f32, (1.0 - y * 2.0 / viewport[1]) as f32]
+}
+
+/// Whether the point lies inside a convex quadrilateral with corners given in order
+fn is_inside_quad(corners: &[(f32, f32); 4], point: (f32, f32)) -> bool {
+    let signs = (0..4)
+        .map(|i| {
+            let (ax, ay) = corners[i];
+            let (bx, by) = corners[(i + 1) % 4];
+
+            (bx - ax) * (point.1 - ay) - (by - ay) * (point.0 - ax)
+        })
+        .collect::<Vec<_>>();
+
+    signs.iter().all(|&sign| sign >= 0.0) || signs.iter().all(|&sign| sign <= 0.0)
+}
+
 pub struct Client {
     events_loop: glium::glutin::EventsLoop,
     display: glium::Display,
 
     text_system: glium_text::TextSystem,
-    font: glium_text::FontTexture,
+    font: Rc<glium_text::FontTexture>,
+    squad_labels: HashMap<u64, glium_text::TextDisplay<Rc<glium_text::FontTexture>>>,
 
-    shape: Vec<Vertex>,
+    circle_batch: CircleBatch,
     program: glium::Program,
+    /// Uploaded when the server sends other lanes, which happens once per map
+    lanes_vertex_buffer: Option<glium::VertexBuffer<Vertex>>,
+    /// Quads rewritten every frame without allocating a buffer
+    box_vertex_buffer: glium::VertexBuffer<Vertex>,
+    minimap_vertex_buffer: glium::VertexBuffer<Vertex>,
+    camera_vertex_buffer: glium::VertexBuffer<Vertex>,
 
     rx: Option<ChannelReceiver<Command>>,
 
     game_cursor: GameCursor,
 
     waypoints: HashMap<Id, Waypoint>,
+    lanes: Vec<(Id, Id)>,
     players: HashMap<PlayerId, Player>,
     squads: HashMap<Id, Squad>,
+    squad_interpolation: SquadInterpolation,
+    match_info: Option<MatchInfo>,
+    standings: Option<Vec<Standing>>,
+    leaderboard: Option<Vec<LeaderboardEntry>>,
+    diplomacy: Diplomacy,
+    rules: Option<Rules>,
+    research_items: Vec<ResearchItem>,
+    is_research_visible: bool,
+    controls: Controls,
+    is_controls_visible: bool,
+    /// Action waiting for a key or mouse button on the controls screen
+    rebinding_action: Option<Action>,
+    chat_lines: Vec<ChatLine>,
+    pings: Vec<Ping>,
+    is_chat_active: bool,
+    battle_log: BattleLog,
+    is_battle_log_visible: bool,
+    effects: Vec<Effect>,
     gold: f64,
+    economy: Option<Economy>,
     me: PlayerId,
+    token: Option<String>,
+    name: Option<String>,
+    profile: Option<String>,
+    is_admin: bool,
+    /// Set by the leave button of the lobby to close the client
+    is_leaving: bool,
+    palette: Palette,
 
     current_selected_waypoint: Option<Id>,
-    current_selected_squad: Option<Id>,
+    selection: Selection,
     is_modifier1: bool,
     is_modifier2: bool,
     sender: Option<Sender>,
@@ -64,9 +155,12 @@ pub struct Client {
 
     viewport: [f64; 2],
     camera: Camera,
+    minimap: Minimap,
 
     fps: usize,
-    fps_counter: FPSCounter
+    fps_counter: FPSCounter,
+    /// Average milliseconds spent to render a frame
+    frame_time: f64
 }
 
 impl Client {
@@ -98,13 +192,12 @@ impl Client {
             glium_text::FontTexture::ascii_character_list()
         ).unwrap();
 
-        let sectors_count = 128_u32;
-        let mut shape = (0..(sectors_count + 1))
-            .map(|sector| (sector as f32) * 2_f32 * ::std::f32::consts::PI / (sectors_count as f32))
-            .map(|angle| Vertex { position: [angle.cos(), angle.sin()] })
-            .collect::<Vec<_>>();
+        let circle_batch = CircleBatch::new(&display);
 
-        shape.insert(0, Vertex { position: [0_f32, 0_f32] });
+        let empty_quad = [Vertex { position: [0.0, 0.0] }; 4];
+        let box_vertex_buffer = glium::VertexBuffer::dynamic(&display, &empty_quad).unwrap();
+        let minimap_vertex_buffer = glium::VertexBuffer::dynamic(&display, &empty_quad).unwrap();
+        let camera_vertex_buffer = glium::VertexBuffer::dynamic(&display, &empty_quad).unwrap();
 
         let program = program!(&display,
             140 => {
@@ -146,9 +239,14 @@ impl Client {
             display,
 
             text_system,
-            font,
-
-            shape,
+            font: Rc::new(font),
+            squad_labels: HashMap::new(),
+
+            circle_batch,
+            lanes_vertex_buffer: None,
+            box_vertex_buffer,
+            minimap_vertex_buffer,
+            camera_vertex_buffer,
             program,
 
             rx: None,
@@ -156,13 +254,38 @@ impl Client {
             game_cursor,
 
             waypoints: HashMap::new(),
+            lanes: vec![],
             players: HashMap::new(),
             squads: HashMap::new(),
+            squad_interpolation: SquadInterpolation::new(),
+            match_info: None,
+            standings: None,
+            leaderboard: None,
+            diplomacy: Diplomacy::new(vec![], vec![]),
+            rules: None,
+            research_items: vec![],
+            is_research_visible: false,
+            controls: Controls::new(DEFAULT_CONTROLS_PATH),
+            is_controls_visible: false,
+            rebinding_action: None,
+            chat_lines: vec![],
+            pings: vec![],
+            is_chat_active: false,
+            battle_log: BattleLog::new(),
+            is_battle_log_visible: false,
+            effects: vec![],
             gold: 0.0,
+            economy: None,
             me: 0,
+            token: None,
+            name: None,
+            profile: None,
+            is_admin: false,
+            is_leaving: false,
+            palette: Palette::Default,
 
             current_selected_waypoint: None,
-            current_selected_squad: None,
+            selection: Selection::new(),
             is_modifier1: false,
             is_modifier2: false,
             sender: None,
@@ -171,13 +294,34 @@ impl Client {
 
             viewport: [WIDTH as f64, HEIGHT as f64],
             camera: Camera::new(),
+            minimap: Minimap::new(),
 
             fps: 0,
-            fps_counter: FPSCounter::new()
+            fps_counter: FPSCounter::new(),
+            frame_time: 0.0
         }
     }
 
-    pub fn run(&mut self, address: String) {
+    pub fn run(&mut self, address: String, token: Option<String>, name: Option<String>, profile: Option<String>, controls_path: Option<String>, is_colorblind: bool) {
+        self.token = token;
+        self.name = name;
+        self.profile = profile;
+        self.palette = if is_colorblind { Palette::Colorblind } else { Palette::Default };
+
+        let controls_path = controls_path.unwrap_or(DEFAULT_CONTROLS_PATH.to_string());
+
+        self.controls = match Controls::load(&controls_path) {
+            Ok(controls) => controls,
+            Err(error) => {
+                println!("Failed to load controls from {}, using defaults: {:?}", controls_path, error);
+                Controls::new(&controls_path)
+            }
+        };
+
+        for conflict in self.controls.conflicts() {
+            println!("Controls conflict: {}", conflict.describe());
+        }
+
         let (tx, rx) = channel::<Command>();
 
         self.rx = Some(rx);
@@ -202,14 +346,29 @@ impl Client {
                 }
             }
 
+            let frame_start = time::precise_time_s();
+
             self.render();
 
+            let render_time = time::precise_time_s() - frame_start;
+            self.frame_time += (render_time * 1000.0 - self.frame_time) * FRAME_TIME_SMOOTHING;
+
             self.update();
             self.update_game_ui();
 
-            self.camera.update(self.game_cursor.position(), &self.viewport, 0.05);
+            if self.is_leaving {
+                break 'main;
+            }
+
+            // Edge scrolling would move the camera while the cursor is on the minimap in the corner
+            if !self.minimap.contains(self.game_cursor.position()) {
+                self.camera.update(self.game_cursor.position(), &self.viewport, 0.05);
+            }
 
-            thread::sleep(Duration::from_millis(40));
+            let elapsed = time::precise_time_s() - frame_start;
+            if elapsed < FRAME_DURATION {
+                thread::sleep(Duration::from_millis(((FRAME_DURATION - elapsed) * 1000.0) as u64));
+            }
         }
     }
 
@@ -217,22 +376,26 @@ impl Client {
         const SPACE_COLOR: [f32; 4] = [0.015686275, 0.129411765, 0.250980392, 1.0];
 
         const SELECTION_COLOR:[f32; 4] = [0.0, 1.0, 0.0, 0.2];
+        const WEAPON_RANGE_COLOR:[f32; 4] = [1.0, 1.0, 1.0, 0.05];
+        const LANE_COLOR: [f32; 4] = [0.125490196, 0.752941176, 0.870588235, 0.3];
         const PLANET_COLOR:[f32; 4] = [0.125490196, 0.752941176, 0.870588235, 1.0];
         const MY_PLANET_COLOR: [f32; 4] = [0.87843137, 0.50588235, 0.35686275, 1.0];
         const ENEMY_PLANET_COLOR: [f32; 4] = [0.34901961, 0.08627451, 0.14117647, 1.0];
-        const MY_SQUAD_COLOR:[f32; 4] = [0.870588235, 0.850980392, 0.529411765, 1.0];
-        const ENEMY_SQUAD_COLOR: [f32; 4] = [0.87843137, 0.22352941, 0.35686275, 1.0];
-        const MY_TEXT_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 0.2];
-        const ENEMY_TEXT_COLOR: [f32; 4] = [0.87843137, 0.22352941, 0.35686275, 1.0];
+        const MINIMAP_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+        const MINIMAP_CAMERA_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+
+        self.squad_interpolation.apply(&mut self.squads);
 
         let mut frame = self.display.draw();
         frame.clear_color_srgb(SPACE_COLOR[0], SPACE_COLOR[1], SPACE_COLOR[2], SPACE_COLOR[3]);
 
-        let vertex_buffer = glium::VertexBuffer::new(&self.display, &self.shape).unwrap();
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan);
 
         let view = self.camera.view_matrix(&self.viewport);
 
+        let label_sin = (-self.camera.angle()).sin() as f32;
+        let label_cos = (-self.camera.angle()).cos() as f32;
+
         let params = glium::DrawParameters {
             blend: glium::draw_parameters::Blend::alpha_blending(),
             .. Default::default()
@@ -241,107 +404,249 @@ impl Client {
         let me = self.me;
 
         let current_selected_waypoint = self.current_selected_waypoint;
-        let current_selected_squad = self.current_selected_squad;
+
+        if let Some(ref lanes_vertex_buffer) = self.lanes_vertex_buffer {
+            let lanes_indices = glium::index::NoIndices(glium::index::PrimitiveType::LinesList);
+
+            let uniforms = uniform! {
+                matrix: [
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [0.0, 0.0, 0.0, 1.0f32],
+                ],
+                view: view,
+                color: LANE_COLOR
+            };
+
+            frame.draw(lanes_vertex_buffer, &lanes_indices, &self.program, &uniforms, &params).unwrap();
+        }
 
         for waypoint in self.waypoints.values() {
             let Position(waypoint_x, waypoint_y) = waypoint.position();
+            let center = [waypoint_x as f32, waypoint_y as f32];
 
             let waypoint_size = Self::get_waypoint_size(waypoint) as f32;
 
-            if let Some(current_selected_waypoint) = current_selected_waypoint {
-                if waypoint.id() == current_selected_waypoint {
-                    let selected_waypoint_size = (waypoint_size * 1.5).max(15.0);
-
-                    let uniforms = uniform! {
-                        matrix: [
-                            [selected_waypoint_size, 0.0, 0.0, 0.0],
-                            [0.0, selected_waypoint_size, 0.0, 0.0],
-                            [0.0, 0.0, selected_waypoint_size, 0.0],
-                            [waypoint_x as f32, waypoint_y as f32, 0.0, 1.0f32],
-                        ],
-                        view: view,
-                        color: SELECTION_COLOR
-                    };
+            if current_selected_waypoint == Some(waypoint.id()) {
+                let selected_waypoint_size = (waypoint_size * 1.5).max(15.0);
+                self.circle_batch.push_circle(center, selected_waypoint_size, SELECTION_COLOR);
+            }
 
-                    frame.draw(&vertex_buffer, &indices, &self.program, &uniforms, &params).unwrap();
-                }
+            if let Some((capturer, progress)) = waypoint.capture() {
+                let capture_color = self.get_player_color(capturer);
+                self.circle_batch.push_sector(center, waypoint_size + 6.0, progress as f32, capture_color);
             }
 
-            let uniforms = uniform! {
-                matrix: [
-                    [waypoint_size, 0.0, 0.0, 0.0],
-                    [0.0, waypoint_size, 0.0, 0.0],
-                    [0.0, 0.0, waypoint_size, 0.0],
-                    [waypoint_x as f32, waypoint_y as f32, 0.0, 1.0f32],
-                ],
-                view: view,
-                color: self.get_waypoint_color(waypoint)
-            };
+            if let Some(owner) = waypoint.owner() {
+                let outline_color = self.get_outline_color(owner);
+                self.circle_batch.push_circle(center, waypoint_size + 2.0, outline_color);
+            }
 
-            frame.draw(&vertex_buffer, &indices, &self.program, &uniforms, &params).unwrap();
+            let waypoint_color = self.get_waypoint_color(waypoint);
+            self.circle_batch.push_circle(center, waypoint_size, waypoint_color);
         }
 
         for squad in self.squads.values() {
             let Position(squad_x, squad_y) = squad.position();
+            let center = [squad_x as f32, squad_y as f32];
+
+            let squad_color = self.get_player_color(squad.owner());
+            let squad_size = Self::get_squad_size(squad);
+
+            // Player colors tell players apart, the outline under the squad tells allies from enemies
+            let outline_color = self.get_outline_color(squad.owner());
+            self.circle_batch.push_circle(center, squad_size + 1.5, outline_color);
+
+            self.circle_batch.push_circle(center, squad_size, squad_color);
+
+            if self.selection.contains(squad.id()) {
+                if let Some(ref rules) = self.rules {
+                    let weapon_range = rules.class(squad.class()).weapon_range as f32;
+                    self.circle_batch.push_circle(center, weapon_range, WEAPON_RANGE_COLOR);
+                }
+
+                self.circle_batch.push_circle(center, squad_size + 3.0, SELECTION_COLOR);
+            }
+        }
+
+        self.effects.retain(|effect| !effect.is_expired());
+
+        for effect in self.effects.iter() {
+            let Position(effect_x, effect_y) = effect.position();
+            self.circle_batch.push_circle([effect_x as f32, effect_y as f32], effect.size(), effect.color());
+        }
+
+        self.pings.retain(|ping| !ping.is_expired());
+
+        for ping in self.pings.iter() {
+            let Position(ping_x, ping_y) = ping.position();
+
+            // Pulses a few times per second while fading out
+            let age = ping.age();
+            let ping_size = 20.0 + 15.0 * (age * 4.0).fract() as f32;
+            let ping_alpha = (1.0 - age / chat::PING_DURATION).max(0.0) as f32;
+
+            let ping_color = self.get_player_color(ping.from());
+            self.circle_batch.push_circle([ping_x as f32, ping_y as f32], ping_size, [ping_color[0], ping_color[1], ping_color[2], 0.5 * ping_alpha]);
+        }
+
+        self.circle_batch.draw(&self.display, &mut frame, view, &params);
+
+        // Labels show squad sizes, which repeat a lot, so their text is laid out once per size
+        if self.squad_labels.len() > MAX_CACHED_SQUAD_LABELS {
+            self.squad_labels.clear();
+        }
+
+        let text_system = &self.text_system;
+        let font = &self.font;
+
+        // Zoomed out far enough, labels turn into specks costing a draw call each
+        let label_height = LABEL_SIZE * (view[0][0].powi(2) + view[0][1].powi(2)).sqrt() * self.viewport[1] as f32 / 2.0;
+        let squads = if label_height >= MIN_LABEL_HEIGHT { self.squads.values().collect::<Vec<_>>() } else { vec![] };
+
+        for squad in squads {
+            let Position(squad_x, squad_y) = squad.position();
+
+            let (offset_x, offset_y) = if squad.owner() == me { (16.0, 12.0) } else { (16.0, -12.0) };
+
+            // Labels are turned against the camera, so they stay upright on the same side of the squad
+            let text_x = (squad_x + (offset_x * label_cos - offset_y * label_sin) as f64) as f32;
+            let text_y = (squad_y + (offset_x * label_sin + offset_y * label_cos) as f64) as f32;
+
+            // Drawing a label is a draw call of its own, so labels out of the window are skipped
+            let ndc_x = view[0][0] * text_x + view[1][0] * text_y + view[3][0];
+            let ndc_y = view[0][1] * text_x + view[1][1] * text_y + view[3][1];
+
+            if ndc_x.abs() > 1.1 || ndc_y.abs() > 1.1 {
+                continue;
+            }
 
-            let squad_color = if squad.owner() == me { MY_SQUAD_COLOR } else { ENEMY_SQUAD_COLOR };
+            let squad_color = self.get_player_color(squad.owner());
+
+            let count = squad.count();
+            let text = self.squad_labels
+                .entry(count)
+                .or_insert_with(|| glium_text::TextDisplay::new(text_system, font.clone(), &format!("{}", count)));
+
+            let matrix = [
+                [LABEL_SIZE * label_cos, LABEL_SIZE * label_sin, 0.0, 0.0],
+                [-LABEL_SIZE * label_sin, LABEL_SIZE * label_cos, 0.0, 0.0],
+                [0.0, 0.0, LABEL_SIZE, 0.0],
+                [text_x, text_y, 0.0, 1.0]
+            ];
+
+            glium_text::draw(text, text_system, &mut frame, vecmath::col_mat4_mul(view, matrix), (squad_color[0], squad_color[1], squad_color[2], squad_color[3]));
+        }
+
+        if let Some((drag_start, drag_end)) = self.selection.drag_box(self.game_cursor.position()) {
+            let box_shape = self.get_window_box_corners(drag_start, drag_end)
+                .iter()
+                .map(|&(x, y)| Vertex { position: [x, y] })
+                .collect::<Vec<_>>();
+
+            self.box_vertex_buffer.write(&box_shape);
 
             let uniforms = uniform! {
                 matrix: [
-                    [5.0, 0.0, 0.0, 0.0],
-                    [0.0, 5.0, 0.0, 0.0],
-                    [0.0, 0.0, 5.0, 0.0],
-                    [squad_x as f32, squad_y as f32, 0.0, 1.0f32],
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [0.0, 0.0, 0.0, 1.0f32],
                 ],
                 view: view,
-                color: squad_color
+                color: SELECTION_COLOR
             };
 
-            frame.draw(&vertex_buffer, &indices, &self.program, &uniforms, &params).unwrap();
+            frame.draw(&self.box_vertex_buffer, &indices, &self.program, &uniforms, &params).unwrap();
 
+            let box_outline_indices = glium::index::NoIndices(glium::index::PrimitiveType::LineLoop);
+            frame.draw(&self.box_vertex_buffer, &box_outline_indices, &self.program, &uniforms, &params).unwrap();
+        }
 
-            if let Some(current_selected_squad) = current_selected_squad {
-                if squad.id() == current_selected_squad {
-                    let uniforms = uniform! {
-                        matrix: [
-                            [8.0, 0.0, 0.0, 0.0],
-                            [0.0, 8.0, 0.0, 0.0],
-                            [0.0, 0.0, 8.0, 0.0],
-                            [squad_x as f32, squad_y as f32, 0.0, 1.0f32],
-                        ],
-                        view: view,
-                        color: SELECTION_COLOR
-                    };
+        let identity_matrix = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0f32],
+        ];
+
+        let minimap_shape = self.minimap
+            .corners()
+            .iter()
+            .map(|&corner| Vertex { position: window_to_ndc(corner, &self.viewport) })
+            .collect::<Vec<_>>();
 
-                    frame.draw(&vertex_buffer, &indices, &self.program, &uniforms, &params).unwrap();
+        self.minimap_vertex_buffer.write(&minimap_shape);
+        let outline_indices = glium::index::NoIndices(glium::index::PrimitiveType::LineLoop);
 
-                }
-            }
+        let uniforms = uniform! {
+            matrix: identity_matrix,
+            view: identity_matrix,
+            color: MINIMAP_COLOR
+        };
 
-            let mut text_x = squad_x;
-            let mut text_y = squad_y;
+        frame.draw(&self.minimap_vertex_buffer, &indices, &self.program, &uniforms, &params).unwrap();
 
-            if squad.owner() == me {
-                text_x += 16.0;
-                text_y += 12.0;
-            } else {
-                text_x += 16.0;
-                text_y -= 12.0;
-            };
+        let uniforms = uniform! {
+            matrix: identity_matrix,
+            view: identity_matrix,
+            color: LANE_COLOR
+        };
 
-            let text_color = if squad.owner() == me { MY_TEXT_COLOR } else { ENEMY_TEXT_COLOR };
-            let text = glium_text::TextDisplay::new(&self.text_system, &self.font, &format!("{}", squad.count()));
+        frame.draw(&self.minimap_vertex_buffer, &outline_indices, &self.program, &uniforms, &params).unwrap();
 
-            let matrix = [
-                [20.0, 0.0, 0.0, 0.0],
-                [0.0, 20.0, 0.0, 0.0],
-                [0.0, 0.0, 20.0, 0.0],
-                [text_x as f32, text_y as f32, 0.0, 1.0]
-            ];
+        for waypoint in self.waypoints.values() {
+            let dot_size = if waypoint.waypoint_type() == WaypointType::Planet { 4.0 } else { 2.0 };
+            let (center, scale) = self.minimap_dot(waypoint.position(), dot_size);
 
-            glium_text::draw(&text, &self.text_system, &mut frame, vecmath::col_mat4_mul(view, matrix), (text_color[0], text_color[1], text_color[2], text_color[3]));
+            let waypoint_color = self.get_waypoint_color(waypoint);
+            self.circle_batch.push_ellipse(center, scale, waypoint_color);
         }
 
+        for squad in self.squads.values() {
+            let (center, scale) = self.minimap_dot(squad.position(), 1.5);
+
+            let (outline_center, outline_scale) = self.minimap_dot(squad.position(), 2.5);
+            let outline_color = self.get_outline_color(squad.owner());
+            self.circle_batch.push_ellipse(outline_center, outline_scale, outline_color);
+
+            let squad_color = self.get_player_color(squad.owner());
+            self.circle_batch.push_ellipse(center, scale, squad_color);
+        }
+
+        for ping in self.pings.iter() {
+            let ping_alpha = (1.0 - ping.age() / chat::PING_DURATION).max(0.0) as f32;
+            let ping_color = self.get_player_color(ping.from());
+
+            let (center, scale) = self.minimap_dot(ping.position(), 3.0 + 3.0 * (ping.age() * 4.0).fract());
+            self.circle_batch.push_ellipse(center, scale, [ping_color[0], ping_color[1], ping_color[2], 0.8 * ping_alpha]);
+        }
+
+        self.circle_batch.draw(&self.display, &mut frame, identity_matrix, &params);
+
+        let (viewport_width, viewport_height) = (self.viewport[0], self.viewport[1]);
+
+        let camera_shape = [(0.0, 0.0), (viewport_width, 0.0), (viewport_width, viewport_height), (0.0, viewport_height)]
+            .iter()
+            .map(|&corner| {
+                let (x, y) = self.camera.unproject(corner, &self.viewport);
+                let minimap_corner = self.minimap.to_window(Position(x as f64, y as f64));
+
+                Vertex { position: window_to_ndc(minimap_corner, &self.viewport) }
+            })
+            .collect::<Vec<_>>();
+
+        self.camera_vertex_buffer.write(&camera_shape);
+
+        let uniforms = uniform! {
+            matrix: identity_matrix,
+            view: identity_matrix,
+            color: MINIMAP_CAMERA_COLOR
+        };
+
+        frame.draw(&self.camera_vertex_buffer, &outline_indices, &self.program, &uniforms, &params).unwrap();
+
         self.game_ui.draw(&self.display, &mut frame);
 
         self.game_cursor.draw(&mut frame);
@@ -352,24 +657,80 @@ impl Client {
     }
 
     fn update(&mut self) {
-        if let Some(ref rx) = self.rx {
-            while let Ok(command) = rx.try_recv() {
-                match command {
-                    Command::Connect { sender } => {
-                        self.sender = Some(sender);
+        let commands = match self.rx {
+            Some(ref rx) => rx.try_iter().collect::<Vec<_>>(),
+            None => vec![]
+        };
+
+        for command in commands {
+            match command {
+                Command::Connect { sender } => {
+                    sender.send(json::format_hello_command(self.token.as_ref(), self.name.as_ref(), self.profile.as_ref()));
+                    self.sender = Some(sender);
+                }
+
+                Command::Rules { rules, .. } => {
+                    self.rules = Some(rules);
+                }
+
+                Command::Session { token, is_admin, .. } => {
+                    println!("Session token: {} (pass it with --token to reconnect)", token);
+                    self.is_admin = is_admin;
+                }
+
+                Command::Error { error, .. } => {
+                    println!("Server error: {}", error);
+                }
+
+                Command::Admin { output, .. } => {
+                    println!("{}", output);
+                }
+
+                Command::Leaderboard { entries, .. } => {
+                    self.leaderboard = Some(entries);
+                }
+
+                Command::Process { sender, tick, waypoints, lanes, players, squads, events, match_info, standings, diplomacy, chat_lines, pings, research_items, gold, economy, me } => {
+                    self.players = players;
+
+                    // Positions of destroyed squads are only known from the previous snapshot
+                    self.record_match_events(&events);
+
+                    self.waypoints = waypoints;
+                    self.minimap.fit(&self.waypoints);
+
+                    let (Position(min_x, min_y), Position(max_x, max_y)) = self.minimap.bounds();
+                    self.camera.set_bounds((min_x, min_y), (max_x, max_y));
+
+                    if lanes != self.lanes {
+                        self.lanes = lanes;
+                        self.lanes_vertex_buffer = self.create_lanes_vertex_buffer();
                     }
 
-                    Command::Process { sender, waypoints, players, squads, gold, me } => {
-                        self.waypoints = waypoints;
-                        self.players = players;
-                        self.squads = squads;
-                        self.gold = gold;
-                        self.me = me;
+                    let (tick_number, tick_time) = tick;
+                    self.squad_interpolation.push(tick_number, tick_time, &squads);
+                    self.squads = squads;
+
+                    let squads = &self.squads;
+                    self.selection.retain(|squad_id| squads.contains_key(&squad_id));
+                    self.match_info = Some(match_info);
+                    // Ratings change once the match is finished, so the leaderboard is asked for then
+                    if standings.is_some() && self.standings.is_none() {
+                        sender.send(json::format_leaderboard_command());
                     }
 
-                    _ => ()
+                    self.standings = standings;
+                    self.diplomacy = diplomacy;
+                    self.research_items = research_items;
+                    Self::add_chat_lines(&mut self.chat_lines, chat_lines);
+                    self.pings.extend(pings);
+                    self.gold = gold;
+                    self.economy = Some(economy);
+                    self.me = me;
                 }
-            };
+
+                _ => ()
+            }
         }
     }
 
@@ -382,102 +743,871 @@ impl Client {
             .filter(|&planet| planet.owner().map_or(false, |owner| owner == self.me))
             .count();
 
+        let income = self.economy
+            .as_ref()
+            .map_or(String::new(), |economy| economy.income_description());
+
+        let supply = self.economy
+            .as_ref()
+            .map_or(String::new(), |economy| economy.supply_description());
+
         let mut players_states = self.players
             .values()
-            .map(|player| format!("{}: {}", player.name(), player.state()))
+            .map(|player| {
+                match player.team() {
+                    Some(team) => format!("{} [{}]: {}", player.name(), team, player.state()),
+                    None => format!("{}: {}", player.name(), player.state())
+                }
+            })
             .collect::<Vec<_>>();
 
         players_states.sort();
 
-        self.game_ui.update(players_count, self.gold, planets_count, self.fps, players_states);
+        let match_description = self.match_info
+            .as_ref()
+            .map_or("".to_string(), |match_info| match_info.describe());
+
+        let costs = self.rules
+            .as_ref()
+            .map_or("".to_string(), |rules| {
+                format!(
+                    "Fighter: {}, Cruiser: {}",
+                    rules.class(SquadClass::Fighter).cost,
+                    rules.class(SquadClass::Cruiser).cost
+                )
+            });
+
+        let game_ui_state = self.get_game_ui_state();
+
+        let is_game = match game_ui_state {
+            GameUiState::Game => true,
+            _ => false
+        };
+
+        self.game_ui.set_state(game_ui_state);
+
+        let research_items = if self.is_research_visible && is_game { Some(&self.research_items) } else { None };
+        let controls_items = if self.is_controls_visible { Some(self.controls.items(self.rebinding_action)) } else { None };
+
+        let battle_log_lines = if self.is_battle_log_visible { Some(self.battle_log.last_lines(12)) } else { None };
+        let notification = self.battle_log.notification().cloned();
+
+        let selection_lines = if is_game { self.get_selection_lines() } else { None };
+        let tooltip = if is_game { self.get_tooltip_lines().map(|lines| (lines, self.game_cursor.position())) } else { None };
+
+        let visible_chat_lines_count = if self.is_chat_active { 12 } else { 6 };
+        let chat_lines = self.chat_lines
+            .iter()
+            .skip(self.chat_lines.len().saturating_sub(visible_chat_lines_count))
+            .map(|chat_line| chat_line.describe(&self.players))
+            .collect::<Vec<_>>();
+
+        let ui_events = self.game_ui.update(Hud {
+            players_count,
+            gold: self.gold,
+            income,
+            planets_count,
+            supply,
+            fps: self.fps,
+            frame_time: self.frame_time,
+            players_states,
+            match_description,
+            costs,
+            research_items,
+            controls_items: controls_items.as_ref(),
+            chat_lines,
+            is_chat_active: self.is_chat_active,
+            battle_log_lines,
+            notification,
+            selection_lines,
+            tooltip
+        });
+
+        for ui_event in ui_events {
+            match ui_event {
+                UiEvent::Research(tech) => {
+                    if let Some(ref sender) = self.sender {
+                        sender.send(json::format_research_command(&tech));
+                    }
+                },
+
+                UiEvent::Rebind(action) => {
+                    self.rebinding_action = Some(action);
+                },
+
+                UiEvent::Ready => {
+                    if let Some(ref sender) = self.sender {
+                        sender.send(json::format_ready_command());
+                    }
+                },
+
+                UiEvent::Color(color) => {
+                    if let Some(ref sender) = self.sender {
+                        sender.send(json::format_color_command(color));
+                    }
+                },
+
+                UiEvent::Leave => {
+                    if let Some(ref sender) = self.sender {
+                        sender.send(json::format_leave_command());
+                        sender.close(CloseCode::Normal);
+                    }
+
+                    self.is_leaving = true;
+                },
+
+                UiEvent::ReturnToLobby => {
+                    if let Some(ref sender) = self.sender {
+                        sender.send(json::format_lobby_command());
+                    }
+
+                    // Ratings of the next match are asked for again once it is finished
+                    self.leaderboard = None;
+                },
+
+                UiEvent::Chat(ref input) if self.is_admin && input.starts_with("/admin ") => {
+                    if let Some(ref sender) = self.sender {
+                        sender.send(json::format_admin_command(&input["/admin ".len()..]));
+                    }
+
+                    self.is_chat_active = false;
+                },
+
+                UiEvent::Chat(input) => {
+                    if let (Some(chat_input), Some(sender)) = (ChatInput::parse(&input, &self.players), self.sender.as_ref()) {
+                        sender.send(json::format_chat_command(chat_input.channel, chat_input.player_id, &chat_input.text));
+                    }
+
+                    self.is_chat_active = false;
+                }
+            }
+        }
     }
 
     fn process_input(&mut self, event: &glium::glutin::WindowEvent) {
-        for mapping in self.get_input_mapping() {
-            if let Some(game_event) = mapping(event) {
-                match game_event {
-                    GameEvent::ReadyToPlay => {
-                        let command_json = json::format_ready_command();
+        if let Some(action) = self.rebinding_action {
+            if self.capture_rebinding(action, event) {
+                return;
+            }
+        }
 
-                        if let Some(ref sender) = self.sender {
-                            sender.send(command_json);
-                        }
-                    },
+        let game_event = match input_mapping::map_input(event, &self.controls, self.is_modifier1, self.is_modifier2, self.is_chat_active) {
+            Some(game_event) => game_event,
+            None => return
+        };
+
+        match game_event {
+            GameEvent::ReadyToPlay => {
+                let command_json = json::format_ready_command();
+
+                if let Some(ref sender) = self.sender {
+                    sender.send(command_json);
+                }
+            },
+
+            GameEvent::Team(team) => {
+                let command_json = json::format_team_command(team);
+
+                if let Some(ref sender) = self.sender {
+                    sender.send(command_json);
+                }
+            },
 
-                    GameEvent::Cursor(x, y) => {
-                        self.game_cursor.set_position((x, y));
-                    },
+            GameEvent::ToggleResearch => {
+                self.is_research_visible = !self.is_research_visible;
+            },
+
+            GameEvent::ToggleBattleLog => {
+                self.is_battle_log_visible = !self.is_battle_log_visible;
+            },
+
+            GameEvent::ToggleControls => {
+                self.is_controls_visible = !self.is_controls_visible;
+                self.rebinding_action = None;
+            },
+
+            GameEvent::OpenChat => {
+                // Releases of camera keys and buttons are not mapped while chatting and would never stop it
+                self.camera.release_keys();
+                self.camera.end_drag();
+                self.is_chat_active = true;
+                self.game_ui.open_chat();
+            },
+
+            GameEvent::CloseChat => {
+                self.is_chat_active = false;
+            },
+
+            GameEvent::TogglePause => {
+                let is_paused = self.match_info.as_ref().map_or(false, |match_info| match_info.is_paused());
+
+                if let Some(ref sender) = self.sender {
+                    sender.send(json::format_pause_command(!is_paused));
+                }
+            },
+
+            GameEvent::SpeedUp => {
+                self.change_speed(2.0);
+            },
+
+            GameEvent::SlowDown => {
+                self.change_speed(0.5);
+            },
+
+            GameEvent::Ping => {
+                let (x, y) = self.cursor_world_coordinates();
+
+                if let Some(ref sender) = self.sender {
+                    sender.send(json::format_ping_command(Position(x as f64, y as f64)));
+                }
+            },
+
+            GameEvent::ProposeAlliance => {
+                self.propose_relation("alliance");
+            },
+
+            GameEvent::ProposeCeasefire => {
+                self.propose_relation("ceasefire");
+            },
+
+            GameEvent::BreakRelation => {
+                if let (Some(player_id), Some(sender)) = (self.find_selected_player(), self.sender.as_ref()) {
+                    sender.send(json::format_diplomacy_break_command(player_id));
+                }
+            },
+
+            GameEvent::Cursor(x, y) => {
+                self.game_cursor.set_position((x, y));
+
+                if self.camera.is_dragging() {
+                    self.camera.drag_to((x, y), &self.viewport);
+                }
+            },
+
+            GameEvent::SelectStart => {
+                let cursor_position = self.game_cursor.position();
+
+                if self.minimap.contains(cursor_position) {
+                    let Position(x, y) = self.minimap.to_world(cursor_position);
+                    self.camera.center_on((x, y), &self.viewport);
+                } else {
+                    self.select_waypoint();
+                    self.selection.start_drag(cursor_position);
+                }
+            },
+
+            GameEvent::SelectEnd => {
+                // Clicks on the minimap do not start a selection
+                if self.selection.is_dragging() {
+                    self.select_squads();
+                }
+            },
+
+            GameEvent::RecallGroup(group) => {
+                if self.selection.recall_group(group) {
+                    self.center_on_selection();
+                }
+            },
+
+            GameEvent::AssignGroup(group) => {
+                self.selection.assign_group(group);
+            },
+
+            GameEvent::SquadSpawn(class) => {
+                if let Some(waypoint_id) = self.current_selected_waypoint {
+                    if let Some(ref sender) = self.sender {
+                        let command_json = json::format_squad_spawn_command(waypoint_id, class);
+                        sender.send(command_json);
+                    }
+                }
+            },
 
-                    GameEvent::SelectStart => {
-                        self.select_waypoint();
-                        self.select_squad();
-                    },
+            // Held second modifier sends half of every selected squad
+            GameEvent::SquadMove => {
+                let squad_ids = self.get_my_selected_squad_ids();
 
-                    GameEvent::SquadSpawn => {
-                        if let Some(waypoint_id) = self.current_selected_waypoint {
-                            if let Some(ref sender) = self.sender {
-                                let command_json = json::format_squad_spawn_command(waypoint_id);
+                if !squad_ids.is_empty() {
+                    if let Some(waypoint) = self.find_waypoint_under_cursor() {
+                        if let Some(ref sender) = self.sender {
+                            for command_json in json::format_squad_move_commands(&squad_ids, waypoint.id(), self.is_modifier2) {
                                 sender.send(command_json);
                             }
                         }
-                    },
-
-                    GameEvent::SquadMove => {
-                        if let Some(squad_id) = self.current_selected_squad {
-                            if let Some(waypoint) = self.find_waypoint_under_cursor() {
-                                if let Some(ref sender) = self.sender {
-                                    let command_json = json::format_squad_move_command(squad_id, waypoint.id());
-                                    sender.send(command_json);
-                                }
-                            }
-                        }
-                    },
+                    }
+                }
+            },
 
-                    GameEvent::Modifier1Start => {
-                        self.is_modifier1 = true;
-                    },
+            GameEvent::Modifier1Start => {
+                self.is_modifier1 = true;
+            },
 
-                    GameEvent::Modifier1End => {
-                        self.is_modifier1 = false;
-                    },
+            GameEvent::Modifier1End => {
+                self.is_modifier1 = false;
+            },
 
-                    GameEvent::Modifier2Start => {
-                        self.is_modifier2 = true;
-                    },
+            GameEvent::Modifier2Start => {
+                self.is_modifier2 = true;
+            },
 
-                    GameEvent::Modifier2End => {
-                        self.is_modifier2 = false;
-                    },
+            GameEvent::Modifier2End => {
+                self.is_modifier2 = false;
+            },
+
+            GameEvent::ZoomIn => {
+                self.camera.zoom_in();
+            }
 
-                    GameEvent::ZoomIn => {
-                        self.camera.zoom_in();
+            GameEvent::ZoomOut => {
+                self.camera.zoom_out();
+            }
+
+            GameEvent::Zoom(lines) => {
+                self.camera.zoom_wheel(lines, self.game_cursor.position());
+            }
+
+            GameEvent::PanStart(direction) => {
+                self.camera.set_pan(direction, true);
+            }
+
+            GameEvent::PanEnd(direction) => {
+                self.camera.set_pan(direction, false);
+            }
+
+            GameEvent::RotateStart(direction) => {
+                self.camera.set_rotation(direction, true);
+            }
+
+            GameEvent::RotateEnd(direction) => {
+                self.camera.set_rotation(direction, false);
+            }
+
+            GameEvent::ResetRotation => {
+                self.camera.reset_rotation(&self.viewport);
+            }
+
+            GameEvent::DragViewStart => {
+                self.camera.start_drag(self.game_cursor.position());
+            }
+
+            GameEvent::DragViewEnd => {
+                self.camera.end_drag();
+            }
+
+            GameEvent::FocusSelection => {
+                self.center_on_selection();
+            }
+
+            GameEvent::Resize(width, height) => {
+                self.viewport = [width as f64, height as f64];
+            }
+
+            _ => { }
+        }
+    }
+
+    /// Takes the next key or mouse button with the held modifiers as the new chord of the action
+    fn capture_rebinding(&mut self, action: Action, event: &glium::glutin::WindowEvent) -> bool {
+        let (input, state) = match input_mapping::get_input(event) {
+            Some(input) => input,
+            None => return false
+        };
+
+        if input == Input::Key(VirtualKeyCode::Escape) {
+            if state == ElementState::Released {
+                self.rebinding_action = None;
+            }
+
+            return true;
+        }
+
+        // Modifiers keep working so they can be held as a part of the chord
+        if self.controls.is_modifier_input(input) && !action.is_modifier() {
+            return false;
+        }
+
+        if state == ElementState::Released {
+            let chord = if action.ignores_modifiers() {
+                Chord::new(input, false, false)
+            } else {
+                Chord::new(input, self.is_modifier1, self.is_modifier2)
+            };
+
+            self.controls.rebind(action, chord);
+            self.rebinding_action = None;
+
+            if let Err(error) = self.controls.save() {
+                println!("Failed to save controls: {:?}", error);
+            }
+        }
+
+        true
+    }
+
+    fn record_match_events(&mut self, events: &Vec<MatchEvent>) {
+        const EXPLOSION_COLOR: [f32; 4] = [1.0, 0.6, 0.2, 0.8];
+        const CAPTURE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+
+        for event in events {
+            match *event {
+                MatchEvent::SquadSpawned { owner, ref class, .. } => {
+                    let line = format!("{} deployed a {}", self.player_name(owner), class);
+                    self.battle_log.add(line);
+                },
+
+                MatchEvent::SquadDestroyed { squad_id, owner, killer } => {
+                    let line = match killer {
+                        Some(killer) => format!("{} destroyed a squad of {}", self.player_name(killer), self.player_name(owner)),
+                        None => format!("{} lost a squad", self.player_name(owner))
+                    };
+                    self.battle_log.add(line);
+
+                    if let Some(squad) = self.squads.get(&squad_id) {
+                        self.effects.push(Effect::new(squad.position(), 20.0, EXPLOSION_COLOR));
                     }
+                },
+
+                MatchEvent::SquadsMerged { squad_id, ref merged_squad_ids } => {
+                    let owner = self.squads.get(&squad_id).map(|squad| squad.owner());
 
-                    GameEvent::ZoomOut => {
-                        self.camera.zoom_out();
+                    if owner == Some(self.me) {
+                        let line = format!("{} squads merged", merged_squad_ids.len() + 1);
+                        self.battle_log.add(line);
                     }
+                },
 
-                    GameEvent::Resize(width, height) => {
-                        self.viewport = [width as f64, height as f64];
+                MatchEvent::WaypointCaptured { waypoint_id, owner, previous_owner } => {
+                    let waypoint = match self.waypoints.get(&waypoint_id) {
+                        Some(waypoint) => waypoint,
+                        None => continue
+                    };
+
+                    let waypoint_name = match waypoint.waypoint_type() {
+                        WaypointType::Planet => "planet",
+                        WaypointType::Planetoid => "planetoid",
+                        WaypointType::Asteroid => "asteroid",
+                        WaypointType::BlackHole => "black hole"
+                    };
+
+                    let line = match previous_owner {
+                        Some(previous_owner) => format!("{} captured a {} from {}", self.player_name(owner), waypoint_name, self.player_name(previous_owner)),
+                        None => format!("{} captured a {}", self.player_name(owner), waypoint_name)
+                    };
+
+                    if owner == self.me || previous_owner == Some(self.me) {
+                        self.battle_log.notify(line);
+                    } else {
+                        self.battle_log.add(line);
                     }
 
-                    _ => { }
+                    let effect_size = (Self::get_waypoint_size(waypoint) * 1.5) as f32;
+                    self.effects.push(Effect::new(waypoint.position(), effect_size, CAPTURE_COLOR));
+                },
+
+                MatchEvent::ResearchCompleted { ref tech } => {
+                    let title = self.research_items
+                        .iter()
+                        .find(|research_item| research_item.tech() == tech)
+                        .map_or(tech.clone(), |research_item| research_item.title().clone());
+
+                    self.battle_log.notify(format!("Research completed: {}", title));
+                },
+
+                MatchEvent::PlayerEliminated { player_id } => {
+                    let line = format!("{} has been eliminated", self.player_name(player_id));
+                    self.battle_log.notify(line);
+                },
+
+                MatchEvent::MatchFinished { ref winners } => {
+                    let winners_names = winners
+                        .iter()
+                        .map(|&winner| self.player_name(winner))
+                        .collect::<Vec<_>>();
+
+                    let line = if winners_names.is_empty() {
+                        "Match finished in a draw".to_string()
+                    } else {
+                        format!("Match finished, winners: {}", winners_names.join(", "))
+                    };
+
+                    self.battle_log.notify(line);
+                }
+            }
+        }
+    }
+
+    /// Lobby before the match, results once the player has won or lost and the game in between
+    fn get_game_ui_state(&self) -> GameUiState {
+        let my_state = self.players
+            .get(&self.me)
+            .map_or("pending", |player| player.state().as_str());
+
+        match my_state {
+            "pending" | "ready" => GameUiState::Lobby(self.get_lobby()),
+            "win" | "loose" => GameUiState::Results(self.get_results()),
+            _ => GameUiState::Game
+        }
+    }
+
+    fn get_lobby(&self) -> Lobby {
+        let mut players = self.players
+            .values()
+            .map(|player| {
+                let state = match player.state().as_str() {
+                    "pending" => "not ready",
+                    "ready" => "ready",
+                    _ => "viewing results"
+                };
+
+                LobbyPlayer {
+                    name: player.name().clone(),
+                    team: player.team(),
+                    color: self.get_player_color(player.id()),
+                    state: state.to_string(),
+                    is_me: player.id() == self.me
+                }
+            })
+            .collect::<Vec<_>>();
+
+        players.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let count_waypoints = |waypoint_type: WaypointType| {
+            self.waypoints
+                .values()
+                .filter(|waypoint| waypoint.waypoint_type() == waypoint_type)
+                .count()
+        };
+
+        let slots_count = count_waypoints(WaypointType::Planet);
+
+        let mut settings = vec![];
+
+        if let Some(ref match_info) = self.match_info {
+            settings.push(match_info.describe());
+        }
+
+        settings.push(format!(
+            "Map: {} planets, {} planetoids, {} asteroids, {} black holes",
+            slots_count,
+            count_waypoints(WaypointType::Planetoid),
+            count_waypoints(WaypointType::Asteroid),
+            count_waypoints(WaypointType::BlackHole)
+        ));
+
+        settings.push(format!("Hyperlanes: {}", if self.lanes.is_empty() { "off" } else { "on" }));
+
+        let my_color = self.players
+            .get(&self.me)
+            .map(|player| player.color());
+
+        let colors = (0..PLAYER_COLORS_COUNT)
+            .map(|color| {
+                LobbyColor {
+                    color_id: color,
+                    color: self.palette.player_color(color),
+                    is_mine: my_color == Some(color),
+                    is_taken: self.players.values().any(|player| player.id() != self.me && player.color() == color)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let is_ready = self.players
+            .get(&self.me)
+            .map_or(false, |player| player.state() == "ready");
+
+        Lobby {
+            players,
+            slots_count,
+            colors,
+            settings,
+            is_ready
+        }
+    }
+
+    fn get_results(&self) -> Results {
+        let is_win = self.players
+            .get(&self.me)
+            .map_or(false, |player| player.state() == "win");
+
+        let mut lines = vec![];
+
+        if let Some(ref match_info) = self.match_info {
+            let time = match_info.time() as u64;
+            lines.push(format!("Match time: {}:{:02}", time / 60, time % 60));
+        }
+
+        match self.standings {
+            Some(ref standings) => {
+                for standing in standings {
+                    let rating = match (standing.rating(), standing.rating_change()) {
+                        (Some(rating), Some(rating_change)) => format!(", rating {} ({:+})", rating, rating_change),
+                        _ => String::new()
+                    };
+
+                    lines.push(format!(
+                        "{}. {}{}: score {}, planets {}, squads {}, built {}, destroyed {}, captured {}{}",
+                        standing.place(),
+                        standing.name(),
+                        if standing.is_winner() { " (winner)" } else { "" },
+                        standing.score(),
+                        standing.planets_count(),
+                        standing.squads_count(),
+                        standing.squads_built(),
+                        standing.squads_destroyed(),
+                        standing.planets_captured(),
+                        rating
+                    ));
                 }
+            },
+
+            None => lines.push("The match goes on without you, standings follow once it is finished".to_string())
+        }
+
+        if let Some(ref leaderboard) = self.leaderboard {
+            if !leaderboard.is_empty() {
+                lines.push("Leaderboard".to_string());
+                lines.extend(leaderboard.iter().enumerate().map(|(index, entry)| entry.describe(index + 1)));
+            }
+        }
+
+        Results {
+            title: if is_win { "Victory".to_string() } else { "Defeat".to_string() },
+            lines,
+            is_match_finished: self.standings.is_some()
+        }
+    }
+
+    /// Selected squads, or the selected waypoint when no squad is selected
+    fn get_selection_lines(&self) -> Option<Vec<String>> {
+        let squads = self.selection
+            .squad_ids()
+            .iter()
+            .filter_map(|squad_id| self.squads.get(squad_id))
+            .collect::<Vec<_>>();
 
-                break;
+        match squads.len() {
+            0 => {
+                self.current_selected_waypoint
+                    .and_then(|waypoint_id| self.waypoints.get(&waypoint_id))
+                    .map(|waypoint| self.describe_waypoint(waypoint))
+            },
+
+            1 => Some(self.describe_squad(squads[0])),
+
+            count => {
+                let ships = squads.iter().fold(0, |ships, squad| ships + squad.count());
+                let firepower = squads.iter().fold(0.0, |firepower, squad| firepower + self.get_squad_firepower(squad));
+
+                Some(vec![
+                    format!("{} squads", count),
+                    format!("Ships: {}, firepower {:.1}/s", ships, firepower)
+                ])
             }
         }
     }
 
-    fn get_input_mapping(&self) -> Vec<fn(&glium::glutin::WindowEvent) -> Option<GameEvent>> {
+    /// Short description of the squad or waypoint under the cursor
+    fn get_tooltip_lines(&self) -> Option<Vec<String>> {
+        const TOOLTIP_LINES_COUNT: usize = 3;
+
+        if self.selection.is_dragging() || self.camera.is_dragging() {
+            return None;
+        }
+
+        let squad = if self.minimap.contains(self.game_cursor.position()) { None } else { self.find_squad_under_cursor() };
+
+        let lines = match squad {
+            Some(squad) => self.describe_squad(squad),
+            None => match self.find_waypoint_under_cursor() {
+                Some(waypoint) => self.describe_waypoint(waypoint),
+                None => return None
+            }
+        };
+
+        Some(lines.into_iter().take(TOOLTIP_LINES_COUNT).collect())
+    }
+
+    fn describe_squad(&self, squad: &Squad) -> Vec<String> {
+        let state = match squad.state() {
+            SquadState::InSpace => "In space".to_string(),
+
+            SquadState::Moving { destination } => {
+                match destination.and_then(|waypoint_id| self.waypoints.get(&waypoint_id)) {
+                    Some(waypoint) => format!("Moving to {}", self.describe_waypoint_name(waypoint)),
+                    None => "Moving".to_string()
+                }
+            },
+
+            SquadState::OnOrbit { waypoint_id } => {
+                match self.waypoints.get(&waypoint_id) {
+                    Some(waypoint) => format!("Orbiting {}", self.describe_waypoint_name(waypoint)),
+                    None => "Orbiting".to_string()
+                }
+            }
+        };
+
         vec![
-            input_mapping::map_squad_input,
-            input_mapping::map_planet_input,
-            input_mapping::map_root_input
+            format!("Squad of {}s", squad.class().name()),
+            format!("Owner: {}", self.describe_owner(squad.owner())),
+            format!("Ships: {}, firepower {:.1}/s", squad.count(), self.get_squad_firepower(squad)),
+            state
         ]
     }
 
+    fn describe_waypoint(&self, waypoint: &Waypoint) -> Vec<String> {
+        let mut lines = vec![
+            waypoint.waypoint_type().title().to_string(),
+            format!("Owner: {}", waypoint.owner().map_or("none".to_string(), |owner| self.describe_owner(owner)))
+        ];
+
+        if let Some((player_id, progress)) = waypoint.capture() {
+            lines.push(format!("Captured by {}: {:.0}%", self.describe_owner(player_id), progress * 100.0));
+        }
+
+        let mut orbiting_ships = BTreeMap::new();
+        for squad in self.squads.values() {
+            if let SquadState::OnOrbit { waypoint_id } = squad.state() {
+                if waypoint_id == waypoint.id() {
+                    *orbiting_ships.entry(squad.owner()).or_insert(0) += squad.count();
+                }
+            }
+        }
+
+        if !orbiting_ships.is_empty() {
+            let orbiting = orbiting_ships
+                .iter()
+                .map(|(&owner, ships)| format!("{} {}", self.describe_owner(owner), ships))
+                .collect::<Vec<_>>();
+
+            lines.push(format!("Ships on orbit: {}", utils::join(orbiting, ", ")));
+        }
+
+        if let Some(income) = self.get_waypoint_income(waypoint) {
+            if waypoint.owner() == Some(self.me) {
+                lines.push(format!("Income: {:+.2}/s", income));
+            } else {
+                lines.push(format!("Income if captured: {:+.2}/s", income));
+            }
+        }
+
+        if waypoint.waypoint_type() == WaypointType::Planet && waypoint.owner() == Some(self.me) {
+            for &class in [SquadClass::Fighter, SquadClass::Cruiser].iter() {
+                lines.push(format!("Spawn {}: {}", class.name(), self.describe_spawn_availability(class)));
+            }
+        }
+
+        lines
+    }
+
+    fn describe_waypoint_name(&self, waypoint: &Waypoint) -> String {
+        let title = waypoint.waypoint_type().title().to_lowercase();
+
+        match waypoint.owner() {
+            Some(owner) => format!("{} of {}", title, self.describe_owner(owner)),
+            None => title
+        }
+    }
+
+    fn describe_owner(&self, player_id: PlayerId) -> String {
+        if player_id == self.me {
+            "you".to_string()
+        } else if self.is_ally(player_id) {
+            format!("{} (ally)", self.player_name(player_id))
+        } else {
+            self.player_name(player_id)
+        }
+    }
+
+    fn get_squad_firepower(&self, squad: &Squad) -> f64 {
+        self.rules
+            .as_ref()
+            .map_or(0.0, |rules| rules.class(squad.class()).damage * squad.count() as f64)
+    }
+
+    /// Own planetoids share their income, which grows slower than their count
+    fn get_waypoint_income(&self, waypoint: &Waypoint) -> Option<f64> {
+        let rules = match self.rules {
+            Some(ref rules) => rules,
+            None => return None
+        };
+
+        // Researched mining multiplies income the same way on the server
+        let income_modifier = self.economy.as_ref().map_or(1.0, |economy| economy.income_modifier());
+
+        match waypoint.waypoint_type() {
+            WaypointType::Planet => Some(rules.planet_income * income_modifier),
+
+            WaypointType::Planetoid => {
+                let planetoids_count = self.waypoints
+                    .values()
+                    .filter(|other| other.waypoint_type() == WaypointType::Planetoid && other.owner() == Some(self.me))
+                    .count() as f64;
+
+                if waypoint.owner() == Some(self.me) {
+                    let planetoids_income = self.economy.as_ref().map_or(0.0, |economy| economy.planetoids_income());
+                    Some(planetoids_income / planetoids_count)
+                } else {
+                    Some(rules.income_rate * income_modifier * ((planetoids_count + 1.0).cbrt() - planetoids_count.cbrt()))
+                }
+            },
+
+            _ => None
+        }
+    }
+
+    /// Mirrors the checks the server makes before spawning a squad
+    fn describe_spawn_availability(&self, class: SquadClass) -> String {
+        let class_rules = match self.rules {
+            Some(ref rules) => rules.class(class),
+            None => return "unknown".to_string()
+        };
+
+        let is_unlocked = class == SquadClass::Fighter || self.research_items
+            .iter()
+            .any(|research_item| research_item.tech() == "cruisers" && research_item.is_completed());
+
+        let free_supply = self.economy.as_ref().map_or(0.0, |economy| economy.free_supply());
+
+        if !is_unlocked {
+            "needs research".to_string()
+        } else if self.gold <= class_rules.cost {
+            format!("needs {} gold", class_rules.cost)
+        } else if free_supply < class_rules.supply {
+            "no supply left".to_string()
+        } else {
+            format!("ready for {} gold", class_rules.cost)
+        }
+    }
+
+    fn player_name(&self, player_id: PlayerId) -> String {
+        self.players.get(&player_id)
+            .map_or("Unknown".to_string(), |player| player.name().clone())
+    }
+
+    /// Asks the server to multiply the current speed, the server clamps it to allowed bounds
+    fn change_speed(&self, factor: f64) {
+        let speed = self.match_info.as_ref().map_or(1.0, |match_info| match_info.speed());
+
+        if let Some(ref sender) = self.sender {
+            sender.send(json::format_speed_command(speed * factor));
+        }
+    }
+
+    fn add_chat_lines(chat_lines: &mut Vec<ChatLine>, new_chat_lines: Vec<ChatLine>) {
+        chat_lines.extend(new_chat_lines);
+
+        if chat_lines.len() > chat::MAX_CHAT_LINES {
+            let overflow = chat_lines.len() - chat::MAX_CHAT_LINES;
+            chat_lines.drain(0..overflow);
+        }
+    }
+
     fn find_waypoint_under_cursor(&self) -> Option<&Waypoint> {
+        let window_position = self.game_cursor.position();
+
+        if self.minimap.contains(window_position) {
+            return self.minimap.find_waypoint(&self.waypoints, window_position);
+        }
+
         let (x, y) = self.cursor_world_coordinates();
         let cursor_position = Position(x as f64, y as f64);
 
@@ -486,18 +1616,123 @@ impl Client {
             .find(|waypoint| cursor_position.distance_to(waypoint.position()) < Self::get_waypoint_size(waypoint).max(20.0))
     }
 
+    fn find_squad_under_cursor(&self) -> Option<&Squad> {
+        let (x, y) = self.cursor_world_coordinates();
+        let cursor_position = Position(x as f64, y as f64);
+
+        self.squads
+            .values()
+            .find(|squad| cursor_position.distance_to(squad.position()) < 10_f64)
+    }
+
     fn select_waypoint(&mut self) {
         self.current_selected_waypoint = self.find_waypoint_under_cursor().map(|waypoint| waypoint.id());
     }
 
-    fn select_squad(&mut self) {
-        let (x, y) = self.cursor_world_coordinates();
-        let cursor_position = Position(x as f64, y as f64);
+    /// Finishes a click or a box selection. Shift adds squads to the selection, Ctrl removes them.
+    fn select_squads(&mut self) {
+        let mode = if self.is_modifier1 {
+            SelectionMode::Add
+        } else if self.is_modifier2 {
+            SelectionMode::Remove
+        } else {
+            SelectionMode::Replace
+        };
+
+        let squad_ids = match self.selection.drag_box(self.game_cursor.position()) {
+            Some((drag_start, drag_end)) => self.find_my_squads_in_box(drag_start, drag_end),
 
-        self.current_selected_squad = self.squads
+            None => self.find_squad_under_cursor().map_or(vec![], |squad| vec![squad.id()])
+        };
+
+        self.selection.end_drag();
+        self.selection.select(squad_ids, mode);
+    }
+
+    /// Only own squads are picked by a box, enemies may still be selected one by one
+    fn find_my_squads_in_box(&self, drag_start: (f64, f64), drag_end: (f64, f64)) -> Vec<Id> {
+        let corners = self.get_window_box_corners(drag_start, drag_end);
+
+        self.squads
             .values()
-            .find(|squad| cursor_position.distance_to(squad.position()) < 10_f64)
-            .map(|squad| squad.id());
+            .filter(|squad| squad.owner() == self.me)
+            .filter(|squad| {
+                let Position(x, y) = squad.position();
+                is_inside_quad(&corners, (x as f32, y as f32))
+            })
+            .map(|squad| squad.id())
+            .collect()
+    }
+
+    /// Center and scale in normalized device coordinates of a minimap dot with the size in pixels
+    fn minimap_dot(&self, position: Position, size: f64) -> ([f32; 2], [f32; 2]) {
+        let center = window_to_ndc(self.minimap.to_window(position), &self.viewport);
+        let scale = [(size * 2.0 / self.viewport[0]) as f32, (size * 2.0 / self.viewport[1]) as f32];
+
+        (center, scale)
+    }
+
+    /// Segments between waypoints of every lane, `None` without lanes
+    fn create_lanes_vertex_buffer(&self) -> Option<glium::VertexBuffer<Vertex>> {
+        let lanes_shape = self.lanes
+            .iter()
+            .filter_map(|&(from, to)| {
+                match (self.waypoints.get(&from), self.waypoints.get(&to)) {
+                    (Some(from), Some(to)) => Some((from.position(), to.position())),
+                    _ => None
+                }
+            })
+            .flat_map(|(Position(from_x, from_y), Position(to_x, to_y))| {
+                vec![
+                    Vertex { position: [from_x as f32, from_y as f32] },
+                    Vertex { position: [to_x as f32, to_y as f32] }
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        if lanes_shape.is_empty() {
+            return None;
+        }
+
+        Some(glium::VertexBuffer::new(&self.display, &lanes_shape).unwrap())
+    }
+
+    /// World coordinates of the window rectangle corners, in order around the rectangle
+    fn get_window_box_corners(&self, drag_start: (f64, f64), drag_end: (f64, f64)) -> [(f32, f32); 4] {
+        [
+            self.camera.unproject(drag_start, &self.viewport),
+            self.camera.unproject((drag_end.0, drag_start.1), &self.viewport),
+            self.camera.unproject(drag_end, &self.viewport),
+            self.camera.unproject((drag_start.0, drag_end.1), &self.viewport)
+        ]
+    }
+
+    fn get_my_selected_squad_ids(&self) -> Vec<Id> {
+        self.selection
+            .squad_ids()
+            .iter()
+            .cloned()
+            .filter(|squad_id| self.squads.get(squad_id).map_or(false, |squad| squad.owner() == self.me))
+            .collect()
+    }
+
+    fn center_on_selection(&mut self) {
+        let positions = self.selection
+            .squad_ids()
+            .iter()
+            .filter_map(|squad_id| self.squads.get(squad_id))
+            .map(|squad| squad.position())
+            .collect::<Vec<_>>();
+
+        if positions.is_empty() {
+            return;
+        }
+
+        let count = positions.len() as f64;
+        let x = positions.iter().fold(0_f64, |x, position| x + position.0) / count;
+        let y = positions.iter().fold(0_f64, |y, position| y + position.1) / count;
+
+        self.camera.center_on((x, y), &self.viewport);
     }
 
     fn get_waypoint_size(waypoint: &Waypoint) -> f64 {
@@ -509,6 +1744,13 @@ impl Client {
         }
     }
 
+    fn get_squad_size(squad: &Squad) -> f32 {
+        match squad.class() {
+            SquadClass::Fighter => 5.0,
+            SquadClass::Cruiser => 7.0
+        }
+    }
+
     fn get_waypoint_color(&self, waypoint: &Waypoint) -> [f32; 4] {
         if waypoint.waypoint_type() == WaypointType::BlackHole {
             return color_from_rgb(0, 0, 0, 1.0);
@@ -516,11 +1758,69 @@ impl Client {
 
         waypoint.owner().map_or(
             color_from_rgb(255, 255, 255, 1.0),
-            |owner| if owner == self.me { color_from_rgb(164, 196, 0, 1.0) } else { color_from_rgb(229, 20, 0, 1.0) }
+            |owner| self.get_player_color(owner)
         )
     }
 
+    /// Color the server assigned to the player, shown with the palette chosen on the client
+    fn get_player_color(&self, player_id: PlayerId) -> [f32; 4] {
+        self.players
+            .get(&player_id)
+            .map_or(color_from_rgb(128, 128, 128, 1.0), |player| self.palette.player_color(player.color()))
+    }
+
+    /// Light for the player and allies, dark for enemies
+    fn get_outline_color(&self, player_id: PlayerId) -> [f32; 4] {
+        self.palette.outline_color(self.is_ally(player_id))
+    }
+
+    fn is_ally(&self, player_id: PlayerId) -> bool {
+        if player_id == self.me {
+            return true;
+        }
+
+        let my_team = self.players.get(&self.me).and_then(|player| player.team());
+        let player_team = self.players.get(&player_id).and_then(|player| player.team());
+
+        let is_same_team = match (my_team, player_team) {
+            (Some(my_team), Some(player_team)) => my_team == player_team,
+            _ => false
+        };
+
+        is_same_team || self.diplomacy.relation(self.me, player_id).map_or(false, |relation| relation == "alliance")
+    }
+
+    /// Owner of the selected squad or waypoint if it is another player
+    fn find_selected_player(&self) -> Option<PlayerId> {
+        let squad_owner = self.selection
+            .squad_ids()
+            .iter()
+            .filter_map(|squad_id| self.squads.get(squad_id))
+            .map(|squad| squad.owner())
+            .find(|&owner| owner != self.me);
+
+        let waypoint_owner = self.current_selected_waypoint
+            .and_then(|waypoint_id| self.waypoints.get(&waypoint_id))
+            .and_then(|waypoint| waypoint.owner());
+
+        squad_owner
+            .or(waypoint_owner)
+            .and_then(|owner| if owner != self.me { Some(owner) } else { None })
+    }
+
+    fn propose_relation(&self, relation: &str) {
+        if let (Some(player_id), Some(sender)) = (self.find_selected_player(), self.sender.as_ref()) {
+            let command_json = if self.diplomacy.has_proposal(player_id, relation) {
+                json::format_diplomacy_accept_command(player_id, relation)
+            } else {
+                json::format_diplomacy_propose_command(player_id, relation)
+            };
+
+            sender.send(command_json);
+        }
+    }
+
     fn cursor_world_coordinates(&self) -> (f32, f32) {
         self.camera.unproject(self.game_cursor.position(), &self.viewport)
     }
-}
+}
\ No newline at end of file
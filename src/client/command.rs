@@ -4,9 +4,18 @@ use ws::{Message, Sender};
 
 use common::{Id, ParseCommandError, ParseCommandResult, PlayerId};
 use common::to_command::ToCommand;
+use client::chat::{ChatLine, Ping};
+use client::diplomacy::Diplomacy;
+use client::economy::Economy;
 use client::json;
+use client::leaderboard::LeaderboardEntry;
+use client::match_event::MatchEvent;
+use client::match_info::MatchInfo;
 use client::player::Player;
+use client::research::ResearchItem;
+use client::rules::Rules;
 use client::squad::Squad;
+use client::standing::Standing;
 use client::waypoint::Waypoint;
 
 pub enum Command {
@@ -16,13 +25,53 @@ pub enum Command {
 
     Process {
         sender: Sender,
+        /// Snapshot number and server clock
+        tick: (u64, f64),
         waypoints: HashMap<Id, Waypoint>,
+        lanes: Vec<(Id, Id)>,
         players: HashMap<PlayerId, Player>,
         squads: HashMap<Id, Squad>,
+        events: Vec<MatchEvent>,
+        match_info: MatchInfo,
+        standings: Option<Vec<Standing>>,
+        diplomacy: Diplomacy,
+        chat_lines: Vec<ChatLine>,
+        pings: Vec<Ping>,
+        research_items: Vec<ResearchItem>,
         gold: f64,
+        economy: Economy,
         me: PlayerId
     },
 
+    Rules {
+        sender: Sender,
+        rules: Rules
+    },
+
+    /// Sent by the server once the connection has been authenticated
+    Session {
+        sender: Sender,
+        token: String,
+        is_admin: bool
+    },
+
+    Error {
+        sender: Sender,
+        error: String
+    },
+
+    /// Output of an admin command
+    Admin {
+        sender: Sender,
+        output: String
+    },
+
+    /// Best rated players kept by the server
+    Leaderboard {
+        sender: Sender,
+        entries: Vec<LeaderboardEntry>
+    },
+
     Disconnect {
         sender: Sender
     }
@@ -37,14 +86,44 @@ impl ToCommand for Command {
         let raw = message.as_text()
             .map_err(ParseCommandError::BrokenCommand)?;
 
-        let (waypoints, players, squads, me, gold) = json::parse_process_command(raw)?;
+        if let Some(rules) = json::parse_rules_command(raw)? {
+            return Ok(Command::Rules { sender, rules });
+        }
+
+        if let Some((token, is_admin)) = json::parse_session_command(raw)? {
+            return Ok(Command::Session { sender, token, is_admin });
+        }
+
+        if let Some(error) = json::parse_error_command(raw)? {
+            return Ok(Command::Error { sender, error });
+        }
+
+        if let Some(output) = json::parse_admin_command(raw)? {
+            return Ok(Command::Admin { sender, output });
+        }
+
+        if let Some(entries) = json::parse_leaderboard_command(raw)? {
+            return Ok(Command::Leaderboard { sender, entries });
+        }
+
+        let (tick, waypoints, lanes, players, squads, events, match_info, standings, diplomacy, chat_lines, pings, research_items, me, gold, economy) = json::parse_process_command(raw)?;
 
         let command = Command::Process {
             sender: sender,
+            tick: tick,
             waypoints: waypoints,
+            lanes: lanes,
             players: players,
             squads: squads,
+            events: events,
+            match_info: match_info,
+            standings: standings,
+            diplomacy: diplomacy,
+            chat_lines: chat_lines,
+            pings: pings,
+            research_items: research_items,
             gold: gold,
+            economy: economy,
             me: me
         };
 
@@ -54,4 +133,17 @@ impl ToCommand for Command {
     fn disconnect(sender: Sender) -> Self {
         Command::Disconnect { sender: sender }
     }
+
+    fn action(&self) -> &'static str {
+        match *self {
+            Command::Connect { .. } => "connect",
+            Command::Process { .. } => "process",
+            Command::Rules { .. } => "rules",
+            Command::Session { .. } => "session",
+            Command::Error { .. } => "error",
+            Command::Admin { .. } => "admin",
+            Command::Leaderboard { .. } => "leaderboard",
+            Command::Disconnect { .. } => "disconnect"
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,680 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+use glium::glutin::{ElementState, MouseButton, VirtualKeyCode};
+
+use client::camera::{PanDirection, RotationDirection};
+use client::game_event::GameEvent;
+use common::{ParseCommandError, SquadClass, TeamId};
+use common::utils;
+use common::utils::json;
+
+/// Controls file used when no path is given on the command line
+pub const DEFAULT_CONTROLS_PATH: &'static str = "controls.json";
+
+const CONTROL_GROUPS_COUNT: u8 = 9;
+const TEAMS_COUNT: TeamId = 4;
+
+const GROUP_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9
+];
+
+const TEAM_KEYS: [VirtualKeyCode; 4] = [VirtualKeyCode::F1, VirtualKeyCode::F2, VirtualKeyCode::F3, VirtualKeyCode::F4];
+
+/// Keys which may be written in the controls file, by their `VirtualKeyCode` names
+const KEYS: [VirtualKeyCode; 82] = [
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4, VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9, VirtualKeyCode::Key0,
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E,
+    VirtualKeyCode::F, VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J,
+    VirtualKeyCode::K, VirtualKeyCode::L, VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O,
+    VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R, VirtualKeyCode::S, VirtualKeyCode::T,
+    VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X, VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::F1, VirtualKeyCode::F2, VirtualKeyCode::F3, VirtualKeyCode::F4, VirtualKeyCode::F5,
+    VirtualKeyCode::F6, VirtualKeyCode::F7, VirtualKeyCode::F8, VirtualKeyCode::F9, VirtualKeyCode::F10,
+    VirtualKeyCode::F11, VirtualKeyCode::F12,
+    VirtualKeyCode::Escape, VirtualKeyCode::Tab, VirtualKeyCode::Space, VirtualKeyCode::Return, VirtualKeyCode::Back,
+    VirtualKeyCode::Insert, VirtualKeyCode::Delete, VirtualKeyCode::Home, VirtualKeyCode::End,
+    VirtualKeyCode::PageUp, VirtualKeyCode::PageDown, VirtualKeyCode::Pause,
+    VirtualKeyCode::Left, VirtualKeyCode::Up, VirtualKeyCode::Right, VirtualKeyCode::Down,
+    VirtualKeyCode::LShift, VirtualKeyCode::RShift, VirtualKeyCode::LControl, VirtualKeyCode::RControl,
+    VirtualKeyCode::LAlt, VirtualKeyCode::RAlt,
+    VirtualKeyCode::Comma, VirtualKeyCode::Period, VirtualKeyCode::Slash, VirtualKeyCode::Semicolon,
+    VirtualKeyCode::Apostrophe, VirtualKeyCode::LBracket, VirtualKeyCode::RBracket, VirtualKeyCode::Backslash,
+    VirtualKeyCode::Minus, VirtualKeyCode::Equals, VirtualKeyCode::Add, VirtualKeyCode::Subtract
+];
+
+#[derive(Debug)]
+pub enum LoadControlsError {
+    Io(io::Error),
+    Parse(ParseCommandError),
+    Invalid(String)
+}
+
+pub type LoadControlsResult<T> = Result<T, LoadControlsError>;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Action {
+    Select,
+    SquadMove,
+    Modifier1,
+    Modifier2,
+    SpawnFighter,
+    SpawnCruiser,
+    ReadyToPlay,
+    Team(TeamId),
+    LeaveTeam,
+    ProposeAlliance,
+    ProposeCeasefire,
+    BreakRelation,
+    ToggleResearch,
+    ToggleBattleLog,
+    ToggleControls,
+    OpenChat,
+    CloseChat,
+    Ping,
+    TogglePause,
+    SpeedUp,
+    SlowDown,
+    ZoomIn,
+    ZoomOut,
+    Pan(PanDirection),
+    Rotate(RotationDirection),
+    ResetRotation,
+    DragView,
+    FocusSelection,
+    RecallGroup(u8),
+    AssignGroup(u8)
+}
+
+impl Action {
+    /// Every bindable action in the order of the controls screen
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::Select,
+            Action::SquadMove,
+            Action::Modifier1,
+            Action::Modifier2,
+            Action::SpawnFighter,
+            Action::SpawnCruiser,
+            Action::ReadyToPlay
+        ];
+
+        for team in 1..TEAMS_COUNT + 1 {
+            actions.push(Action::Team(team));
+        }
+
+        actions.extend(vec![
+            Action::LeaveTeam,
+            Action::ProposeAlliance,
+            Action::ProposeCeasefire,
+            Action::BreakRelation,
+            Action::ToggleResearch,
+            Action::ToggleBattleLog,
+            Action::ToggleControls,
+            Action::OpenChat,
+            Action::CloseChat,
+            Action::Ping,
+            Action::TogglePause,
+            Action::SpeedUp,
+            Action::SlowDown,
+            Action::ZoomIn,
+            Action::ZoomOut,
+            Action::Pan(PanDirection::Left),
+            Action::Pan(PanDirection::Right),
+            Action::Pan(PanDirection::Up),
+            Action::Pan(PanDirection::Down),
+            Action::Rotate(RotationDirection::Counterclockwise),
+            Action::Rotate(RotationDirection::Clockwise),
+            Action::ResetRotation,
+            Action::DragView,
+            Action::FocusSelection
+        ]);
+
+        for group in 1..CONTROL_GROUPS_COUNT + 1 {
+            actions.push(Action::RecallGroup(group));
+        }
+
+        for group in 1..CONTROL_GROUPS_COUNT + 1 {
+            actions.push(Action::AssignGroup(group));
+        }
+
+        actions
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Self::all().into_iter().find(|action| action.name() == name)
+    }
+
+    /// Name of the action in the controls file
+    pub fn name(&self) -> String {
+        match *self {
+            Action::Select => "select".to_string(),
+            Action::SquadMove => "squad_move".to_string(),
+            Action::Modifier1 => "modifier1".to_string(),
+            Action::Modifier2 => "modifier2".to_string(),
+            Action::SpawnFighter => "spawn_fighter".to_string(),
+            Action::SpawnCruiser => "spawn_cruiser".to_string(),
+            Action::ReadyToPlay => "ready_to_play".to_string(),
+            Action::Team(team) => format!("team_{}", team),
+            Action::LeaveTeam => "leave_team".to_string(),
+            Action::ProposeAlliance => "propose_alliance".to_string(),
+            Action::ProposeCeasefire => "propose_ceasefire".to_string(),
+            Action::BreakRelation => "break_relation".to_string(),
+            Action::ToggleResearch => "toggle_research".to_string(),
+            Action::ToggleBattleLog => "toggle_battle_log".to_string(),
+            Action::ToggleControls => "toggle_controls".to_string(),
+            Action::OpenChat => "open_chat".to_string(),
+            Action::CloseChat => "close_chat".to_string(),
+            Action::Ping => "ping".to_string(),
+            Action::TogglePause => "toggle_pause".to_string(),
+            Action::SpeedUp => "speed_up".to_string(),
+            Action::SlowDown => "slow_down".to_string(),
+            Action::ZoomIn => "zoom_in".to_string(),
+            Action::ZoomOut => "zoom_out".to_string(),
+            Action::Pan(PanDirection::Left) => "pan_left".to_string(),
+            Action::Pan(PanDirection::Right) => "pan_right".to_string(),
+            Action::Pan(PanDirection::Up) => "pan_up".to_string(),
+            Action::Pan(PanDirection::Down) => "pan_down".to_string(),
+            Action::Rotate(RotationDirection::Counterclockwise) => "rotate_counterclockwise".to_string(),
+            Action::Rotate(RotationDirection::Clockwise) => "rotate_clockwise".to_string(),
+            Action::ResetRotation => "reset_rotation".to_string(),
+            Action::DragView => "drag_view".to_string(),
+            Action::FocusSelection => "focus_selection".to_string(),
+            Action::RecallGroup(group) => format!("recall_group_{}", group),
+            Action::AssignGroup(group) => format!("assign_group_{}", group)
+        }
+    }
+
+    pub fn title(&self) -> String {
+        let name = self.name().replace('_', " ");
+        name[..1].to_uppercase() + &name[1..]
+    }
+
+    pub fn is_modifier(&self) -> bool {
+        match *self {
+            Action::Modifier1 | Action::Modifier2 => true,
+            _ => false
+        }
+    }
+
+    /// The chat text box takes the keyboard, so only these actions work while it is open
+    fn is_chat_action(&self) -> bool {
+        match *self {
+            Action::Modifier1 | Action::Modifier2 | Action::CloseChat => true,
+            _ => false
+        }
+    }
+
+    fn is_game_action(&self) -> bool {
+        match *self {
+            Action::CloseChat => false,
+            _ => true
+        }
+    }
+
+    fn is_available(&self, is_chat_active: bool) -> bool {
+        if is_chat_active { self.is_chat_action() } else { self.is_game_action() }
+    }
+
+    /// Held modifiers change how these actions apply instead of choosing another action
+    pub fn ignores_modifiers(&self) -> bool {
+        match *self {
+            Action::Select | Action::SquadMove | Action::Modifier1 | Action::Modifier2 => true,
+            Action::Pan(_) | Action::Rotate(_) | Action::DragView => true,
+            _ => false
+        }
+    }
+
+    pub fn to_game_event(&self, state: ElementState) -> Option<GameEvent> {
+        match (*self, state) {
+            (Action::Select, ElementState::Pressed) => Some(GameEvent::SelectStart),
+            (Action::Select, ElementState::Released) => Some(GameEvent::SelectEnd),
+            (Action::Modifier1, ElementState::Pressed) => Some(GameEvent::Modifier1Start),
+            (Action::Modifier1, ElementState::Released) => Some(GameEvent::Modifier1End),
+            (Action::Modifier2, ElementState::Pressed) => Some(GameEvent::Modifier2Start),
+            (Action::Modifier2, ElementState::Released) => Some(GameEvent::Modifier2End),
+            (Action::Pan(direction), ElementState::Pressed) => Some(GameEvent::PanStart(direction)),
+            (Action::Pan(direction), ElementState::Released) => Some(GameEvent::PanEnd(direction)),
+            (Action::Rotate(direction), ElementState::Pressed) => Some(GameEvent::RotateStart(direction)),
+            (Action::Rotate(direction), ElementState::Released) => Some(GameEvent::RotateEnd(direction)),
+            (Action::DragView, ElementState::Pressed) => Some(GameEvent::DragViewStart),
+            (Action::DragView, ElementState::Released) => Some(GameEvent::DragViewEnd),
+
+            // The text box takes focus before the key is released
+            (Action::OpenChat, ElementState::Pressed) => Some(GameEvent::OpenChat),
+            (Action::OpenChat, ElementState::Released) => None,
+
+            (_, ElementState::Pressed) => None,
+
+            (Action::SquadMove, _) => Some(GameEvent::SquadMove),
+            (Action::SpawnFighter, _) => Some(GameEvent::SquadSpawn(SquadClass::Fighter)),
+            (Action::SpawnCruiser, _) => Some(GameEvent::SquadSpawn(SquadClass::Cruiser)),
+            (Action::ReadyToPlay, _) => Some(GameEvent::ReadyToPlay),
+            (Action::Team(team), _) => Some(GameEvent::Team(Some(team))),
+            (Action::LeaveTeam, _) => Some(GameEvent::Team(None)),
+            (Action::ProposeAlliance, _) => Some(GameEvent::ProposeAlliance),
+            (Action::ProposeCeasefire, _) => Some(GameEvent::ProposeCeasefire),
+            (Action::BreakRelation, _) => Some(GameEvent::BreakRelation),
+            (Action::ToggleResearch, _) => Some(GameEvent::ToggleResearch),
+            (Action::ToggleBattleLog, _) => Some(GameEvent::ToggleBattleLog),
+            (Action::ToggleControls, _) => Some(GameEvent::ToggleControls),
+            (Action::CloseChat, _) => Some(GameEvent::CloseChat),
+            (Action::Ping, _) => Some(GameEvent::Ping),
+            (Action::TogglePause, _) => Some(GameEvent::TogglePause),
+            (Action::SpeedUp, _) => Some(GameEvent::SpeedUp),
+            (Action::SlowDown, _) => Some(GameEvent::SlowDown),
+            (Action::ZoomIn, _) => Some(GameEvent::ZoomIn),
+            (Action::ZoomOut, _) => Some(GameEvent::ZoomOut),
+            (Action::ResetRotation, _) => Some(GameEvent::ResetRotation),
+            (Action::FocusSelection, _) => Some(GameEvent::FocusSelection),
+            (Action::RecallGroup(group), _) => Some(GameEvent::RecallGroup(group)),
+            (Action::AssignGroup(group), _) => Some(GameEvent::AssignGroup(group))
+        }
+    }
+
+    fn default_chords(&self) -> Vec<Chord> {
+        match *self {
+            Action::Select => vec![Chord::mouse(MouseButton::Left)],
+            Action::SquadMove => vec![Chord::mouse(MouseButton::Right)],
+            Action::Modifier1 => vec![Chord::key(VirtualKeyCode::LShift), Chord::key(VirtualKeyCode::RShift)],
+            Action::Modifier2 => vec![Chord::key(VirtualKeyCode::LControl), Chord::key(VirtualKeyCode::RControl)],
+            Action::SpawnFighter => vec![Chord::key(VirtualKeyCode::V)],
+            Action::SpawnCruiser => vec![Chord::key(VirtualKeyCode::C)],
+            Action::ReadyToPlay => vec![Chord::key(VirtualKeyCode::Space)],
+            Action::Team(team) => vec![Chord::key(TEAM_KEYS[team as usize - 1])],
+            Action::LeaveTeam => vec![Chord::key(VirtualKeyCode::F5)],
+            Action::ProposeAlliance => vec![Chord::key(VirtualKeyCode::Y)],
+            Action::ProposeCeasefire => vec![Chord::key(VirtualKeyCode::F)],
+            Action::BreakRelation => vec![Chord::key(VirtualKeyCode::B)],
+            Action::ToggleResearch => vec![Chord::key(VirtualKeyCode::R)],
+            Action::ToggleBattleLog => vec![Chord::key(VirtualKeyCode::L)],
+            Action::ToggleControls => vec![Chord::key(VirtualKeyCode::F10)],
+            Action::OpenChat => vec![Chord::key(VirtualKeyCode::Return)],
+            Action::CloseChat => vec![Chord::key(VirtualKeyCode::Escape)],
+            Action::Ping => vec![Chord::key(VirtualKeyCode::P)],
+            Action::TogglePause => vec![Chord::key(VirtualKeyCode::Pause)],
+            Action::SpeedUp => vec![Chord::key(VirtualKeyCode::PageUp)],
+            Action::SlowDown => vec![Chord::key(VirtualKeyCode::PageDown)],
+            Action::ZoomIn => vec![Chord::key(VirtualKeyCode::Z)],
+            Action::ZoomOut => vec![Chord::key(VirtualKeyCode::X)],
+            Action::Pan(PanDirection::Left) => vec![Chord::key(VirtualKeyCode::A), Chord::key(VirtualKeyCode::Left)],
+            Action::Pan(PanDirection::Right) => vec![Chord::key(VirtualKeyCode::D), Chord::key(VirtualKeyCode::Right)],
+            Action::Pan(PanDirection::Up) => vec![Chord::key(VirtualKeyCode::W), Chord::key(VirtualKeyCode::Up)],
+            Action::Pan(PanDirection::Down) => vec![Chord::key(VirtualKeyCode::S), Chord::key(VirtualKeyCode::Down)],
+            Action::Rotate(RotationDirection::Counterclockwise) => vec![Chord::key(VirtualKeyCode::Q)],
+            Action::Rotate(RotationDirection::Clockwise) => vec![Chord::key(VirtualKeyCode::E)],
+            Action::ResetRotation => vec![Chord::key(VirtualKeyCode::N)],
+            Action::DragView => vec![Chord::mouse(MouseButton::Middle)],
+            Action::FocusSelection => vec![Chord::key(VirtualKeyCode::Home)],
+            Action::RecallGroup(group) => vec![Chord::key(GROUP_KEYS[group as usize - 1])],
+            Action::AssignGroup(group) => vec![Chord::new(Input::Key(GROUP_KEYS[group as usize - 1]), false, true)]
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton)
+}
+
+impl Input {
+    fn parse(name: &str) -> Option<Input> {
+        match name {
+            "MouseLeft" => Some(Input::Mouse(MouseButton::Left)),
+            "MouseRight" => Some(Input::Mouse(MouseButton::Right)),
+            "MouseMiddle" => Some(Input::Mouse(MouseButton::Middle)),
+            _ if name.starts_with("Mouse") => name["Mouse".len()..].parse().ok().map(|button| Input::Mouse(MouseButton::Other(button))),
+            _ => KEYS.iter().find(|key| format!("{:?}", key) == name).map(|&key| Input::Key(key))
+        }
+    }
+
+    fn describe(&self) -> String {
+        match *self {
+            Input::Key(key) => format!("{:?}", key),
+            Input::Mouse(MouseButton::Left) => "MouseLeft".to_string(),
+            Input::Mouse(MouseButton::Right) => "MouseRight".to_string(),
+            Input::Mouse(MouseButton::Middle) => "MouseMiddle".to_string(),
+            Input::Mouse(MouseButton::Other(button)) => format!("Mouse{}", button)
+        }
+    }
+}
+
+/// Key or mouse button together with the modifiers which must be held, written as "Mod2+Key1"
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Chord {
+    pub input: Input,
+    pub modifier1: bool,
+    pub modifier2: bool
+}
+
+impl Chord {
+    pub fn new(input: Input, modifier1: bool, modifier2: bool) -> Chord {
+        Chord {
+            input,
+            modifier1,
+            modifier2
+        }
+    }
+
+    fn key(key: VirtualKeyCode) -> Chord {
+        Chord::new(Input::Key(key), false, false)
+    }
+
+    fn mouse(button: MouseButton) -> Chord {
+        Chord::new(Input::Mouse(button), false, false)
+    }
+
+    pub fn parse(text: &str) -> Option<Chord> {
+        let mut input = None;
+        let mut modifier1 = false;
+        let mut modifier2 = false;
+
+        for part in text.split('+').map(|part| part.trim()) {
+            match part {
+                "Mod1" => modifier1 = true,
+                "Mod2" => modifier2 = true,
+                _ if input.is_none() => match Input::parse(part) {
+                    Some(parsed_input) => input = Some(parsed_input),
+                    None => return None
+                },
+                _ => return None
+            }
+        }
+
+        input.map(|input| Chord::new(input, modifier1, modifier2))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+
+        if self.modifier1 {
+            parts.push("Mod1".to_string());
+        }
+
+        if self.modifier2 {
+            parts.push("Mod2".to_string());
+        }
+
+        parts.push(self.input.describe());
+
+        utils::join(parts, "+")
+    }
+
+    fn matches(&self, input: Input, modifier1: bool, modifier2: bool, ignores_modifiers: bool) -> bool {
+        self.input == input && (ignores_modifiers || (self.modifier1 == modifier1 && self.modifier2 == modifier2))
+    }
+}
+
+/// Two actions bound to chords which may be pressed at once in the same context
+pub struct Conflict {
+    pub first: Action,
+    pub second: Action,
+    pub chord: Chord
+}
+
+impl Conflict {
+    pub fn describe(&self) -> String {
+        format!("{} and {} are both bound to {}", self.first.title(), self.second.title(), self.chord.describe())
+    }
+}
+
+pub struct ControlsItem {
+    action: Action,
+    chords: Vec<Chord>,
+    is_conflicting: bool,
+    is_rebinding: bool
+}
+
+impl ControlsItem {
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    pub fn is_conflicting(&self) -> bool {
+        self.is_conflicting
+    }
+
+    pub fn is_rebinding(&self) -> bool {
+        self.is_rebinding
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_rebinding {
+            return format!("{}: press a key or mouse button, Escape cancels", self.action.title());
+        }
+
+        let chords = if self.chords.is_empty() {
+            "unbound".to_string()
+        } else {
+            utils::join(self.chords.iter().map(|chord| chord.describe()).collect(), ", ")
+        };
+
+        format!("{}: {}", self.action.title(), chords)
+    }
+}
+
+/// Bindings of actions to chords. Every action may be rebound by a JSON controls file, missing
+/// actions keep their default chords.
+pub struct Controls {
+    path: String,
+    bindings: Vec<(Action, Vec<Chord>)>
+}
+
+impl Controls {
+    pub fn new(path: &str) -> Controls {
+        Controls {
+            path: path.to_string(),
+            bindings: Action::all()
+                .into_iter()
+                .map(|action| (action, action.default_chords()))
+                .collect()
+        }
+    }
+
+    /// Missing file means default controls, it is created when something is rebound
+    pub fn load(path: &str) -> LoadControlsResult<Controls> {
+        let mut controls = Controls::new(path);
+        let mut raw = String::new();
+
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut raw)) {
+            Ok(_) => controls.parse(&raw)?,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => (),
+            Err(error) => return Err(LoadControlsError::Io(error))
+        }
+
+        Ok(controls)
+    }
+
+    /// Writes a temporary file first, so a crash never leaves the controls half written
+    pub fn save(&self) -> io::Result<()> {
+        let temporary_path = format!("{}.tmp", self.path);
+
+        File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(self.format().as_bytes()))?;
+
+        fs::rename(&temporary_path, &self.path)
+    }
+
+    /// First action bound to the input with exactly the held modifiers
+    pub fn find_action(&self, input: Input, modifier1: bool, modifier2: bool, is_chat_active: bool) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|&&(action, _)| action.is_available(is_chat_active))
+            .find(|&&(action, ref chords)| {
+                chords.iter().any(|chord| chord.matches(input, modifier1, modifier2, action.ignores_modifiers()))
+            })
+            .map(|&(action, _)| action)
+    }
+
+    pub fn is_modifier_input(&self, input: Input) -> bool {
+        self.bindings
+            .iter()
+            .any(|&(action, ref chords)| action.is_modifier() && chords.iter().any(|chord| chord.input == input))
+    }
+
+    pub fn rebind(&mut self, action: Action, chord: Chord) {
+        if let Some(binding) = self.bindings.iter_mut().find(|binding| binding.0 == action) {
+            binding.1 = vec![chord];
+        }
+    }
+
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = vec![];
+
+        for (i, &(first, ref first_chords)) in self.bindings.iter().enumerate() {
+            for &(second, ref second_chords) in self.bindings.iter().skip(i + 1) {
+                let is_same_context = (first.is_game_action() && second.is_game_action())
+                    || (first.is_chat_action() && second.is_chat_action());
+
+                if !is_same_context {
+                    continue;
+                }
+
+                let ignores_modifiers = first.ignores_modifiers() || second.ignores_modifiers();
+
+                for first_chord in first_chords {
+                    let is_conflicting = second_chords
+                        .iter()
+                        .any(|second_chord| first_chord.matches(second_chord.input, second_chord.modifier1, second_chord.modifier2, ignores_modifiers));
+
+                    if is_conflicting {
+                        conflicts.push(Conflict {
+                            first,
+                            second,
+                            chord: *first_chord
+                        });
+                    }
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    pub fn items(&self, rebinding_action: Option<Action>) -> Vec<ControlsItem> {
+        let conflicts = self.conflicts();
+
+        self.bindings
+            .iter()
+            .map(|&(action, ref chords)| {
+                ControlsItem {
+                    action,
+                    chords: chords.clone(),
+                    is_conflicting: conflicts.iter().any(|conflict| conflict.first == action || conflict.second == action),
+                    is_rebinding: rebinding_action == Some(action)
+                }
+            })
+            .collect()
+    }
+
+    fn parse(&mut self, raw: &str) -> LoadControlsResult<()> {
+        let json = json::parse_json(raw).map_err(LoadControlsError::Parse)?;
+        let params = json::parse_json_as_object(&json).map_err(LoadControlsError::Parse)?;
+        let bindings = json::parse_object_from_json_object(params, "bindings").map_err(LoadControlsError::Parse)?;
+
+        for (name, chords_json) in bindings {
+            let action = Action::from_name(name)
+                .ok_or_else(|| LoadControlsError::Invalid(format!("unknown action {}", name)))?;
+
+            let chords_json = chords_json
+                .as_array()
+                .ok_or_else(|| LoadControlsError::Parse(ParseCommandError::IncompatibleType(name.to_string())))?;
+
+            let mut chords = vec![];
+            for chord_json in chords_json {
+                let chord = chord_json
+                    .as_string()
+                    .and_then(Chord::parse)
+                    .ok_or_else(|| LoadControlsError::Invalid(format!("unknown chord {} of action {}", chord_json, name)))?;
+
+                chords.push(chord);
+            }
+
+            if let Some(binding) = self.bindings.iter_mut().find(|binding| binding.0 == action) {
+                binding.1 = chords;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn format(&self) -> String {
+        let bindings = self.bindings
+            .iter()
+            .map(|&(action, ref chords)| {
+                let chords = chords
+                    .iter()
+                    .map(|chord| format!(r#""{}""#, chord.describe()))
+                    .collect::<Vec<_>>();
+
+                format!(r#"    "{}": [{}]"#, action.name(), utils::join(chords, ", "))
+            })
+            .collect::<Vec<_>>();
+
+        format!("{{\n  \"bindings\": {{\n{}\n  }}\n}}\n", utils::join(bindings, ",\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glium::glutin::{MouseButton, VirtualKeyCode};
+
+    use super::{Action, Chord, Controls, Input};
+
+    #[test]
+    fn default_chords_round_trip() {
+        for action in Action::all() {
+            for chord in action.default_chords() {
+                assert_eq!(Chord::parse(&chord.describe()), Some(chord), "{} of {}", chord.describe(), action.name());
+            }
+        }
+    }
+
+    #[test]
+    fn chord_parsing() {
+        assert_eq!(Chord::parse("Mod1+Mod2+F"), Some(Chord::new(Input::Key(VirtualKeyCode::F), true, true)));
+        assert_eq!(Chord::parse("Mod2 + Mouse4"), Some(Chord::new(Input::Mouse(MouseButton::Other(4)), false, true)));
+        assert_eq!(Chord::parse("Mod1"), None);
+        assert_eq!(Chord::parse("A+B"), None);
+        assert_eq!(Chord::parse("Unknown"), None);
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let conflicts = Controls::new("").conflicts();
+        let descriptions = conflicts.iter().map(|conflict| conflict.describe()).collect::<Vec<_>>();
+
+        assert!(conflicts.is_empty(), "{:?}", descriptions);
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut controls = Controls::new("");
+        controls.rebind(Action::ZoomIn, Chord::new(Input::Key(VirtualKeyCode::V), false, false));
+
+        let conflicts = controls.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].first, Action::SpawnFighter);
+        assert_eq!(conflicts[0].second, Action::ZoomIn);
+
+        // Same input with other modifiers is another chord
+        controls.rebind(Action::ZoomIn, Chord::new(Input::Key(VirtualKeyCode::V), true, false));
+        assert!(controls.conflicts().is_empty());
+
+        // Unless one of the actions applies whatever modifiers are held
+        controls.rebind(Action::Ping, Chord::new(Input::Mouse(MouseButton::Right), true, false));
+        assert!(controls.conflicts().iter().any(|conflict| conflict.first == Action::SquadMove && conflict.second == Action::Ping));
+
+        // Chat and game actions are never active at once
+        let mut controls = Controls::new("");
+        controls.rebind(Action::CloseChat, Chord::new(Input::Key(VirtualKeyCode::V), false, false));
+        assert!(controls.conflicts().is_empty());
+    }
+}
\ No newline at end of file
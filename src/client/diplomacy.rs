@@ -0,0 +1,28 @@
+use common::PlayerId;
+
+pub struct Diplomacy {
+    relations: Vec<(PlayerId, PlayerId, String)>,
+    proposals: Vec<(PlayerId, String)>
+}
+
+impl Diplomacy {
+    pub fn new(relations: Vec<(PlayerId, PlayerId, String)>, proposals: Vec<(PlayerId, String)>) -> Diplomacy {
+        Diplomacy {
+            relations,
+            proposals
+        }
+    }
+
+    pub fn relation(&self, a: PlayerId, b: PlayerId) -> Option<&String> {
+        self.relations
+            .iter()
+            .find(|&&(first, second, _)| (first == a && second == b) || (first == b && second == a))
+            .map(|&(_, _, ref relation)| relation)
+    }
+
+    pub fn has_proposal(&self, from: PlayerId, relation: &str) -> bool {
+        self.proposals
+            .iter()
+            .any(|&(proposer, ref proposed_relation)| proposer == from && proposed_relation == relation)
+    }
+}
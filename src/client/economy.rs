@@ -0,0 +1,54 @@
+/// Player's gold income by source and supply usage as sent by the server
+pub struct Economy {
+    planetoids_income: f64,
+    planets_income: f64,
+    upkeep: f64,
+    /// Research multiplier of planetoids and planets income
+    income_modifier: f64,
+    supply_used: f64,
+    supply_cap: f64
+}
+
+impl Economy {
+    pub fn new(planetoids_income: f64, planets_income: f64, upkeep: f64, income_modifier: f64, supply_used: f64, supply_cap: f64) -> Economy {
+        Economy {
+            planetoids_income,
+            planets_income,
+            upkeep,
+            income_modifier,
+            supply_used,
+            supply_cap
+        }
+    }
+
+    pub fn planetoids_income(&self) -> f64 {
+        self.planetoids_income
+    }
+
+    pub fn income_modifier(&self) -> f64 {
+        self.income_modifier
+    }
+
+    /// Supply left for new squads
+    pub fn free_supply(&self) -> f64 {
+        self.supply_cap - self.supply_used
+    }
+
+    pub fn total_income(&self) -> f64 {
+        self.planetoids_income + self.planets_income - self.upkeep
+    }
+
+    pub fn income_description(&self) -> String {
+        format!(
+            "{:+.2}/s (planetoids {:+.2}, planets {:+.2}, upkeep {:+.2})",
+            self.total_income(),
+            self.planetoids_income,
+            self.planets_income,
+            -self.upkeep
+        )
+    }
+
+    pub fn supply_description(&self) -> String {
+        format!("Supply: {}/{}", self.supply_used, self.supply_cap)
+    }
+}
@@ -1,10 +1,28 @@
+use client::camera::{PanDirection, RotationDirection};
+use common::{SquadClass, TeamId};
+
 #[derive(Debug)]
 pub enum GameEvent {
     Cursor(f64, f64),
     SelectStart,
     SelectEnd,
+    RecallGroup(u8),
+    AssignGroup(u8),
     ReadyToPlay,
-    SquadSpawn,
+    Team(Option<TeamId>),
+    ProposeAlliance,
+    ProposeCeasefire,
+    BreakRelation,
+    ToggleResearch,
+    ToggleBattleLog,
+    ToggleControls,
+    OpenChat,
+    CloseChat,
+    Ping,
+    TogglePause,
+    SpeedUp,
+    SlowDown,
+    SquadSpawn(SquadClass),
     SquadMove,
     Modifier1Start,
     Modifier1End,
@@ -12,5 +30,15 @@ pub enum GameEvent {
     Modifier2End,
     ZoomIn,
     ZoomOut,
+    /// Mouse wheel lines, positive away from the user
+    Zoom(f64),
+    PanStart(PanDirection),
+    PanEnd(PanDirection),
+    RotateStart(RotationDirection),
+    RotateEnd(RotationDirection),
+    ResetRotation,
+    DragViewStart,
+    DragViewEnd,
+    FocusSelection,
     Resize(f64, f64)
 }
\ No newline at end of file
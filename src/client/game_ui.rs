@@ -1,9 +1,10 @@
 use std::env::current_dir;
 use std::time::Duration;
 
-use conrod::{Colorable, Positionable, Theme, Ui, UiBuilder, Widget};
+use conrod::{Colorable, Labelable, Positionable, Sizeable, Theme, Ui, UiBuilder, Widget};
 use conrod::color;
-use conrod::widget::{Canvas, Text};
+use conrod::widget::{Button, Canvas, Text, TextBox};
+use conrod::widget::text_box;
 use conrod::backend::glium::Renderer;
 use conrod::backend::winit;
 use conrod::image::Map;
@@ -13,6 +14,10 @@ use glium::{Display, Surface};
 use glium::glutin::WindowEvent;
 use glium::texture::Texture2d;
 
+use client::controls::{Action, ControlsItem};
+use client::research::ResearchItem;
+use common::{ColorId, TeamId};
+
 widget_ids! {
     pub struct UiIds {
         master,
@@ -25,15 +30,135 @@ widget_ids! {
         gold,
         planets,
         fps,
-        players[]
+        match_description,
+        costs,
+        players[],
+
+        lobby_panel,
+        lobby_title,
+        lobby_colors[],
+        lobby_players[],
+        lobby_color_title,
+        lobby_color_buttons[],
+        lobby_settings_title,
+        lobby_settings[],
+        ready_button,
+        leave_button,
+
+        results_panel,
+        results_title,
+        results_lines[],
+        return_to_lobby_button,
+
+        research_panel,
+        research_title,
+        research_items[],
+
+        controls_panel,
+        controls_title,
+        controls_items[],
+
+        chat_panel,
+        chat_lines[],
+        chat_input,
+
+        battle_log_panel,
+        battle_log_lines[],
+
+        selection_panel,
+        selection_lines[],
+
+        tooltip_panel,
+        tooltip_text,
+
+        notification
     }
 }
 
+pub enum UiEvent {
+    Research(String),
+    Rebind(Action),
+    Chat(String),
+    Ready,
+    Leave,
+    Color(ColorId),
+    ReturnToLobby
+}
+
+/// Screen shown over the map, follows the state of the player in the match
+pub enum GameUiState {
+    /// Before the match while players get ready
+    Lobby(Lobby),
+    Game,
+    /// After the player has won or lost
+    Results(Results)
+}
+
+pub struct LobbyPlayer {
+    pub name: String,
+    pub team: Option<TeamId>,
+    pub color: [f32; 4],
+    pub state: String,
+    pub is_me: bool
+}
+
+pub struct LobbyColor {
+    pub color_id: ColorId,
+    pub color: [f32; 4],
+    pub is_mine: bool,
+    /// Picked by another player
+    pub is_taken: bool
+}
+
+pub struct Lobby {
+    pub players: Vec<LobbyPlayer>,
+    /// Planets to start from, one for each player
+    pub slots_count: usize,
+    pub colors: Vec<LobbyColor>,
+    pub settings: Vec<String>,
+    pub is_ready: bool
+}
+
+pub struct Results {
+    pub title: String,
+    pub lines: Vec<String>,
+    /// The lobby of the next match opens only after the match is over for everyone
+    pub is_match_finished: bool
+}
+
+/// Everything shown by the header and the panels during a frame
+pub struct Hud<'a> {
+    pub players_count: usize,
+    pub gold: f64,
+    pub income: String,
+    pub planets_count: usize,
+    pub supply: String,
+    pub fps: usize,
+    /// Milliseconds
+    pub frame_time: f64,
+    pub players_states: Vec<String>,
+    pub match_description: String,
+    pub costs: String,
+    /// Panels are hidden when `None`
+    pub research_items: Option<&'a Vec<ResearchItem>>,
+    pub controls_items: Option<&'a Vec<ControlsItem>>,
+    pub chat_lines: Vec<String>,
+    pub is_chat_active: bool,
+    pub battle_log_lines: Option<Vec<String>>,
+    pub notification: Option<String>,
+    pub selection_lines: Option<Vec<String>>,
+    /// Lines along with the cursor position
+    pub tooltip: Option<(Vec<String>, (f64, f64))>
+}
+
 pub struct GameUi {
     ui: Ui,
     ui_ids: UiIds,
     ui_image_map: Map<Texture2d>,
     ui_renderer: Renderer,
+    chat_input: String,
+    is_chat_focus_pending: bool,
+    state: GameUiState
 }
 
 impl GameUi {
@@ -72,7 +197,10 @@ impl GameUi {
             ui,
             ui_ids,
             ui_image_map: Map::new(),
-            ui_renderer
+            ui_renderer,
+            chat_input: String::new(),
+            is_chat_focus_pending: false,
+            state: GameUiState::Game
         }
     }
 
@@ -83,25 +211,110 @@ impl GameUi {
         self.ui_renderer.draw(display, target, &self.ui_image_map).unwrap();
     }
 
-    pub fn update(&mut self, players_count: usize, gold: f64, planets_count: usize, fps: usize, players_states: Vec<String>) {
-        const HEADER_ITEMS_COUNT: usize = 8;
+    pub fn set_state(&mut self, state: GameUiState) {
+        self.state = state;
+    }
+
+    /// Clears the chat input and gives it keyboard focus once it is shown
+    pub fn open_chat(&mut self) {
+        self.chat_input.clear();
+        self.is_chat_focus_pending = true;
+    }
+
+    pub fn update(&mut self, hud: Hud) -> Vec<UiEvent> {
+        const RESEARCH_PANEL_WIDTH: f64 = 300.0;
+        const RESEARCH_ITEM_HEIGHT: f64 = 30.0;
+        const RESEARCH_ITEM_MARGIN: f64 = 6.0;
+
+        const CONTROLS_PANEL_WIDTH: f64 = 460.0;
+        const CONTROLS_PANEL_HEIGHT: f64 = 480.0;
+        const CONTROLS_ITEM_HEIGHT: f64 = 22.0;
+        const CONTROLS_ITEM_MARGIN: f64 = 4.0;
+
+        const BATTLE_LOG_PANEL_WIDTH: f64 = 420.0;
+
+        const SELECTION_PANEL_WIDTH: f64 = 260.0;
+        const SELECTION_LINE_HEIGHT: f64 = 18.0;
+        /// Below the minimap in the top left corner
+        const SELECTION_PANEL_TOP: f64 = 220.0;
+
+        const TOOLTIP_WIDTH: f64 = 240.0;
+        const TOOLTIP_LINE_HEIGHT: f64 = 16.0;
+        const TOOLTIP_OFFSET: f64 = 16.0;
+
+        const CHAT_PANEL_WIDTH: f64 = 420.0;
+        const CHAT_LINE_HEIGHT: f64 = 16.0;
+        const CHAT_INPUT_HEIGHT: f64 = 24.0;
+
+        const LOBBY_PANEL_WIDTH: f64 = 520.0;
+        const LOBBY_ROW_HEIGHT: f64 = 16.0;
+        const LOBBY_ROW_MARGIN: f64 = 8.0;
+        const LOBBY_BUTTON_WIDTH: f64 = 160.0;
+        const LOBBY_BUTTON_HEIGHT: f64 = 30.0;
+        const LOBBY_COLOR_SIZE: f64 = 24.0;
+
+        const RESULTS_PANEL_WIDTH: f64 = 760.0;
+        const RESULTS_LINE_HEIGHT: f64 = 14.0;
+        const RESULTS_LINE_MARGIN: f64 = 8.0;
+
+        const HEADER_ITEMS_COUNT: usize = 9;
         const HEADER_PADDING: f64 = 10.0;
+        const GOLD_ITEM_WIDTH: f64 = 380.0;
+        const PLANETS_ITEM_WIDTH: f64 = 200.0;
+
+        let Hud {
+            players_count,
+            gold,
+            income,
+            planets_count,
+            supply,
+            fps,
+            frame_time,
+            players_states,
+            match_description,
+            costs,
+            research_items,
+            controls_items,
+            chat_lines,
+            is_chat_active,
+            battle_log_lines,
+            notification,
+            selection_lines,
+            tooltip
+        } = hud;
+
+        // The text box may only capture keyboard after it has been set at least once
+        if is_chat_active && self.is_chat_focus_pending && self.ui.widget_graph().widget(self.ui_ids.chat_input).is_some() {
+            self.ui.keyboard_capture(self.ui_ids.chat_input);
+            self.is_chat_focus_pending = false;
+        }
 
         let mut ui = self.ui.set_widgets();
 
         self.ui_ids.header_items.resize(HEADER_ITEMS_COUNT, &mut ui.widget_id_generator());
         self.ui_ids.players.resize(players_count, &mut ui.widget_id_generator());
 
+        let is_game = match self.state {
+            GameUiState::Game => true,
+            _ => false
+        };
+
+        let mut ui_events = vec![];
+
         let mut header_items = vec![];
         for i in 0..HEADER_ITEMS_COUNT {
-            header_items.push(
-                (
-                    self.ui_ids.header_items[i],
-                    Canvas::new()
-                        .pad_left(HEADER_PADDING)
-                        .pad_right(HEADER_PADDING)
-                )
-            )
+            let header_item = Canvas::new()
+                .pad_left(HEADER_PADDING)
+                .pad_right(HEADER_PADDING);
+
+            // Gold with income breakdown and planets with supply need more room than the rest
+            let header_item = match i {
+                0 => header_item.length(GOLD_ITEM_WIDTH),
+                1 => header_item.length(PLANETS_ITEM_WIDTH),
+                _ => header_item
+            };
+
+            header_items.push((self.ui_ids.header_items[i], header_item))
         }
 
         Canvas::new()
@@ -117,30 +330,441 @@ impl GameUi {
             ])
             .set(self.ui_ids.master, &mut ui);
 
-        Text::new(&format!("Gold: {}", gold.floor()))
-            .color(color::LIGHT_BLUE)
-            .mid_left_of(self.ui_ids.header_items[0])
-            .set(self.ui_ids.gold, &mut ui);
+        if is_game {
+            Text::new(&format!("Gold: {} {}", gold.floor(), income))
+                .color(color::LIGHT_BLUE)
+                .mid_left_of(self.ui_ids.header_items[0])
+                .set(self.ui_ids.gold, &mut ui);
 
-        let planets_count = &format!("Planets: {}", planets_count);
+            let planets_count = &format!("Planets: {}, {}", planets_count, supply);
 
-        Text::new(planets_count)
-            .color(color::LIGHT_BLUE)
-            .mid_left_of(self.ui_ids.header_items[1])
-            .set(self.ui_ids.planets, &mut ui);
+            Text::new(planets_count)
+                .color(color::LIGHT_BLUE)
+                .mid_left_of(self.ui_ids.header_items[1])
+                .set(self.ui_ids.planets, &mut ui);
+        }
 
-        Text::new(&format!("FPS: {}", fps))
+        Text::new(&format!("FPS: {}, frame: {:.1} ms", fps, frame_time))
             .color(color::LIGHT_BLUE)
             .mid_left_of(self.ui_ids.header_items[2])
             .set(self.ui_ids.fps, &mut ui);
 
-        let players_states_slice = &players_states[0..players_states.len().min(4)];
-        for i in 0..players_states_slice.len() {
-            Text::new(&players_states_slice[i])
+        Text::new(&match_description)
+            .color(color::LIGHT_BLUE)
+            .mid_left_of(self.ui_ids.header_items[3])
+            .set(self.ui_ids.match_description, &mut ui);
+
+        if is_game {
+            Text::new(&costs)
+                .color(color::LIGHT_BLUE)
+                .mid_left_of(self.ui_ids.header_items[4])
+                .set(self.ui_ids.costs, &mut ui);
+
+            let players_states_slice = &players_states[0..players_states.len().min(4)];
+            for i in 0..players_states_slice.len() {
+                Text::new(&players_states_slice[i])
+                    .color(color::LIGHT_BLUE)
+                    .mid_left_of(self.ui_ids.header_items[HEADER_ITEMS_COUNT - players_states_slice.len() + i])
+                    .set(self.ui_ids.players[i], &mut ui);
+            }
+        }
+
+        if let GameUiState::Lobby(ref lobby) = self.state {
+            // Free slots are listed after the players
+            let rows_count = lobby.players.len().max(lobby.slots_count);
+
+            self.ui_ids.lobby_colors.resize(rows_count, &mut ui.widget_id_generator());
+            self.ui_ids.lobby_players.resize(rows_count, &mut ui.widget_id_generator());
+            self.ui_ids.lobby_settings.resize(lobby.settings.len(), &mut ui.widget_id_generator());
+
+            self.ui_ids.lobby_color_buttons.resize(lobby.colors.len(), &mut ui.widget_id_generator());
+
+            let lobby_panel_height = 150.0 + LOBBY_BUTTON_HEIGHT + LOBBY_COLOR_SIZE
+                + (rows_count + lobby.settings.len()) as f64 * (LOBBY_ROW_HEIGHT + LOBBY_ROW_MARGIN);
+
+            Canvas::new()
+                .w_h(LOBBY_PANEL_WIDTH, lobby_panel_height)
+                .mid_top_with_margin_on(self.ui_ids.body, 80.0)
+                .color(color::DARK_CHARCOAL.alpha(0.9))
+                .set(self.ui_ids.lobby_panel, &mut ui);
+
+            Text::new(&format!("Lobby, players {}/{}", lobby.players.len(), lobby.slots_count))
+                .color(color::LIGHT_BLUE)
+                .font_size(18)
+                .top_left_with_margins_on(self.ui_ids.lobby_panel, 10.0, 10.0)
+                .set(self.ui_ids.lobby_title, &mut ui);
+
+            for i in 0..rows_count {
+                let (player_color, description) = match lobby.players.get(i) {
+                    Some(player) => {
+                        let player_color = color::Color::Rgba(player.color[0], player.color[1], player.color[2], player.color[3]);
+                        let team = player.team.map_or(String::new(), |team| format!(" [team {}]", team));
+                        let me = if player.is_me { " (you)" } else { "" };
+
+                        (player_color, format!("{}{}{}: {}", player.name, me, team, player.state))
+                    },
+
+                    None => (color::CHARCOAL, "Open slot".to_string())
+                };
+
+                let swatch = Canvas::new()
+                    .w_h(LOBBY_ROW_HEIGHT, LOBBY_ROW_HEIGHT)
+                    .color(player_color);
+
+                let swatch = if i == 0 {
+                    swatch.down_from(self.ui_ids.lobby_title, 16.0)
+                } else {
+                    swatch.down_from(self.ui_ids.lobby_colors[i - 1], LOBBY_ROW_MARGIN)
+                };
+
+                swatch.set(self.ui_ids.lobby_colors[i], &mut ui);
+
+                Text::new(&description)
+                    .color(if i < lobby.players.len() { color::WHITE } else { color::GREY })
+                    .font_size(14)
+                    .right_from(self.ui_ids.lobby_colors[i], 10.0)
+                    .set(self.ui_ids.lobby_players[i], &mut ui);
+            }
+
+            let color_title = Text::new("Color")
+                .color(color::LIGHT_BLUE)
+                .font_size(14);
+
+            let color_title = if rows_count == 0 {
+                color_title.down_from(self.ui_ids.lobby_title, 16.0)
+            } else {
+                color_title.down_from(self.ui_ids.lobby_colors[rows_count - 1], 16.0)
+            };
+
+            color_title.set(self.ui_ids.lobby_color_title, &mut ui);
+
+            for (i, lobby_color) in lobby.colors.iter().enumerate() {
+                let alpha = if lobby_color.is_taken { 0.3 } else { 1.0 };
+                let button_color = color::Color::Rgba(lobby_color.color[0], lobby_color.color[1], lobby_color.color[2], alpha);
+
+                let button = Button::new()
+                    .label(if lobby_color.is_mine { "*" } else { "" })
+                    .label_font_size(14)
+                    .label_color(color::BLACK)
+                    .w_h(LOBBY_COLOR_SIZE, LOBBY_COLOR_SIZE)
+                    .color(button_color);
+
+                let button = if i == 0 {
+                    button.down_from(self.ui_ids.lobby_color_title, LOBBY_ROW_MARGIN)
+                } else {
+                    button.right_from(self.ui_ids.lobby_color_buttons[i - 1], LOBBY_ROW_MARGIN)
+                };
+
+                for _click in button.set(self.ui_ids.lobby_color_buttons[i], &mut ui) {
+                    if !lobby_color.is_taken && !lobby_color.is_mine {
+                        ui_events.push(UiEvent::Color(lobby_color.color_id));
+                    }
+                }
+            }
+
+            let settings_title = Text::new("Match settings")
+                .color(color::LIGHT_BLUE)
+                .font_size(14);
+
+            let settings_title = if lobby.colors.is_empty() {
+                settings_title.down_from(self.ui_ids.lobby_color_title, 16.0)
+            } else {
+                settings_title.down_from(self.ui_ids.lobby_color_buttons[0], 16.0)
+            };
+
+            settings_title.set(self.ui_ids.lobby_settings_title, &mut ui);
+
+            for i in 0..lobby.settings.len() {
+                let text = Text::new(&lobby.settings[i])
+                    .color(color::WHITE)
+                    .font_size(14);
+
+                let text = if i == 0 {
+                    text.down_from(self.ui_ids.lobby_settings_title, LOBBY_ROW_MARGIN)
+                } else {
+                    text.down_from(self.ui_ids.lobby_settings[i - 1], LOBBY_ROW_MARGIN)
+                };
+
+                text.set(self.ui_ids.lobby_settings[i], &mut ui);
+            }
+
+            let ready_button = Button::new()
+                .label(if lobby.is_ready { "Waiting for others" } else { "Ready" })
+                .label_font_size(14)
+                .w_h(LOBBY_BUTTON_WIDTH, LOBBY_BUTTON_HEIGHT)
+                .color(if lobby.is_ready { color::CHARCOAL } else { color::DARK_GREEN })
+                .bottom_left_with_margins_on(self.ui_ids.lobby_panel, 10.0, 10.0);
+
+            for _click in ready_button.set(self.ui_ids.ready_button, &mut ui) {
+                if !lobby.is_ready {
+                    ui_events.push(UiEvent::Ready);
+                }
+            }
+
+            let leave_button = Button::new()
+                .label("Leave")
+                .label_font_size(14)
+                .w_h(LOBBY_BUTTON_WIDTH, LOBBY_BUTTON_HEIGHT)
+                .color(color::DARK_RED)
+                .bottom_right_with_margins_on(self.ui_ids.lobby_panel, 10.0, 10.0);
+
+            for _click in leave_button.set(self.ui_ids.leave_button, &mut ui) {
+                ui_events.push(UiEvent::Leave);
+            }
+        }
+
+        if let GameUiState::Results(ref results) = self.state {
+            self.ui_ids.results_lines.resize(results.lines.len(), &mut ui.widget_id_generator());
+
+            let results_panel_height = 90.0 + LOBBY_BUTTON_HEIGHT
+                + results.lines.len() as f64 * (RESULTS_LINE_HEIGHT + RESULTS_LINE_MARGIN);
+
+            Canvas::new()
+                .w_h(RESULTS_PANEL_WIDTH, results_panel_height)
+                .mid_top_with_margin_on(self.ui_ids.body, 80.0)
+                .color(color::DARK_CHARCOAL.alpha(0.9))
+                .set(self.ui_ids.results_panel, &mut ui);
+
+            Text::new(&results.title)
                 .color(color::LIGHT_BLUE)
-                .mid_left_of(self.ui_ids.header_items[HEADER_ITEMS_COUNT - players_states_slice.len() + i])
-                .set(self.ui_ids.players[i], &mut ui);
+                .font_size(18)
+                .mid_top_with_margin_on(self.ui_ids.results_panel, 10.0)
+                .set(self.ui_ids.results_title, &mut ui);
+
+            for i in 0..results.lines.len() {
+                let text = Text::new(&results.lines[i])
+                    .color(color::WHITE)
+                    .font_size(14);
+
+                let text = if i == 0 {
+                    text.top_left_with_margins_on(self.ui_ids.results_panel, 50.0, 10.0)
+                } else {
+                    text.down_from(self.ui_ids.results_lines[i - 1], RESULTS_LINE_MARGIN)
+                };
+
+                text.set(self.ui_ids.results_lines[i], &mut ui);
+            }
+
+            let return_button = Button::new()
+                .label(if results.is_match_finished { "Return to lobby" } else { "Waiting for the match to finish" })
+                .label_font_size(14)
+                .w_h(2.0 * LOBBY_BUTTON_WIDTH, LOBBY_BUTTON_HEIGHT)
+                .color(if results.is_match_finished { color::DARK_GREEN } else { color::CHARCOAL })
+                .mid_bottom_with_margin_on(self.ui_ids.results_panel, 10.0);
+
+            for _click in return_button.set(self.ui_ids.return_to_lobby_button, &mut ui) {
+                if results.is_match_finished {
+                    ui_events.push(UiEvent::ReturnToLobby);
+                }
+            }
         }
+
+        if let Some(research_items) = research_items {
+            self.ui_ids.research_items.resize(research_items.len(), &mut ui.widget_id_generator());
+
+            let research_panel_height = 40.0 + research_items.len() as f64 * (RESEARCH_ITEM_HEIGHT + RESEARCH_ITEM_MARGIN);
+
+            Canvas::new()
+                .w_h(RESEARCH_PANEL_WIDTH, research_panel_height)
+                .top_right_with_margins_on(self.ui_ids.body, 10.0, 10.0)
+                .color(color::DARK_CHARCOAL.alpha(0.8))
+                .set(self.ui_ids.research_panel, &mut ui);
+
+            Text::new("Research")
+                .color(color::LIGHT_BLUE)
+                .top_left_with_margins_on(self.ui_ids.research_panel, 10.0, 10.0)
+                .set(self.ui_ids.research_title, &mut ui);
+
+            for (i, research_item) in research_items.iter().enumerate() {
+                let button = Button::new()
+                    .label(&research_item.describe())
+                    .label_font_size(12)
+                    .w_h(RESEARCH_PANEL_WIDTH - 20.0, RESEARCH_ITEM_HEIGHT)
+                    .color(if research_item.is_available() { color::LIGHT_CHARCOAL } else { color::CHARCOAL });
+
+                let button = if i == 0 {
+                    button.down_from(self.ui_ids.research_title, 10.0)
+                } else {
+                    button.down_from(self.ui_ids.research_items[i - 1], RESEARCH_ITEM_MARGIN)
+                };
+
+                for _click in button.set(self.ui_ids.research_items[i], &mut ui) {
+                    if research_item.is_available() {
+                        ui_events.push(UiEvent::Research(research_item.tech().clone()));
+                    }
+                }
+            }
+        }
+
+        if let Some(controls_items) = controls_items {
+            self.ui_ids.controls_items.resize(controls_items.len(), &mut ui.widget_id_generator());
+
+            Canvas::new()
+                .w_h(CONTROLS_PANEL_WIDTH, CONTROLS_PANEL_HEIGHT)
+                .mid_top_with_margin_on(self.ui_ids.body, 60.0)
+                .color(color::DARK_CHARCOAL.alpha(0.9))
+                .scroll_kids_vertically()
+                .set(self.ui_ids.controls_panel, &mut ui);
+
+            Text::new("Controls, click an action to rebind it")
+                .color(color::LIGHT_BLUE)
+                .top_left_with_margins_on(self.ui_ids.controls_panel, 10.0, 10.0)
+                .set(self.ui_ids.controls_title, &mut ui);
+
+            for (i, controls_item) in controls_items.iter().enumerate() {
+                let color = if controls_item.is_rebinding() {
+                    color::DARK_BLUE
+                } else if controls_item.is_conflicting() {
+                    color::DARK_RED
+                } else {
+                    color::LIGHT_CHARCOAL
+                };
+
+                let button = Button::new()
+                    .label(&controls_item.describe())
+                    .label_font_size(12)
+                    .w_h(CONTROLS_PANEL_WIDTH - 30.0, CONTROLS_ITEM_HEIGHT)
+                    .color(color)
+                    .parent(self.ui_ids.controls_panel);
+
+                let button = if i == 0 {
+                    button.down_from(self.ui_ids.controls_title, 10.0)
+                } else {
+                    button.down_from(self.ui_ids.controls_items[i - 1], CONTROLS_ITEM_MARGIN)
+                };
+
+                for _click in button.set(self.ui_ids.controls_items[i], &mut ui) {
+                    ui_events.push(UiEvent::Rebind(controls_item.action()));
+                }
+            }
+        }
+
+        if let Some(notification) = notification {
+            Text::new(&notification)
+                .color(color::YELLOW)
+                .font_size(18)
+                .mid_top_with_margin_on(self.ui_ids.body, 40.0)
+                .set(self.ui_ids.notification, &mut ui);
+        }
+
+        if let Some(battle_log_lines) = battle_log_lines {
+            self.ui_ids.battle_log_lines.resize(battle_log_lines.len(), &mut ui.widget_id_generator());
+
+            let battle_log_panel_height = 20.0 + battle_log_lines.len() as f64 * CHAT_LINE_HEIGHT;
+
+            Canvas::new()
+                .w_h(BATTLE_LOG_PANEL_WIDTH, battle_log_panel_height)
+                .bottom_right_with_margins_on(self.ui_ids.body, 10.0, 10.0)
+                .color(color::DARK_CHARCOAL.alpha(0.6))
+                .set(self.ui_ids.battle_log_panel, &mut ui);
+
+            for i in 0..battle_log_lines.len() {
+                let text = Text::new(&battle_log_lines[i])
+                    .color(color::LIGHT_GREY)
+                    .font_size(12);
+
+                let text = if i == 0 {
+                    text.top_left_with_margins_on(self.ui_ids.battle_log_panel, 10.0, 10.0)
+                } else {
+                    text.down_from(self.ui_ids.battle_log_lines[i - 1], 4.0)
+                };
+
+                text.set(self.ui_ids.battle_log_lines[i], &mut ui);
+            }
+        }
+
+        if let Some(selection_lines) = selection_lines {
+            self.ui_ids.selection_lines.resize(selection_lines.len(), &mut ui.widget_id_generator());
+
+            let selection_panel_height = 20.0 + selection_lines.len() as f64 * SELECTION_LINE_HEIGHT;
+
+            Canvas::new()
+                .w_h(SELECTION_PANEL_WIDTH, selection_panel_height)
+                .top_left_with_margins_on(self.ui_ids.body, SELECTION_PANEL_TOP, 10.0)
+                .color(color::DARK_CHARCOAL.alpha(0.8))
+                .set(self.ui_ids.selection_panel, &mut ui);
+
+            for i in 0..selection_lines.len() {
+                // The first line names the selected entity
+                let text = Text::new(&selection_lines[i])
+                    .color(if i == 0 { color::LIGHT_BLUE } else { color::WHITE })
+                    .font_size(12);
+
+                let text = if i == 0 {
+                    text.top_left_with_margins_on(self.ui_ids.selection_panel, 10.0, 10.0)
+                } else {
+                    text.down_from(self.ui_ids.selection_lines[i - 1], 5.0)
+                };
+
+                text.set(self.ui_ids.selection_lines[i], &mut ui);
+            }
+        }
+
+        if let Some((tooltip_lines, (cursor_x, cursor_y))) = tooltip {
+            let tooltip_height = 12.0 + tooltip_lines.len() as f64 * TOOLTIP_LINE_HEIGHT;
+
+            // Conrod puts the origin in the middle of the window with y pointing up
+            let width = ui.win_w;
+            let height = ui.win_h;
+            let x = (cursor_x + TOOLTIP_OFFSET + TOOLTIP_WIDTH / 2.0).min(width - TOOLTIP_WIDTH / 2.0) - width / 2.0;
+            let y = height / 2.0 - (cursor_y + TOOLTIP_OFFSET + tooltip_height / 2.0).min(height - tooltip_height / 2.0);
+
+            Canvas::new()
+                .w_h(TOOLTIP_WIDTH, tooltip_height)
+                .x_y(x, y)
+                .color(color::BLACK.alpha(0.8))
+                .set(self.ui_ids.tooltip_panel, &mut ui);
+
+            Text::new(&tooltip_lines.join("\n"))
+                .color(color::WHITE)
+                .font_size(12)
+                .line_spacing(4.0)
+                .top_left_with_margins_on(self.ui_ids.tooltip_panel, 6.0, 8.0)
+                .set(self.ui_ids.tooltip_text, &mut ui);
+        }
+
+        self.ui_ids.chat_lines.resize(chat_lines.len(), &mut ui.widget_id_generator());
+
+        let chat_panel_height = 20.0 + chat_lines.len() as f64 * CHAT_LINE_HEIGHT + if is_chat_active { CHAT_INPUT_HEIGHT } else { 0.0 };
+
+        if !chat_lines.is_empty() || is_chat_active {
+            Canvas::new()
+                .w_h(CHAT_PANEL_WIDTH, chat_panel_height)
+                .bottom_left_with_margins_on(self.ui_ids.body, 10.0, 10.0)
+                .color(color::DARK_CHARCOAL.alpha(0.6))
+                .set(self.ui_ids.chat_panel, &mut ui);
+        }
+
+        for i in 0..chat_lines.len() {
+            let text = Text::new(&chat_lines[i])
+                .color(color::WHITE)
+                .font_size(12);
+
+            let text = if i == 0 {
+                text.top_left_with_margins_on(self.ui_ids.chat_panel, 10.0, 10.0)
+            } else {
+                text.down_from(self.ui_ids.chat_lines[i - 1], 4.0)
+            };
+
+            text.set(self.ui_ids.chat_lines[i], &mut ui);
+        }
+
+        if is_chat_active {
+            let text_box_events = TextBox::new(&self.chat_input)
+                .w_h(CHAT_PANEL_WIDTH - 20.0, CHAT_INPUT_HEIGHT - 4.0)
+                .mid_bottom_with_margin_on(self.ui_ids.chat_panel, 6.0)
+                .font_size(12)
+                .set(self.ui_ids.chat_input, &mut ui);
+
+            for text_box_event in text_box_events {
+                match text_box_event {
+                    text_box::Event::Update(text) => self.chat_input = text,
+                    text_box::Event::Enter => ui_events.push(UiEvent::Chat(self.chat_input.clone()))
+                }
+            }
+        }
+
+        ui_events
     }
 
     pub fn process_event(&mut self, display: &Display, event: WindowEvent) {
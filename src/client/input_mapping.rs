@@ -1,69 +1,42 @@
-use glium::glutin::{WindowEvent, ElementState, MouseButton, KeyboardInput, VirtualKeyCode};
+use glium::glutin::{WindowEvent, ElementState, KeyboardInput, MouseScrollDelta};
 
+use client::controls::{Controls, Input};
 use client::game_event::GameEvent;
 
-pub fn map_root_input(event: &WindowEvent) -> Option<GameEvent> {
+/// Pixels of a touchpad scroll counted as one wheel line
+const PIXELS_PER_LINE: f64 = 40.0;
+
+/// Cursor moves, wheel and resizes are always mapped, keys and mouse buttons are looked up in the controls
+pub fn map_input(event: &WindowEvent, controls: &Controls, modifier1: bool, modifier2: bool, is_chat_active: bool) -> Option<GameEvent> {
     match *event {
         WindowEvent::MouseMoved { position, .. } => Some(GameEvent::Cursor(position.0, position.1)),
 
-        WindowEvent::MouseInput { button: MouseButton::Left, state: ElementState::Pressed, .. } => Some(GameEvent::SelectStart),
-            WindowEvent::MouseInput { button: MouseButton::Left, state: ElementState::Released, .. } => Some(GameEvent::SelectEnd),
-
-        WindowEvent::KeyboardInput {
-            input: KeyboardInput {
-                state: ElementState::Released,
-                virtual_keycode: Some(VirtualKeyCode::Space),
-                ..
-            },
-            ..
-        } => Some(GameEvent::ReadyToPlay),
+        WindowEvent::MouseWheel { delta: MouseScrollDelta::LineDelta(_, lines), .. } => Some(GameEvent::Zoom(lines as f64)),
 
-        WindowEvent::KeyboardInput {
-            input: KeyboardInput {
-                state: ElementState::Released,
-                virtual_keycode: Some(VirtualKeyCode::Z),
-                ..
-            },
-            ..
-        } => Some(GameEvent::ZoomIn),
-
-        WindowEvent::KeyboardInput {
-            input: KeyboardInput {
-                state: ElementState::Released,
-                virtual_keycode: Some(VirtualKeyCode::X),
-                ..
-            },
-            ..
-        } => Some(GameEvent::ZoomOut),
+        WindowEvent::MouseWheel { delta: MouseScrollDelta::PixelDelta(_, pixels), .. } => Some(GameEvent::Zoom(pixels as f64 / PIXELS_PER_LINE)),
 
         WindowEvent::Resized(width, height) => Some(GameEvent::Resize(width as f64, height as f64)),
 
-        _ => None
+        _ => get_input(event).and_then(|(input, state)| {
+            controls
+                .find_action(input, modifier1, modifier2, is_chat_active)
+                .and_then(|action| action.to_game_event(state))
+        })
     }
 }
 
-pub fn map_planet_input(event: &WindowEvent) -> Option<GameEvent> {
+pub fn get_input(event: &WindowEvent) -> Option<(Input, ElementState)> {
     match *event {
+        WindowEvent::MouseInput { button, state, .. } => Some((Input::Mouse(button), state)),
+
         WindowEvent::KeyboardInput {
             input: KeyboardInput {
-                state: ElementState::Released,
-                virtual_keycode: Some(VirtualKeyCode::S),
+                state,
+                virtual_keycode: Some(keycode),
                 ..
             },
             ..
-        } => Some(GameEvent::SquadSpawn),
-
-        _ => None
-    }
-}
-
-pub fn map_squad_input(event: &WindowEvent) -> Option<GameEvent> {
-    match *event {
-        WindowEvent::MouseInput {
-            button: MouseButton::Right,
-            state: ElementState::Released,
-            ..
-        } => Some(GameEvent::SquadMove),
+        } => Some((Input::Key(keycode), state)),
 
         _ => None
     }
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use time;
+
+use client::squad::{Squad, SquadState};
+use common::{Id, Position};
+
+/// Squads are drawn this many seconds behind the latest snapshot, about one server tick,
+/// so there is almost always a newer snapshot to move towards
+const INTERPOLATION_DELAY: f64 = 0.1;
+
+/// Longest time squads keep moving past the latest snapshot when the next one is late
+const MAX_EXTRAPOLATION: f64 = 0.25;
+
+struct Snapshot {
+    tick: u64,
+    /// Server clock of the snapshot
+    time: f64,
+    /// Local clock when the snapshot has been received
+    received_at: f64,
+    positions: HashMap<Id, Position>
+}
+
+/// Smooths squads movement between server snapshots sent every tick
+pub struct SquadInterpolation {
+    previous: Option<Snapshot>,
+    latest: Option<Snapshot>
+}
+
+impl SquadInterpolation {
+    pub fn new() -> SquadInterpolation {
+        SquadInterpolation {
+            previous: None,
+            latest: None
+        }
+    }
+
+    /// Repeated snapshots are dropped, a lower tick means the server has been restarted
+    pub fn push(&mut self, tick: u64, time: f64, squads: &HashMap<Id, Squad>) {
+        self.push_received_at(tick, time, time::precise_time_s(), squads);
+    }
+
+    /// Moves squads to their positions at the moment being drawn
+    pub fn apply(&self, squads: &mut HashMap<Id, Squad>) {
+        self.apply_at(time::precise_time_s(), squads);
+    }
+
+    fn push_received_at(&mut self, tick: u64, time: f64, received_at: f64, squads: &HashMap<Id, Squad>) {
+        let latest_tick = self.latest.as_ref().map(|latest| latest.tick);
+
+        match latest_tick {
+            Some(latest_tick) if tick == latest_tick => return,
+            Some(latest_tick) if tick < latest_tick => self.latest = None,
+            _ => { }
+        }
+
+        let snapshot = Snapshot {
+            tick,
+            time,
+            received_at,
+            positions: squads
+                .values()
+                .map(|squad| (squad.id(), squad.position()))
+                .collect()
+        };
+
+        self.previous = self.latest.take();
+        self.latest = Some(snapshot);
+    }
+
+    /// `now` is the local clock
+    fn apply_at(&self, now: f64, squads: &mut HashMap<Id, Squad>) {
+        let (previous, latest) = match (self.previous.as_ref(), self.latest.as_ref()) {
+            (Some(previous), Some(latest)) => (previous, latest),
+            _ => return
+        };
+
+        let snapshots_interval = latest.time - previous.time;
+        if snapshots_interval <= 0.0 {
+            return;
+        }
+
+        let render_time = latest.time + (now - latest.received_at) - INTERPOLATION_DELAY;
+        let render_time = render_time.min(latest.time + MAX_EXTRAPOLATION);
+
+        let progress = ((render_time - previous.time) / snapshots_interval).max(0.0);
+
+        for squad in squads.values_mut() {
+            // Squads just spawned have nothing to move from
+            let (previous_position, latest_position) = match (previous.positions.get(&squad.id()), latest.positions.get(&squad.id())) {
+                (Some(&previous_position), Some(&latest_position)) => (previous_position, latest_position),
+                _ => continue
+            };
+
+            // Squads that have stopped stay where they are instead of flying past their target
+            let progress = match squad.state() {
+                SquadState::Moving { .. } => progress,
+                SquadState::InSpace | SquadState::OnOrbit { .. } => progress.min(1.0)
+            };
+
+            let Position(previous_x, previous_y) = previous_position;
+            let Position(latest_x, latest_y) = latest_position;
+
+            squad.set_position(Position(
+                previous_x + (latest_x - previous_x) * progress,
+                previous_y + (latest_y - previous_y) * progress
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use client::squad::{Squad, SquadState};
+    use common::{Id, Position, SquadClass};
+    use super::{SquadInterpolation, INTERPOLATION_DELAY, MAX_EXTRAPOLATION};
+
+    const RECEIVED_AT: f64 = 100.0;
+    const TICK_INTERVAL: f64 = 0.1;
+    const EPSILON: f64 = 1e-9;
+
+    fn create_squads(x: f64, state: SquadState) -> HashMap<Id, Squad> {
+        let mut squads = HashMap::new();
+        squads.insert(1, Squad::new(1, 1, SquadClass::Fighter, Position(x, 0.0), 1, state));
+        squads
+    }
+
+    /// Squad moved from 0 to 10 between two snapshots, returns its drawn x at the local time
+    fn get_drawn_x(now: f64, state: SquadState) -> f64 {
+        let mut interpolation = SquadInterpolation::new();
+        interpolation.push_received_at(1, 0.0, RECEIVED_AT - TICK_INTERVAL, &create_squads(0.0, state));
+        interpolation.push_received_at(2, TICK_INTERVAL, RECEIVED_AT, &create_squads(10.0, state));
+
+        let mut squads = create_squads(10.0, state);
+        interpolation.apply_at(now, &mut squads);
+
+        squads[&1].position().0
+    }
+
+    fn moving() -> SquadState {
+        SquadState::Moving { destination: None }
+    }
+
+    #[test]
+    fn interpolates_behind_latest_snapshot() {
+        assert!((get_drawn_x(RECEIVED_AT, moving()) - 0.0).abs() < EPSILON);
+        assert!((get_drawn_x(RECEIVED_AT + INTERPOLATION_DELAY / 2.0, moving()) - 5.0).abs() < EPSILON);
+        assert!((get_drawn_x(RECEIVED_AT + INTERPOLATION_DELAY, moving()) - 10.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn extrapolation_is_limited() {
+        let max_x = 10.0 * (TICK_INTERVAL + MAX_EXTRAPOLATION) / TICK_INTERVAL;
+
+        assert!(get_drawn_x(RECEIVED_AT + INTERPOLATION_DELAY + 0.05, moving()) > 10.0);
+        assert!((get_drawn_x(RECEIVED_AT + 10.0, moving()) - max_x).abs() < EPSILON);
+    }
+
+    #[test]
+    fn stopped_squads_are_not_extrapolated() {
+        let on_orbit = SquadState::OnOrbit { waypoint_id: 1 };
+
+        assert!((get_drawn_x(RECEIVED_AT + INTERPOLATION_DELAY / 2.0, on_orbit) - 5.0).abs() < EPSILON);
+        assert!((get_drawn_x(RECEIVED_AT + 10.0, on_orbit) - 10.0).abs() < EPSILON);
+        assert!((get_drawn_x(RECEIVED_AT + 10.0, SquadState::InSpace) - 10.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn restarted_server_drops_snapshots() {
+        let mut interpolation = SquadInterpolation::new();
+        interpolation.push_received_at(5, 0.0, RECEIVED_AT - TICK_INTERVAL, &create_squads(0.0, moving()));
+        interpolation.push_received_at(1, TICK_INTERVAL, RECEIVED_AT, &create_squads(10.0, moving()));
+
+        let mut squads = create_squads(10.0, moving());
+        interpolation.apply_at(RECEIVED_AT, &mut squads);
+
+        assert!((squads[&1].position().0 - 10.0).abs() < EPSILON);
+    }
+}
\ No newline at end of file
@@ -2,20 +2,39 @@ use std::collections::HashMap;
 
 use rustc_serialize::json::{Json, Object};
 
+use client::chat::{ChatLine, Ping};
+use client::diplomacy::Diplomacy;
+use client::economy::Economy;
+use client::leaderboard::LeaderboardEntry;
+use client::match_event::MatchEvent;
+use client::match_info::MatchInfo;
 use client::player::Player;
-use client::squad::Squad;
+use client::research::ResearchItem;
+use client::rules::{ClassRules, Rules};
+use client::squad::{Squad, SquadState};
+use client::standing::Standing;
 use client::waypoint::{Waypoint, WaypointType};
-use common::{Id, PlayerId, ParseCommandError, ParseCommandResult, Position};
+use common::{ColorId, Id, PlayerId, ParseCommandError, ParseCommandResult, Position, SquadClass, TeamId, utils};
 use common::utils::json;
 
 type Result<T> = ParseCommandResult<T>;
 
 type ProcessCommandTuple = (
+    (u64, f64),
     HashMap<Id, Waypoint>,
+    Vec<(Id, Id)>,
     HashMap<PlayerId, Player>,
     HashMap<Id, Squad>,
+    Vec<MatchEvent>,
+    MatchInfo,
+    Option<Vec<Standing>>,
+    Diplomacy,
+    Vec<ChatLine>,
+    Vec<Ping>,
+    Vec<ResearchItem>,
     PlayerId,
-    f64
+    f64,
+    Economy
 );
 
 pub fn parse_process_command(string: &str) -> Result<ProcessCommandTuple> {
@@ -23,35 +42,256 @@ pub fn parse_process_command(string: &str) -> Result<ProcessCommandTuple> {
     let params = json::parse_json_as_object(&json)?;
 
     let process_command_tuple = (
+        parse_tick(params)?,
         parse_waypoints(params)?,
+        parse_lanes(params)?,
         parse_players(params)?,
         parse_squads(params)?,
+        parse_events(params)?,
+        parse_match_info(params)?,
+        parse_standings(params)?,
+        parse_diplomacy(params)?,
+        parse_chat(params)?,
+        parse_pings(params)?,
+        parse_research(params)?,
         json::parse_player_id_from_json_object(params, "id")?,
-        json::parse_f64_from_json_object(params, "gold")?
+        json::parse_f64_from_json_object(params, "gold")?,
+        parse_economy(params)?
     );
 
     return Ok(process_command_tuple);
 }
 
+/// Returns `None` for messages other than the rules command
+pub fn parse_rules_command(string: &str) -> Result<Option<Rules>> {
+    let json = json::parse_json(string)?;
+    let params = json::parse_json_as_object(&json)?;
+
+    if !params.contains_key("rules") {
+        return Ok(None);
+    }
+
+    let rules_json_object = json::parse_object_from_json_object(params, "rules")?;
+    let classes_json_object = json::parse_object_from_json_object(rules_json_object, "classes")?;
+
+    let rules = Rules {
+        starting_gold: json::parse_f64_from_json_object(rules_json_object, "starting_gold")?,
+        income_rate: json::parse_f64_from_json_object(rules_json_object, "income_rate")?,
+        planet_income: json::parse_f64_from_json_object(rules_json_object, "planet_income")?,
+        upkeep_per_supply: json::parse_f64_from_json_object(rules_json_object, "upkeep_per_supply")?,
+        supply_base: json::parse_f64_from_json_object(rules_json_object, "supply_base")?,
+        supply_per_planet: json::parse_f64_from_json_object(rules_json_object, "supply_per_planet")?,
+        merge_distance: json::parse_f64_from_json_object(rules_json_object, "merge_distance")?,
+        orbit_distance: json::parse_f64_from_json_object(rules_json_object, "orbit_distance")?,
+        planet_capture_threshold: json::parse_f64_from_json_object(rules_json_object, "planet_capture_threshold")?,
+        planetoid_capture_threshold: json::parse_f64_from_json_object(rules_json_object, "planetoid_capture_threshold")?,
+        fighter: parse_class_rules(classes_json_object, SquadClass::Fighter)?,
+        cruiser: parse_class_rules(classes_json_object, SquadClass::Cruiser)?
+    };
+
+    Ok(Some(rules))
+}
+
+/// Returns `None` for messages other than the session command
+pub fn parse_session_command(string: &str) -> Result<Option<(String, bool)>> {
+    let json = json::parse_json(string)?;
+    let params = json::parse_json_as_object(&json)?;
+
+    if !params.contains_key("session") {
+        return Ok(None);
+    }
+
+    let session_json_object = json::parse_object_from_json_object(params, "session")?;
+
+    let token = json::parse_string_from_json_object(session_json_object, "token")?.to_string();
+    let is_admin = session_json_object.get("admin").and_then(|admin| admin.as_boolean()).unwrap_or(false);
+
+    Ok(Some((token, is_admin)))
+}
+
+/// Returns `None` for messages other than the error command
+pub fn parse_error_command(string: &str) -> Result<Option<String>> {
+    let json = json::parse_json(string)?;
+    let params = json::parse_json_as_object(&json)?;
+
+    if !params.contains_key("error") {
+        return Ok(None);
+    }
+
+    Ok(Some(json::parse_string_from_json_object(params, "error")?.to_string()))
+}
+
+/// Returns `None` for messages other than the admin command output
+pub fn parse_admin_command(string: &str) -> Result<Option<String>> {
+    let json = json::parse_json(string)?;
+    let params = json::parse_json_as_object(&json)?;
+
+    if !params.contains_key("admin") {
+        return Ok(None);
+    }
+
+    Ok(Some(json::parse_string_from_json_object(params, "admin")?.to_string()))
+}
+
+pub fn format_admin_command(command: &str) -> String {
+    format!(
+        r#"{{"action":"admin","data":{{"command":{}}}}}"#,
+        Json::String(command.to_string())
+    )
+}
+
+pub fn format_hello_command(token: Option<&String>, name: Option<&String>, profile: Option<&String>) -> String {
+    let format_option = |value: Option<&String>| value.map_or("null".to_string(), |value| Json::String(value.clone()).to_string());
+
+    format!(
+        r#"{{"action":"hello","data":{{"token":{},"name":{},"profile":{}}}}}"#,
+        format_option(token),
+        format_option(name),
+        format_option(profile)
+    )
+}
+
+pub fn format_leaderboard_command() -> String {
+    format!(r#"{{"action":"leaderboard","data":{{}}}}"#)
+}
+
+pub fn parse_leaderboard_command(string: &str) -> Result<Option<Vec<LeaderboardEntry>>> {
+    let json = json::parse_json(string)?;
+    let params = json::parse_json_as_object(&json)?;
+
+    if !params.contains_key("leaderboard") {
+        return Ok(None);
+    }
+
+    let mut entries = vec![];
+    for entry_json in json::parse_array_from_json_object(params, "leaderboard")?.into_iter() {
+        let entry_json_object = json::parse_json_as_object(entry_json)?;
+
+        entries.push(LeaderboardEntry::new(
+            json::parse_string_from_json_object(entry_json_object, "name")?.to_string(),
+            json::parse_f64_from_json_object(entry_json_object, "rating")?,
+            json::parse_u64_from_json_object(entry_json_object, "wins")?,
+            json::parse_u64_from_json_object(entry_json_object, "losses")?
+        ));
+    }
+
+    Ok(Some(entries))
+}
+
+pub fn format_pause_command(paused: bool) -> String {
+    format!(r#"{{"action":"pause","data":{{"paused":{}}}}}"#, paused)
+}
+
+pub fn format_speed_command(speed: f64) -> String {
+    format!(r#"{{"action":"speed","data":{{"speed":{}}}}}"#, speed)
+}
+
 pub fn format_ready_command() -> String {
     format!(r#"{{"action":"ready","data":{{}}}}"#)
 }
 
-pub fn format_squad_spawn_command(planet_id: Id) -> String {
+pub fn format_lobby_command() -> String {
+    format!(r#"{{"action":"lobby","data":{{}}}}"#)
+}
+
+pub fn format_leave_command() -> String {
+    format!(r#"{{"action":"leave","data":{{}}}}"#)
+}
+
+pub fn format_squad_spawn_command(planet_id: Id, class: SquadClass) -> String {
+    format!(
+        r#"{{"action":"squad_spawn","data":{{"planet_id":{},"class":"{}"}}}}"#,
+        planet_id,
+        class.name()
+    )
+}
+
+pub fn format_research_command(tech: &str) -> String {
     format!(
-        r#"{{"action":"squad_spawn","data":{{"planet_id":{}}}}}"#,
-        planet_id
+        r#"{{"action":"research","data":{{"tech":"{}"}}}}"#,
+        tech
     )
 }
 
-pub fn format_squad_move_command(squad_id: Id, waypoint_id: Id) -> String {
+pub fn format_team_command(team: Option<TeamId>) -> String {
+    format!(
+        r#"{{"action":"team","data":{{"team":{}}}}}"#,
+        team.map_or("null".to_string(), |team| team.to_string())
+    )
+}
+
+pub fn format_color_command(color: ColorId) -> String {
+    format!(r#"{{"action":"color","data":{{"color":{}}}}}"#, color)
+}
+
+pub fn format_diplomacy_propose_command(player_id: PlayerId, relation: &str) -> String {
     format!(
-        r#"{{"action":"squad_move","data":{{"squad_id":{},"waypoint_id":{}}}}}"#,
-        squad_id,
-        waypoint_id
+        r#"{{"action":"diplomacy_propose","data":{{"player_id":{},"relation":"{}"}}}}"#,
+        player_id,
+        relation
     )
 }
 
+pub fn format_diplomacy_accept_command(player_id: PlayerId, relation: &str) -> String {
+    format!(
+        r#"{{"action":"diplomacy_accept","data":{{"player_id":{},"relation":"{}"}}}}"#,
+        player_id,
+        relation
+    )
+}
+
+pub fn format_diplomacy_break_command(player_id: PlayerId) -> String {
+    format!(
+        r#"{{"action":"diplomacy_break","data":{{"player_id":{}}}}}"#,
+        player_id
+    )
+}
+
+pub fn format_chat_command(channel: &str, player_id: Option<PlayerId>, text: &str) -> String {
+    let player_id_json = player_id.map_or("".to_string(), |player_id| format!(r#","player_id":{}"#, player_id));
+
+    format!(
+        r#"{{"action":"chat","data":{{"channel":"{}"{},"text":{}}}}}"#,
+        channel,
+        player_id_json,
+        Json::String(text.to_string())
+    )
+}
+
+pub fn format_ping_command(position: Position) -> String {
+    let Position(x, y) = position;
+
+    format!(
+        r#"{{"action":"ping","data":{{"x":{},"y":{}}}}}"#,
+        x,
+        y
+    )
+}
+
+/// With `split` only half of every squad moves. Large selections are sent in several commands,
+/// so that each of them fits the message size limit of the server.
+pub fn format_squad_move_commands(squad_ids: &[Id], waypoint_id: Id, split: bool) -> Vec<String> {
+    // Ids take up to 20 digits, a full command stays about half of the limit
+    const MAX_SQUADS_PER_COMMAND: usize = 100;
+
+    squad_ids
+        .chunks(MAX_SQUADS_PER_COMMAND)
+        .map(|squad_ids| {
+            let formatted_squad_ids = squad_ids
+                .iter()
+                .map(|squad_id| squad_id.to_string())
+                .collect::<Vec<String>>();
+
+            format!(
+                r#"{{"action":"squad_move","data":{{"squad_ids":[{}],"waypoint_id":{},"split":{}}}}}"#,
+                utils::join(formatted_squad_ids, ","),
+                waypoint_id,
+                split
+            )
+        })
+        .collect()
+}
+
 fn parse_waypoints(params: &Object) -> Result<HashMap<Id, Waypoint>> {
     let waypoints_json_array = json::parse_array_from_json_object(params, "waypoints")?;
 
@@ -64,6 +304,7 @@ fn parse_waypoints(params: &Object) -> Result<HashMap<Id, Waypoint>> {
         let y = json::parse_f64_from_json_object(waypoint_json_object, "y")?;
 
         let owner = json::parse_option_player_id_from_json_object(waypoint_json_object, "owner")?;
+        let capture = parse_capture(waypoint_json_object)?;
 
         let waypoint_type = match json::parse_string_from_json_object(waypoint_json_object, "type")? {
             "asteroid" => WaypointType::Asteroid,
@@ -73,13 +314,37 @@ fn parse_waypoints(params: &Object) -> Result<HashMap<Id, Waypoint>> {
             _ => unreachable!()
         };
 
-        let waypoint = Waypoint::new(waypoint_id, waypoint_type,Position(x, y), owner);
+        let waypoint = Waypoint::new(waypoint_id, waypoint_type, Position(x, y), owner, capture);
         waypoints.insert(waypoint_id, waypoint);
     }
 
     Ok(waypoints)
 }
 
+fn parse_capture(waypoint_json_object: &Object) -> Result<Option<(PlayerId, f64)>> {
+    match waypoint_json_object.get("capture") {
+        Some(&Json::Object(ref capture_json_object)) => {
+            let player_id = json::parse_player_id_from_json_object(capture_json_object, "player")?;
+            let progress = json::parse_f64_from_json_object(capture_json_object, "progress")?;
+
+            Ok(Some((player_id, progress)))
+        },
+
+        _ => Ok(None)
+    }
+}
+
+fn parse_lanes(params: &Object) -> Result<Vec<(Id, Id)>> {
+    let lanes_json_array = json::parse_array_from_json_object(params, "lanes")?;
+
+    let mut lanes = vec![];
+    for lane_json in lanes_json_array.into_iter() {
+        lanes.push(json::parse_id_pair(lane_json)?);
+    }
+
+    Ok(lanes)
+}
+
 fn parse_players(params: &Object) -> Result<HashMap<PlayerId, Player>> {
     let players_json_array = json::parse_array_from_json_object(params, "players")?;
 
@@ -90,8 +355,10 @@ fn parse_players(params: &Object) -> Result<HashMap<PlayerId, Player>> {
         let player_id = json::parse_player_id_from_json_object(player_json_object, "id")?;
         let player_name = json::parse_string_from_json_object(player_json_object, "name")?;
         let player_state = json::parse_string_from_json_object(player_json_object, "state")?;
+        let team = json::parse_option_u64_from_json_object(player_json_object, "team")?;
+        let color = json::parse_u64_from_json_object(player_json_object, "color")?;
 
-        let player = Player::new(player_id, player_name.to_string(), player_state.to_string());
+        let player = Player::new(player_id, player_name.to_string(), player_state.to_string(), team, color);
         players.insert(player_id, player);
     }
 
@@ -107,13 +374,296 @@ fn parse_squads(params: &Object) -> Result<HashMap<Id, Squad>> {
 
         let squad_id = json::parse_id_from_json_object(squad_json_object, "id")?;
         let owner = json::parse_player_id_from_json_object(squad_json_object, "owner")?;
+        let class_name = json::parse_string_from_json_object(squad_json_object, "class")?;
+        let class = SquadClass::from_name(class_name)
+            .ok_or(ParseCommandError::IncompatibleType("class".to_string()))?;
         let x = json::parse_f64_from_json_object(squad_json_object, "x")?;
         let y = json::parse_f64_from_json_object(squad_json_object, "y")?;
         let count = json::parse_u64_from_json_object(squad_json_object, "count")?;
+        let waypoint_id = json::parse_option_u64_from_json_object(squad_json_object, "waypoint")?;
 
-        let squad = Squad::new(squad_id, owner, Position(x, y), count);
+        let state = match (json::parse_string_from_json_object(squad_json_object, "state")?, waypoint_id) {
+            ("moving", destination) => SquadState::Moving { destination },
+            ("orbiting", Some(waypoint_id)) => SquadState::OnOrbit { waypoint_id },
+            _ => SquadState::InSpace
+        };
+
+        let squad = Squad::new(squad_id, owner, class, Position(x, y), count, state);
         squads.insert(squad_id, squad);
     }
 
     Ok(squads)
-}
\ No newline at end of file
+}
+
+fn parse_events(params: &Object) -> Result<Vec<MatchEvent>> {
+    let events_json_array = json::parse_array_from_json_object(params, "events")?;
+
+    let mut events = vec![];
+    for event_json in events_json_array {
+        let event_json_object = json::parse_json_as_object(event_json)?;
+
+        let event = match json::parse_string_from_json_object(event_json_object, "type")? {
+            "squad_spawned" => MatchEvent::SquadSpawned {
+                squad_id: json::parse_id_from_json_object(event_json_object, "squad_id")?,
+                owner: json::parse_player_id_from_json_object(event_json_object, "owner")?,
+                class: json::parse_string_from_json_object(event_json_object, "class")?.to_string(),
+                waypoint_id: json::parse_id_from_json_object(event_json_object, "waypoint_id")?
+            },
+
+            "squad_destroyed" => MatchEvent::SquadDestroyed {
+                squad_id: json::parse_id_from_json_object(event_json_object, "squad_id")?,
+                owner: json::parse_player_id_from_json_object(event_json_object, "owner")?,
+                killer: json::parse_option_player_id_from_json_object(event_json_object, "killer")?
+            },
+
+            "squads_merged" => MatchEvent::SquadsMerged {
+                squad_id: json::parse_id_from_json_object(event_json_object, "squad_id")?,
+                merged_squad_ids: parse_ids(event_json_object, "merged_squad_ids")?
+            },
+
+            "waypoint_captured" => MatchEvent::WaypointCaptured {
+                waypoint_id: json::parse_id_from_json_object(event_json_object, "waypoint_id")?,
+                owner: json::parse_player_id_from_json_object(event_json_object, "owner")?,
+                previous_owner: json::parse_option_player_id_from_json_object(event_json_object, "previous_owner")?
+            },
+
+            "research_completed" => MatchEvent::ResearchCompleted {
+                tech: json::parse_string_from_json_object(event_json_object, "tech")?.to_string()
+            },
+
+            "player_eliminated" => MatchEvent::PlayerEliminated {
+                player_id: json::parse_player_id_from_json_object(event_json_object, "player_id")?
+            },
+
+            "match_finished" => MatchEvent::MatchFinished {
+                winners: parse_ids(event_json_object, "winners")?
+                    .into_iter()
+                    .map(|winner| winner as PlayerId)
+                    .collect()
+            },
+
+            // Events added by newer servers are skipped
+            _ => continue
+        };
+
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+fn parse_ids(object: &Object, property: &str) -> Result<Vec<Id>> {
+    json::parse_array_from_json_object(object, property)?
+        .iter()
+        .map(|id| id.as_u64().ok_or(ParseCommandError::IncompatibleType(property.to_string())))
+        .collect()
+}
+
+fn parse_match_info(params: &Object) -> Result<MatchInfo> {
+    let match_json_object = json::parse_object_from_json_object(params, "match")?;
+
+    let victory = json::parse_string_from_json_object(match_json_object, "victory")?;
+    let target = match_json_object.get("target").and_then(|target| target.as_f64());
+    let time = json::parse_f64_from_json_object(match_json_object, "time")?;
+    let is_paused = match_json_object.get("paused").and_then(|paused| paused.as_boolean()).unwrap_or(false);
+    let speed = json::parse_f64_from_json_object(match_json_object, "speed")?;
+
+    Ok(MatchInfo::new(victory.to_string(), target, time, is_paused, speed))
+}
+
+fn parse_chat(params: &Object) -> Result<Vec<ChatLine>> {
+    let chat_json_array = json::parse_array_from_json_object(params, "chat")?;
+
+    let mut chat_lines = vec![];
+    for message_json in chat_json_array {
+        let message_json_object = json::parse_json_as_object(message_json)?;
+
+        chat_lines.push(ChatLine::new(
+            json::parse_player_id_from_json_object(message_json_object, "from")?,
+            json::parse_string_from_json_object(message_json_object, "channel")?.to_string(),
+            json::parse_string_from_json_object(message_json_object, "text")?.to_string()
+        ));
+    }
+
+    Ok(chat_lines)
+}
+
+fn parse_pings(params: &Object) -> Result<Vec<Ping>> {
+    let pings_json_array = json::parse_array_from_json_object(params, "pings")?;
+
+    let mut pings = vec![];
+    for ping_json in pings_json_array {
+        let ping_json_object = json::parse_json_as_object(ping_json)?;
+
+        let x = json::parse_f64_from_json_object(ping_json_object, "x")?;
+        let y = json::parse_f64_from_json_object(ping_json_object, "y")?;
+
+        pings.push(Ping::new(json::parse_player_id_from_json_object(ping_json_object, "from")?, Position(x, y)));
+    }
+
+    Ok(pings)
+}
+
+fn parse_economy(params: &Object) -> Result<Economy> {
+    let income_json_object = json::parse_object_from_json_object(params, "income")?;
+    let supply_json_object = json::parse_object_from_json_object(params, "supply")?;
+
+    Ok(Economy::new(
+        json::parse_f64_from_json_object(income_json_object, "planetoids")?,
+        json::parse_f64_from_json_object(income_json_object, "planets")?,
+        json::parse_f64_from_json_object(income_json_object, "upkeep")?,
+        json::parse_f64_from_json_object(income_json_object, "modifier")?,
+        json::parse_f64_from_json_object(supply_json_object, "used")?,
+        json::parse_f64_from_json_object(supply_json_object, "cap")?
+    ))
+}
+
+/// Returns the snapshot number and the server clock it has been sent at
+fn parse_tick(params: &Object) -> Result<(u64, f64)> {
+    let tick_json_object = json::parse_object_from_json_object(params, "tick")?;
+
+    Ok((
+        json::parse_u64_from_json_object(tick_json_object, "number")?,
+        json::parse_f64_from_json_object(tick_json_object, "time")?
+    ))
+}
+
+fn parse_standings(params: &Object) -> Result<Option<Vec<Standing>>> {
+    if params.get("standings").map_or(true, |standings| standings.is_null()) {
+        return Ok(None);
+    }
+
+    let standings_json_array = json::parse_array_from_json_object(params, "standings")?;
+
+    let mut standings = vec![];
+    for standing_json in standings_json_array.into_iter() {
+        let standing_json_object = json::parse_json_as_object(standing_json)?;
+
+        let player_id = json::parse_player_id_from_json_object(standing_json_object, "id")?;
+        let name = json::parse_string_from_json_object(standing_json_object, "name")?;
+        let place = json::parse_u64_from_json_object(standing_json_object, "place")?;
+        let is_winner = standing_json_object.get("winner").and_then(|winner| winner.as_boolean()).unwrap_or(false);
+        let score = json::parse_f64_from_json_object(standing_json_object, "score")?;
+        let planets_count = json::parse_u64_from_json_object(standing_json_object, "planets")?;
+        let squads_count = json::parse_u64_from_json_object(standing_json_object, "squads")?;
+        let squads_built = json::parse_u64_from_json_object(standing_json_object, "squads_built")?;
+        let squads_destroyed = json::parse_u64_from_json_object(standing_json_object, "squads_destroyed")?;
+        let planets_captured = json::parse_u64_from_json_object(standing_json_object, "planets_captured")?;
+        let rating = standing_json_object.get("rating").and_then(|rating| rating.as_f64());
+        let rating_change = standing_json_object.get("rating_change").and_then(|rating_change| rating_change.as_f64());
+
+        standings.push(Standing::new(
+            player_id,
+            name.to_string(),
+            place,
+            is_winner,
+            score,
+            planets_count,
+            squads_count,
+            squads_built,
+            squads_destroyed,
+            planets_captured,
+            rating,
+            rating_change
+        ));
+    }
+
+    Ok(Some(standings))
+}
+
+fn parse_diplomacy(params: &Object) -> Result<Diplomacy> {
+    let relations_json_array = json::parse_array_from_json_object(params, "relations")?;
+
+    let mut relations = vec![];
+    for relation_json in relations_json_array.into_iter() {
+        let relation_json_object = json::parse_json_as_object(relation_json)?;
+
+        let players_json = relation_json_object.get("players")
+            .ok_or(ParseCommandError::MissedProperty("players".to_string()))?;
+        let (a, b) = json::parse_id_pair(players_json)?;
+        let relation = json::parse_string_from_json_object(relation_json_object, "relation")?;
+
+        relations.push((a as PlayerId, b as PlayerId, relation.to_string()));
+    }
+
+    let proposals_json_array = json::parse_array_from_json_object(params, "proposals")?;
+
+    let mut proposals = vec![];
+    for proposal_json in proposals_json_array.into_iter() {
+        let proposal_json_object = json::parse_json_as_object(proposal_json)?;
+
+        let from = json::parse_player_id_from_json_object(proposal_json_object, "from")?;
+        let relation = json::parse_string_from_json_object(proposal_json_object, "relation")?;
+
+        proposals.push((from, relation.to_string()));
+    }
+
+    Ok(Diplomacy::new(relations, proposals))
+}
+
+fn parse_class_rules(classes_json_object: &Object, class: SquadClass) -> Result<ClassRules> {
+    let class_json_object = json::parse_object_from_json_object(classes_json_object, class.name())?;
+
+    Ok(ClassRules {
+        cost: json::parse_f64_from_json_object(class_json_object, "cost")?,
+        supply: json::parse_f64_from_json_object(class_json_object, "supply")?,
+        life: json::parse_f64_from_json_object(class_json_object, "life")?,
+        speed: json::parse_f64_from_json_object(class_json_object, "speed")?,
+        weapon_range: json::parse_f64_from_json_object(class_json_object, "weapon_range")?,
+        damage: json::parse_f64_from_json_object(class_json_object, "damage")?
+    })
+}
+
+fn parse_research(params: &Object) -> Result<Vec<ResearchItem>> {
+    let research_json_array = json::parse_array_from_json_object(params, "research")?;
+
+    let mut research_items = vec![];
+    for research_json in research_json_array.into_iter() {
+        let research_json_object = json::parse_json_as_object(research_json)?;
+
+        let tech = json::parse_string_from_json_object(research_json_object, "tech")?;
+        let title = json::parse_string_from_json_object(research_json_object, "title")?;
+        let cost = json::parse_f64_from_json_object(research_json_object, "cost")?;
+        let time = json::parse_f64_from_json_object(research_json_object, "time")?;
+        let state = json::parse_string_from_json_object(research_json_object, "state")?;
+        let progress = json::parse_f64_from_json_object(research_json_object, "progress")?;
+
+        research_items.push(ResearchItem::new(tech.to_string(), title.to_string(), cost, time, state.to_string(), progress));
+    }
+
+    Ok(research_items)
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_serialize::json::Json;
+
+    use common::Id;
+    use common::rate_limit::MAX_MESSAGE_SIZE;
+    use super::format_squad_move_commands;
+
+    #[test]
+    fn squad_move_commands_fit_message_size() {
+        // Longest ids possible
+        let squad_ids = (0..450).map(|i| Id::max_value() - i).collect::<Vec<_>>();
+
+        let commands = format_squad_move_commands(&squad_ids, Id::max_value(), true);
+        assert_eq!(commands.len(), 5);
+
+        let mut sent_squad_ids = vec![];
+        for command in &commands {
+            assert!(command.len() < MAX_MESSAGE_SIZE, "command of {} bytes", command.len());
+
+            let json = Json::from_str(command).unwrap();
+            let data = json.find("data").unwrap();
+
+            assert_eq!(data.find("split").and_then(Json::as_boolean), Some(true));
+            assert_eq!(data.find("waypoint_id").and_then(Json::as_u64), Some(Id::max_value()));
+
+            sent_squad_ids.extend(data.find("squad_ids").unwrap().as_array().unwrap().iter().map(|id| id.as_u64().unwrap()));
+        }
+
+        assert_eq!(sent_squad_ids, squad_ids);
+        assert!(format_squad_move_commands(&[], 1, false).is_empty());
+    }
+}
@@ -0,0 +1,21 @@
+pub struct LeaderboardEntry {
+    name: String,
+    rating: f64,
+    wins: u64,
+    losses: u64
+}
+
+impl LeaderboardEntry {
+    pub fn new(name: String, rating: f64, wins: u64, losses: u64) -> LeaderboardEntry {
+        LeaderboardEntry {
+            name,
+            rating,
+            wins,
+            losses
+        }
+    }
+
+    pub fn describe(&self, place: usize) -> String {
+        format!("{}. {}: rating {}, wins {}, losses {}", place, self.name, self.rating, self.wins, self.losses)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,40 @@
+use common::{Id, PlayerId};
+
+/// Things happened on the server during a tick
+pub enum MatchEvent {
+    SquadSpawned {
+        squad_id: Id,
+        owner: PlayerId,
+        class: String,
+        waypoint_id: Id
+    },
+
+    SquadDestroyed {
+        squad_id: Id,
+        owner: PlayerId,
+        killer: Option<PlayerId>
+    },
+
+    SquadsMerged {
+        squad_id: Id,
+        merged_squad_ids: Vec<Id>
+    },
+
+    WaypointCaptured {
+        waypoint_id: Id,
+        owner: PlayerId,
+        previous_owner: Option<PlayerId>
+    },
+
+    ResearchCompleted {
+        tech: String
+    },
+
+    PlayerEliminated {
+        player_id: PlayerId
+    },
+
+    MatchFinished {
+        winners: Vec<PlayerId>
+    }
+}
@@ -0,0 +1,64 @@
+pub struct MatchInfo {
+    victory: String,
+    target: Option<f64>,
+    time: f64,
+    is_paused: bool,
+    speed: f64
+}
+
+impl MatchInfo {
+    pub fn new(victory: String, target: Option<f64>, time: f64, is_paused: bool, speed: f64) -> MatchInfo {
+        MatchInfo {
+            victory,
+            target,
+            time,
+            is_paused,
+            speed
+        }
+    }
+
+    pub fn victory(&self) -> &String {
+        &self.victory
+    }
+
+    pub fn target(&self) -> Option<f64> {
+        self.target
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn describe(&self) -> String {
+        let victory = self.describe_victory();
+
+        if self.is_paused {
+            format!("{} (paused)", victory)
+        } else if self.speed != 1.0 {
+            format!("{} ({}x)", victory, self.speed)
+        } else {
+            victory
+        }
+    }
+
+    fn describe_victory(&self) -> String {
+        match (self.victory.as_ref(), self.target) {
+            ("score", Some(time_limit)) => {
+                let time_left = (time_limit - self.time).max(0.0) as u64;
+                format!("Score: {}:{:02} left", time_left / 60, time_left % 60)
+            },
+
+            ("hill", Some(hold_time)) => format!("Hill: hold {}s", hold_time),
+            ("economic", Some(gold_target)) => format!("Economic: {} gold", gold_target),
+            (victory, _) => format!("Victory: {}", victory)
+        }
+    }
+}
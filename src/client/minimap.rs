@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use client::waypoint::Waypoint;
+use common::{Id, Position};
+
+/// Side of the minimap in pixels
+const MINIMAP_SIZE: f64 = 200.0;
+
+/// Offset of the minimap from the top left window corner, below the header
+const MINIMAP_LEFT: f64 = 10.0;
+const MINIMAP_TOP: f64 = 40.0;
+
+/// Empty space around the outermost waypoints in world units
+const BOUNDS_MARGIN: f64 = 100.0;
+
+/// Half side of the area shown before any waypoint is known, as large as a generated map
+const DEFAULT_HALF_SIZE: f64 = 1000.0;
+
+/// Distance in pixels from the cursor within which a waypoint is picked on the minimap
+const PICK_RADIUS: f64 = 8.0;
+
+/// Square area of the world shown in a window corner
+pub struct Minimap {
+    center: Position,
+    half_size: f64
+}
+
+impl Minimap {
+    pub fn new() -> Minimap {
+        Minimap {
+            center: Position(0.0, 0.0),
+            half_size: DEFAULT_HALF_SIZE
+        }
+    }
+
+    /// Fits the shown area to the waypoints, maps loaded from files may be of any size
+    pub fn fit(&mut self, waypoints: &HashMap<Id, Waypoint>) {
+        if waypoints.is_empty() {
+            return;
+        }
+
+        let (min_x, min_y, max_x, max_y) = waypoints
+            .values()
+            .map(|waypoint| waypoint.position())
+            .fold(
+                (::std::f64::INFINITY, ::std::f64::INFINITY, ::std::f64::NEG_INFINITY, ::std::f64::NEG_INFINITY),
+                |(min_x, min_y, max_x, max_y), Position(x, y)| (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            );
+
+        self.center = Position((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+        self.half_size = (max_x - min_x).max(max_y - min_y) / 2.0 + BOUNDS_MARGIN;
+    }
+
+    pub fn contains(&self, window_position: (f64, f64)) -> bool {
+        let (x, y) = window_position;
+
+        x >= MINIMAP_LEFT && x <= MINIMAP_LEFT + MINIMAP_SIZE && y >= MINIMAP_TOP && y <= MINIMAP_TOP + MINIMAP_SIZE
+    }
+
+    /// World units per minimap pixel
+    pub fn scale(&self) -> f64 {
+        self.half_size * 2.0 / MINIMAP_SIZE
+    }
+
+    pub fn to_world(&self, window_position: (f64, f64)) -> Position {
+        let (x, y) = window_position;
+        let Position(center_x, center_y) = self.center;
+
+        Position(
+            center_x + (x - MINIMAP_LEFT - MINIMAP_SIZE / 2.0) * self.scale(),
+            center_y - (y - MINIMAP_TOP - MINIMAP_SIZE / 2.0) * self.scale()
+        )
+    }
+
+    /// Window position of a world point, points outside of the shown area are clamped to its border
+    pub fn to_window(&self, position: Position) -> (f64, f64) {
+        let Position(x, y) = position;
+        let Position(center_x, center_y) = self.center;
+
+        let minimap_x = ((x - center_x) / self.scale()).max(-MINIMAP_SIZE / 2.0).min(MINIMAP_SIZE / 2.0);
+        let minimap_y = ((center_y - y) / self.scale()).max(-MINIMAP_SIZE / 2.0).min(MINIMAP_SIZE / 2.0);
+
+        (MINIMAP_LEFT + MINIMAP_SIZE / 2.0 + minimap_x, MINIMAP_TOP + MINIMAP_SIZE / 2.0 + minimap_y)
+    }
+
+    /// Waypoint nearest to the window position within the pick radius
+    pub fn find_waypoint<'a>(&self, waypoints: &'a HashMap<Id, Waypoint>, window_position: (f64, f64)) -> Option<&'a Waypoint> {
+        let position = self.to_world(window_position);
+        let pick_distance = PICK_RADIUS * self.scale();
+
+        waypoints
+            .values()
+            .filter(|waypoint| position.distance_to(waypoint.position()) < pick_distance)
+            .min_by(|a, b| {
+                let a_distance = position.distance_to(a.position());
+                let b_distance = position.distance_to(b.position());
+
+                a_distance.partial_cmp(&b_distance).unwrap_or(Ordering::Equal)
+            })
+    }
+
+    /// Corners of the shown world area with the smallest and the largest coordinates
+    pub fn bounds(&self) -> (Position, Position) {
+        let Position(center_x, center_y) = self.center;
+
+        (
+            Position(center_x - self.half_size, center_y - self.half_size),
+            Position(center_x + self.half_size, center_y + self.half_size)
+        )
+    }
+
+    /// Window corners of the minimap in order around it
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        [
+            (MINIMAP_LEFT, MINIMAP_TOP),
+            (MINIMAP_LEFT + MINIMAP_SIZE, MINIMAP_TOP),
+            (MINIMAP_LEFT + MINIMAP_SIZE, MINIMAP_TOP + MINIMAP_SIZE),
+            (MINIMAP_LEFT, MINIMAP_TOP + MINIMAP_SIZE)
+        ]
+    }
+}
\ No newline at end of file
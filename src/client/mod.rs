@@ -1,18 +1,34 @@
+mod battle_log;
 mod client;
 mod camera;
+mod chat;
+mod circle_batch;
 mod command;
+mod controls;
+mod diplomacy;
+mod economy;
 mod game_cursor;
 mod game_event;
 mod game_ui;
 mod input_mapping;
+mod interpolation;
 mod json;
+mod leaderboard;
+mod match_event;
+mod match_info;
+mod minimap;
+mod palette;
 mod player;
+mod research;
+mod rules;
+mod selection;
 mod squad;
+mod standing;
 mod waypoint;
 
 use client::client::Client;
 
-pub fn run(address: String) {
+pub fn run(address: String, token: Option<String>, name: Option<String>, profile: Option<String>, controls_path: Option<String>, is_colorblind: bool) {
     let mut client = Client::new();
-    client.run(address);
+    client.run(address, token, name, profile, controls_path, is_colorblind);
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+use common::{ColorId, PLAYER_COLORS_COUNT};
+
+/// Colors of players in the order of color ids
+const DEFAULT_COLORS: [(u8, u8, u8); PLAYER_COLORS_COUNT as usize] = [
+    (164, 196, 0),
+    (229, 20, 0),
+    (27, 161, 226),
+    (250, 104, 0),
+    (170, 0, 255),
+    (227, 200, 0),
+    (244, 114, 208),
+    (0, 171, 169)
+];
+
+/// Okabe-Ito colors, told apart with any kind of color vision deficiency, black is replaced by grey for the dark space
+const COLORBLIND_COLORS: [(u8, u8, u8); PLAYER_COLORS_COUNT as usize] = [
+    (230, 159, 0),
+    (86, 180, 233),
+    (0, 158, 115),
+    (240, 228, 66),
+    (0, 114, 178),
+    (213, 94, 0),
+    (204, 121, 167),
+    (187, 187, 187)
+];
+
+/// Outlines around objects of allies and enemies differ in brightness, so they are told apart with any palette
+const ALLY_OUTLINE_COLOR: (u8, u8, u8) = (255, 255, 255);
+const ENEMY_OUTLINE_COLOR: (u8, u8, u8) = (32, 32, 32);
+
+#[derive(Copy, Clone)]
+pub enum Palette {
+    Default,
+    Colorblind
+}
+
+impl Palette {
+    pub fn player_color(&self, color: ColorId) -> [f32; 4] {
+        let colors = match *self {
+            Palette::Default => &DEFAULT_COLORS,
+            Palette::Colorblind => &COLORBLIND_COLORS
+        };
+
+        to_color(colors[(color % PLAYER_COLORS_COUNT) as usize])
+    }
+
+    pub fn outline_color(&self, is_ally: bool) -> [f32; 4] {
+        to_color(if is_ally { ALLY_OUTLINE_COLOR } else { ENEMY_OUTLINE_COLOR })
+    }
+}
+
+fn to_color((r, g, b): (u8, u8, u8)) -> [f32; 4] {
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0]
+}
\ No newline at end of file
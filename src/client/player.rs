@@ -1,17 +1,21 @@
-use common::PlayerId;
+use common::{ColorId, PlayerId, TeamId};
 
 pub struct Player {
     id: PlayerId,
     name: String,
-    state: String
+    state: String,
+    team: Option<TeamId>,
+    color: ColorId
 }
 
 impl Player {
-    pub fn new(id: PlayerId, name: String, state: String) -> Player {
+    pub fn new(id: PlayerId, name: String, state: String, team: Option<TeamId>, color: ColorId) -> Player {
         Player {
             id: id,
             name: name,
-            state: state
+            state: state,
+            team: team,
+            color: color
         }
     }
 
@@ -26,4 +30,12 @@ impl Player {
     pub fn state(&self) -> &String {
         &self.state
     }
-}
\ No newline at end of file
+
+    pub fn team(&self) -> Option<TeamId> {
+        self.team
+    }
+
+    pub fn color(&self) -> ColorId {
+        self.color
+    }
+}
@@ -0,0 +1,46 @@
+pub struct ResearchItem {
+    tech: String,
+    title: String,
+    cost: f64,
+    time: f64,
+    state: String,
+    progress: f64
+}
+
+impl ResearchItem {
+    pub fn new(tech: String, title: String, cost: f64, time: f64, state: String, progress: f64) -> ResearchItem {
+        ResearchItem {
+            tech,
+            title,
+            cost,
+            time,
+            state,
+            progress
+        }
+    }
+
+    pub fn tech(&self) -> &String {
+        &self.tech
+    }
+
+    pub fn title(&self) -> &String {
+        &self.title
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state == "completed"
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.state == "available"
+    }
+
+    pub fn describe(&self) -> String {
+        match self.state.as_ref() {
+            "researching" => format!("{} ({}%)", self.title, (self.progress * 100.0).floor()),
+            "completed" => format!("{} (done)", self.title),
+            "locked" => format!("{} (locked)", self.title),
+            _ => format!("{} ({} gold, {}s)", self.title, self.cost, self.time)
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use common::SquadClass;
+
+pub struct ClassRules {
+    pub cost: f64,
+    pub supply: f64,
+    pub life: f64,
+    pub speed: f64,
+    pub weapon_range: f64,
+    pub damage: f64
+}
+
+/// Rules in effect for the match as sent by the server at match start
+pub struct Rules {
+    pub starting_gold: f64,
+    pub income_rate: f64,
+    pub planet_income: f64,
+    pub upkeep_per_supply: f64,
+    pub supply_base: f64,
+    pub supply_per_planet: f64,
+    pub merge_distance: f64,
+    pub orbit_distance: f64,
+    pub planet_capture_threshold: f64,
+    pub planetoid_capture_threshold: f64,
+    pub fighter: ClassRules,
+    pub cruiser: ClassRules
+}
+
+impl Rules {
+    pub fn class(&self, class: SquadClass) -> &ClassRules {
+        match class {
+            SquadClass::Fighter => &self.fighter,
+            SquadClass::Cruiser => &self.cruiser
+        }
+    }
+}
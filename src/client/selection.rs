@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use common::{Id, Position};
+
+/// Cursor has to move this many pixels while the button is held to start a box selection
+const DRAG_THRESHOLD: f64 = 4.0;
+
+/// Seconds between two recalls of the same control group counted as a double tap
+const DOUBLE_TAP_INTERVAL: f64 = 0.3;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SelectionMode {
+    Replace,
+    Add,
+    Remove
+}
+
+/// Selected squads, the box being dragged and control groups
+pub struct Selection {
+    squad_ids: HashSet<Id>,
+    drag_start: Option<(f64, f64)>,
+    groups: HashMap<u8, HashSet<Id>>,
+    last_recall: Option<(u8, Instant)>
+}
+
+impl Selection {
+    pub fn new() -> Selection {
+        Selection {
+            squad_ids: HashSet::new(),
+            drag_start: None,
+            groups: HashMap::new(),
+            last_recall: None
+        }
+    }
+
+    pub fn squad_ids(&self) -> &HashSet<Id> {
+        &self.squad_ids
+    }
+
+    pub fn contains(&self, squad_id: Id) -> bool {
+        self.squad_ids.contains(&squad_id)
+    }
+
+    pub fn select(&mut self, squad_ids: Vec<Id>, mode: SelectionMode) {
+        match mode {
+            SelectionMode::Replace => {
+                self.squad_ids = squad_ids.into_iter().collect();
+            },
+
+            SelectionMode::Add => {
+                self.squad_ids.extend(squad_ids);
+            },
+
+            SelectionMode::Remove => {
+                for squad_id in squad_ids {
+                    self.squad_ids.remove(&squad_id);
+                }
+            }
+        }
+    }
+
+    pub fn start_drag(&mut self, cursor_position: (f64, f64)) {
+        self.drag_start = Some(cursor_position);
+    }
+
+    /// Window corners of the box while the cursor has moved far enough from the drag start
+    pub fn drag_box(&self, cursor_position: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+        let drag_start = match self.drag_start {
+            Some(drag_start) => drag_start,
+            None => return None
+        };
+
+        let distance = Position(drag_start.0, drag_start.1).distance_to(Position(cursor_position.0, cursor_position.1));
+
+        if distance < DRAG_THRESHOLD {
+            None
+        } else {
+            Some((drag_start, cursor_position))
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag_start.is_some()
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag_start = None;
+    }
+
+    pub fn assign_group(&mut self, group: u8) {
+        let squad_ids = self.squad_ids.clone();
+        self.groups.insert(group, squad_ids);
+    }
+
+    /// Selects squads of the group and returns whether the group has been recalled twice in a row quickly
+    pub fn recall_group(&mut self, group: u8) -> bool {
+        self.recall_group_at(group, Instant::now())
+    }
+
+    fn recall_group_at(&mut self, group: u8, now: Instant) -> bool {
+        let squad_ids = match self.groups.get(&group) {
+            Some(squad_ids) => squad_ids.clone(),
+            None => return false
+        };
+
+        self.squad_ids = squad_ids;
+
+        let is_double_tap = match self.last_recall {
+            Some((last_group, last_recall_time)) => {
+                let elapsed = now.duration_since(last_recall_time);
+                last_group == group && elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0 < DOUBLE_TAP_INTERVAL
+            },
+
+            None => false
+        };
+
+        self.last_recall = if is_double_tap { None } else { Some((group, now)) };
+
+        is_double_tap
+    }
+
+    /// Forgets destroyed and merged away squads
+    pub fn retain<F>(&mut self, is_alive: F) where F: Fn(Id) -> bool {
+        self.squad_ids.retain(|&squad_id| is_alive(squad_id));
+
+        for squad_ids in self.groups.values_mut() {
+            squad_ids.retain(|&squad_id| is_alive(squad_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
+
+    use common::Id;
+    use super::{Selection, SelectionMode};
+
+    fn ids(selection: &Selection) -> Vec<Id> {
+        let mut squad_ids = selection.squad_ids().iter().cloned().collect::<Vec<_>>();
+        squad_ids.sort();
+        squad_ids
+    }
+
+    #[test]
+    fn modes() {
+        let mut selection = Selection::new();
+
+        selection.select(vec![1, 2, 3], SelectionMode::Replace);
+        selection.select(vec![3, 4], SelectionMode::Add);
+        assert_eq!(ids(&selection), vec![1, 2, 3, 4]);
+
+        selection.select(vec![2, 4, 5], SelectionMode::Remove);
+        assert_eq!(ids(&selection), vec![1, 3]);
+
+        selection.select(vec![5], SelectionMode::Replace);
+        assert_eq!(ids(&selection), vec![5]);
+        assert!(selection.contains(5) && !selection.contains(1));
+    }
+
+    #[test]
+    fn drag_box() {
+        let mut selection = Selection::new();
+        assert!(selection.drag_box((50.0, 50.0)).is_none());
+
+        selection.start_drag((10.0, 10.0));
+        assert!(selection.is_dragging());
+
+        // A click with a slightly moving cursor is not a box
+        assert!(selection.drag_box((12.0, 11.0)).is_none());
+        assert_eq!(selection.drag_box((40.0, 5.0)), Some(((10.0, 10.0), (40.0, 5.0))));
+
+        selection.end_drag();
+        assert!(!selection.is_dragging());
+        assert!(selection.drag_box((40.0, 5.0)).is_none());
+    }
+
+    #[test]
+    fn control_groups() {
+        let mut selection = Selection::new();
+        let now = Instant::now();
+
+        assert!(!selection.recall_group_at(1, now));
+
+        selection.select(vec![1, 2], SelectionMode::Replace);
+        selection.assign_group(1);
+
+        selection.select(vec![3], SelectionMode::Replace);
+        selection.assign_group(2);
+
+        // Groups keep their squads when the selection changes
+        selection.select(vec![4], SelectionMode::Add);
+        assert!(!selection.recall_group_at(1, now));
+        assert_eq!(ids(&selection), vec![1, 2]);
+
+        assert!(!selection.recall_group_at(2, now));
+        assert_eq!(ids(&selection), vec![3]);
+
+        // Destroyed squads are dropped from groups too
+        let alive = vec![1, 3].into_iter().collect::<HashSet<Id>>();
+        selection.retain(|squad_id| alive.contains(&squad_id));
+        selection.recall_group_at(1, now + Duration::from_secs(1));
+        assert_eq!(ids(&selection), vec![1]);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut selection = Selection::new();
+        let now = Instant::now();
+
+        selection.select(vec![1], SelectionMode::Replace);
+        selection.assign_group(1);
+        selection.select(vec![2], SelectionMode::Replace);
+        selection.assign_group(2);
+
+        assert!(!selection.recall_group_at(1, now));
+        assert!(selection.recall_group_at(1, now + Duration::from_millis(200)));
+
+        // A third tap starts over rather than counting as another double tap
+        assert!(!selection.recall_group_at(1, now + Duration::from_millis(300)));
+
+        // Too slow
+        assert!(!selection.recall_group_at(1, now + Duration::from_millis(700)));
+
+        // Another group in between
+        assert!(!selection.recall_group_at(2, now + Duration::from_millis(800)));
+        assert!(!selection.recall_group_at(1, now + Duration::from_millis(900)));
+    }
+}
@@ -1,19 +1,34 @@
-use common::{Id, PlayerId, Position};
+use common::{Id, PlayerId, Position, SquadClass};
+
+#[derive(Copy, Clone)]
+pub enum SquadState {
+    InSpace,
+    Moving {
+        destination: Option<Id>
+    },
+    OnOrbit {
+        waypoint_id: Id
+    }
+}
 
 pub struct Squad {
     id: Id,
     owner: PlayerId,
+    class: SquadClass,
     position: Position,
-    count: u64
+    count: u64,
+    state: SquadState
 }
 
 impl Squad {
-    pub fn new(id: Id, owner: PlayerId, position: Position, count: u64) -> Squad {
+    pub fn new(id: Id, owner: PlayerId, class: SquadClass, position: Position, count: u64, state: SquadState) -> Squad {
         Squad {
             id: id,
             owner: owner,
+            class: class,
             position: position,
-            count: count
+            count: count,
+            state: state
         }
     }
 
@@ -25,6 +40,10 @@ impl Squad {
         self.owner
     }
 
+    pub fn class(&self) -> SquadClass {
+        self.class
+    }
+
     pub fn position(&self) -> Position {
         self.position
     }
@@ -40,4 +59,8 @@ impl Squad {
     pub fn set_count(&mut self, count: u64) {
         self.count = count;
     }
+
+    pub fn state(&self) -> SquadState {
+        self.state
+    }
 }
\ No newline at end of file
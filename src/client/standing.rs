@@ -0,0 +1,97 @@
+use common::PlayerId;
+
+pub struct Standing {
+    player_id: PlayerId,
+    name: String,
+    place: u64,
+    is_winner: bool,
+    score: f64,
+    planets_count: u64,
+    squads_count: u64,
+    squads_built: u64,
+    squads_destroyed: u64,
+    planets_captured: u64,
+    /// Rating after the match, only for players rated by the server
+    rating: Option<f64>,
+    rating_change: Option<f64>
+}
+
+impl Standing {
+    pub fn new(
+        player_id: PlayerId,
+        name: String,
+        place: u64,
+        is_winner: bool,
+        score: f64,
+        planets_count: u64,
+        squads_count: u64,
+        squads_built: u64,
+        squads_destroyed: u64,
+        planets_captured: u64,
+        rating: Option<f64>,
+        rating_change: Option<f64>
+    ) -> Standing {
+        Standing {
+            player_id,
+            name,
+            place,
+            is_winner,
+            score,
+            planets_count,
+            squads_count,
+            squads_built,
+            squads_destroyed,
+            planets_captured,
+            rating,
+            rating_change
+        }
+    }
+
+    pub fn player_id(&self) -> PlayerId {
+        self.player_id
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn place(&self) -> u64 {
+        self.place
+    }
+
+    pub fn is_winner(&self) -> bool {
+        self.is_winner
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn planets_count(&self) -> u64 {
+        self.planets_count
+    }
+
+    pub fn squads_count(&self) -> u64 {
+        self.squads_count
+    }
+
+    pub fn squads_built(&self) -> u64 {
+        self.squads_built
+    }
+
+    pub fn squads_destroyed(&self) -> u64 {
+        self.squads_destroyed
+    }
+
+    pub fn planets_captured(&self) -> u64 {
+        self.planets_captured
+    }
+
+    pub fn rating(&self) -> Option<f64> {
+        self.rating
+    }
+
+    pub fn rating_change(&self) -> Option<f64> {
+        self.rating_change
+    }
+}
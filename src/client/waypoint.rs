@@ -8,20 +8,33 @@ pub enum WaypointType {
     BlackHole
 }
 
+impl WaypointType {
+    pub fn title(&self) -> &'static str {
+        match *self {
+            WaypointType::Planet => "Planet",
+            WaypointType::Planetoid => "Planetoid",
+            WaypointType::Asteroid => "Asteroid",
+            WaypointType::BlackHole => "Black hole"
+        }
+    }
+}
+
 pub struct Waypoint {
     id: Id,
     waypoint_type: WaypointType,
     owner: Option<PlayerId>,
-    position: Position
+    position: Position,
+    capture: Option<(PlayerId, f64)>
 }
 
 impl Waypoint {
-    pub fn new(id: Id, waypoint_type: WaypointType, position: Position, owner: Option<PlayerId>) -> Waypoint {
+    pub fn new(id: Id, waypoint_type: WaypointType, position: Position, owner: Option<PlayerId>, capture: Option<(PlayerId, f64)>) -> Waypoint {
         Waypoint {
             id,
             waypoint_type,
             position,
-            owner
+            owner,
+            capture
         }
     }
 
@@ -44,4 +57,9 @@ impl Waypoint {
     pub fn set_owner(&mut self, owner: Option<PlayerId>) {
         self.owner = owner;
     }
-}
\ No newline at end of file
+
+    /// Player capturing the waypoint and the capture progress from 0.0 to 1.0
+    pub fn capture(&self) -> Option<(PlayerId, f64)> {
+        self.capture
+    }
+}
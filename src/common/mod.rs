@@ -2,12 +2,19 @@ use rustc_serialize::json::ParserError;
 
 use ws::Error;
 
+pub mod rate_limit;
 pub mod to_command;
 pub mod utils;
 pub mod websocket_handler;
 
 pub type Id = u64;
 pub type PlayerId = usize;
+pub type TeamId = u64;
+/// Index of a player color in the palettes of the client
+pub type ColorId = u64;
+
+/// Count of distinct player colors, players beyond it share colors
+pub const PLAYER_COLORS_COUNT: ColorId = 8;
 
 #[derive(Copy, Clone)]
 pub struct Position(pub f64, pub f64);
@@ -18,6 +25,29 @@ impl Position {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SquadClass {
+    Fighter,
+    Cruiser
+}
+
+impl SquadClass {
+    pub fn from_name(name: &str) -> Option<SquadClass> {
+        match name {
+            "fighter" => Some(SquadClass::Fighter),
+            "cruiser" => Some(SquadClass::Cruiser),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SquadClass::Fighter => "fighter",
+            SquadClass::Cruiser => "cruiser"
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseCommandError {
     ParserError(ParserError),
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::utils::token_bucket::TokenBucket;
+
+/// Longest message accepted from a client, long commands are split by the client to fit it
+pub const MAX_MESSAGE_SIZE: usize = 4 * 1024;
+
+/// Limits applied to every message received by a websocket connection
+pub struct RateLimits {
+    /// Longer messages are dropped without parsing
+    pub max_message_size: usize,
+    /// Connection is closed once it has made that many violations
+    pub max_violations: f64,
+    /// Violations forgiven per second, so that rare mistakes never add up to a disconnect
+    pub violation_decay: f64,
+    /// Burst and refill per second for actions without their own limit
+    pub default_limit: (f64, f64),
+    limits: HashMap<&'static str, (f64, f64)>
+}
+
+impl RateLimits {
+    pub fn new(max_message_size: usize, max_violations: f64, violation_decay: f64, default_limit: (f64, f64)) -> Self {
+        RateLimits {
+            max_message_size,
+            max_violations,
+            violation_decay,
+            default_limit,
+            limits: HashMap::new()
+        }
+    }
+
+    pub fn limit(mut self, action: &'static str, burst: f64, rate: f64) -> Self {
+        self.limits.insert(action, (burst, rate));
+        self
+    }
+
+    pub fn create_bucket(&self, action: &'static str) -> TokenBucket {
+        let (burst, rate) = self.limits.get(action).cloned().unwrap_or(self.default_limit);
+        TokenBucket::new(burst, rate)
+    }
+}
+
+/// Totals over all connections, used to tune the limits
+pub struct RateLimitCounters {
+    pub messages: AtomicUsize,
+    pub oversized: AtomicUsize,
+    pub malformed: AtomicUsize,
+    pub rate_limited: AtomicUsize,
+    pub disconnected: AtomicUsize
+}
+
+impl RateLimitCounters {
+    pub fn new() -> Self {
+        RateLimitCounters {
+            messages: AtomicUsize::new(0),
+            oversized: AtomicUsize::new(0),
+            malformed: AtomicUsize::new(0),
+            rate_limited: AtomicUsize::new(0),
+            disconnected: AtomicUsize::new(0)
+        }
+    }
+
+    pub fn increment(counter: &AtomicUsize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "messages: {}, oversized: {}, malformed: {}, rate limited: {}, disconnected: {}",
+            self.messages.load(Ordering::Relaxed),
+            self.oversized.load(Ordering::Relaxed),
+            self.malformed.load(Ordering::Relaxed),
+            self.rate_limited.load(Ordering::Relaxed),
+            self.disconnected.load(Ordering::Relaxed)
+        )
+    }
+}
@@ -10,4 +10,7 @@ pub trait ToCommand {
         where Self: Sized;
 
     fn disconnect(sender: Sender) -> Self;
+
+    /// Name of the command kind, rate limits are applied per action
+    fn action(&self) -> &'static str;
 }
\ No newline at end of file
@@ -67,6 +67,16 @@ pub fn parse_f64_from_json_object(object: &Object, property: &str) -> Result<f64
         .ok_or(incompatible_type_error(property))
 }
 
+pub fn parse_id_pair(json: &Json) -> Result<(Id, Id)> {
+    let pair = json.as_array()
+        .ok_or(incompatible_type_error(""))?;
+
+    match (pair.get(0).and_then(Json::as_u64), pair.get(1).and_then(Json::as_u64), pair.len()) {
+        (Some(first), Some(second), 2) => Ok((first as Id, second as Id)),
+        _ => Err(incompatible_type_error(""))
+    }
+}
+
 fn parse_value_from_json_object<'a>(object: &'a Object, property: &str) -> Result<&'a Json> {
     object.get(property)
         .ok_or(missed_property_error(property))
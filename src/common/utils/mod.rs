@@ -1,4 +1,5 @@
 pub mod json;
+pub mod token_bucket;
 
 pub fn join<S: ToString>(vec: Vec<S>, sep: &str) -> String {
     vec
@@ -0,0 +1,74 @@
+use time;
+
+/// Allows bursts of up to `capacity` actions, refilled at `rate` actions per second
+pub struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    tokens: f64,
+    last_time: f64
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64, rate: f64) -> Self {
+        TokenBucket {
+            capacity,
+            rate,
+            tokens: capacity,
+            last_time: time::precise_time_s()
+        }
+    }
+
+    /// Takes a token if there is one left, returns whether the action is allowed
+    pub fn take(&mut self) -> bool {
+        self.take_at(time::precise_time_s())
+    }
+
+    /// Same as `take` at the given time in seconds, which must not go back
+    pub fn take_at(&mut self, now: f64) -> bool {
+        self.tokens = (self.tokens + (now - self.last_time) * self.rate).min(self.capacity);
+        self.last_time = now;
+
+        if self.tokens >= 1_f64 {
+            self.tokens -= 1_f64;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenBucket;
+
+    #[test]
+    fn burst() {
+        let mut bucket = TokenBucket::new(3.0, 0.5);
+        let start = bucket.last_time;
+
+        assert!(bucket.take_at(start));
+        assert!(bucket.take_at(start));
+        assert!(bucket.take_at(start));
+        assert!(!bucket.take_at(start));
+        assert!(!bucket.take_at(start + 1.0));
+    }
+
+    #[test]
+    fn refill() {
+        let mut bucket = TokenBucket::new(2.0, 0.5);
+        let start = bucket.last_time;
+
+        assert!(bucket.take_at(start));
+        assert!(bucket.take_at(start));
+
+        // A token comes back every two seconds
+        assert!(!bucket.take_at(start + 1.5));
+        assert!(bucket.take_at(start + 2.0));
+        assert!(!bucket.take_at(start + 2.0));
+
+        // Tokens are never stored above the capacity however long the bucket is idle
+        assert!(bucket.take_at(start + 100.0));
+        assert!(bucket.take_at(start + 100.0));
+        assert!(!bucket.take_at(start + 100.0));
+    }
+}
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::sync::mpsc::Sender as ChannelSender;
+use time;
 use ws::{
     CloseCode,
     Handler,
@@ -8,16 +11,78 @@ use ws::{
     Message
 };
 
+use common::rate_limit::{RateLimitCounters, RateLimits};
 use common::to_command::ToCommand;
+use common::utils::token_bucket::TokenBucket;
 
 pub struct WebsocketHandler<C> {
     sender: Sender,
-    tx: ChannelSender<C>
+    tx: ChannelSender<C>,
+    rate_limits: Option<(Arc<RateLimits>, Arc<RateLimitCounters>)>,
+    buckets: HashMap<&'static str, TokenBucket>,
+    violations: f64,
+    last_violation_time: f64
 }
 
 impl<C> WebsocketHandler<C> {
     pub fn new(sender: Sender, tx: ChannelSender<C>) -> Self {
-        WebsocketHandler { sender: sender, tx: tx }
+        WebsocketHandler {
+            sender: sender,
+            tx: tx,
+            rate_limits: None,
+            buckets: HashMap::new(),
+            violations: 0.0,
+            last_violation_time: 0.0
+        }
+    }
+
+    /// Handler for an untrusted peer. Oversized, malformed and too frequent messages are dropped
+    /// and counted as violations, the connection is closed after too many of them.
+    pub fn with_rate_limits(sender: Sender, tx: ChannelSender<C>, rate_limits: Arc<RateLimits>, counters: Arc<RateLimitCounters>) -> Self {
+        WebsocketHandler {
+            rate_limits: Some((rate_limits, counters)),
+            .. Self::new(sender, tx)
+        }
+    }
+
+    /// Returns whether one more command of the action is allowed, always true without limits
+    fn take_token(&mut self, action: &'static str) -> bool {
+        let (rate_limits, counters) = match self.rate_limits {
+            Some((ref rate_limits, ref counters)) => (rate_limits, counters),
+            None => return true
+        };
+
+        let is_allowed = self.buckets
+            .entry(action)
+            .or_insert_with(|| rate_limits.create_bucket(action))
+            .take();
+
+        if !is_allowed {
+            RateLimitCounters::increment(&counters.rate_limited);
+        }
+
+        is_allowed
+    }
+
+    fn add_violation(&mut self) -> Result<()> {
+        self.add_violation_at(time::precise_time_s())
+    }
+
+    fn add_violation_at(&mut self, now: f64) -> Result<()> {
+        if let Some((ref rate_limits, ref counters)) = self.rate_limits {
+            let decay = (now - self.last_violation_time) * rate_limits.violation_decay;
+            self.violations = (self.violations - decay).max(0.0) + 1.0;
+            self.last_violation_time = now;
+
+            if self.violations >= rate_limits.max_violations {
+                RateLimitCounters::increment(&counters.disconnected);
+                println!("Closing connection {:?} after {} violations", self.sender.token(), self.violations);
+
+                return self.sender.close_with_reason(CloseCode::Policy, "Too many invalid or too frequent messages");
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -30,14 +95,47 @@ impl<C: ToCommand> Handler for WebsocketHandler<C> {
     }
 
     fn on_message(&mut self, message: Message) -> Result<()> {
+        let is_oversized = match self.rate_limits {
+            Some((ref rate_limits, ref counters)) => {
+                RateLimitCounters::increment(&counters.messages);
+
+                let is_oversized = message.len() > rate_limits.max_message_size;
+                if is_oversized {
+                    RateLimitCounters::increment(&counters.oversized);
+                }
+
+                is_oversized
+            },
+
+            None => false
+        };
+
+        if is_oversized {
+            return self.add_violation();
+        }
+
         match C::process(self.sender.clone(), &message) {
             Ok(command) => {
+                if !self.take_token(command.action()) {
+                    return self.add_violation();
+                }
+
                 self.tx.send(command);
             },
 
             Err(err) => {
                 println!("Error on processing command: {:?}", err);
-                println!("Message: {}", message);
+
+                let counters = self.rate_limits.as_ref().map(|&(_, ref counters)| counters.clone());
+
+                match counters {
+                    Some(counters) => {
+                        RateLimitCounters::increment(&counters.malformed);
+                        return self.add_violation();
+                    },
+
+                    None => println!("Message: {}", message)
+                }
             }
         }
 
@@ -48,4 +146,131 @@ impl<C: ToCommand> Handler for WebsocketHandler<C> {
         let disconnect_command = C::disconnect(self.sender.clone());
         self.tx.send(disconnect_command);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc::{channel, Receiver};
+    use ws::{Handler, Message, Result, Sender, WebSocket};
+
+    use common::{ParseCommandError, ParseCommandResult};
+    use common::rate_limit::{RateLimitCounters, RateLimits};
+    use common::to_command::ToCommand;
+    use super::WebsocketHandler;
+
+    enum TestCommand {
+        Connect,
+        Say(String),
+        Disconnect
+    }
+
+    impl ToCommand for TestCommand {
+        fn connect(_: Sender) -> Self {
+            TestCommand::Connect
+        }
+
+        fn process(_: Sender, message: &Message) -> ParseCommandResult<Self> {
+            match message.as_text() {
+                Ok(text) if text.starts_with("say ") => Ok(TestCommand::Say(text[4..].to_string())),
+                _ => Err(ParseCommandError::UnsupportedAction)
+            }
+        }
+
+        fn disconnect(_: Sender) -> Self {
+            TestCommand::Disconnect
+        }
+
+        fn action(&self) -> &'static str {
+            match *self {
+                TestCommand::Connect => "connect",
+                TestCommand::Say(_) => "say",
+                TestCommand::Disconnect => "disconnect"
+            }
+        }
+    }
+
+    fn create_handler(max_violations: f64) -> (WebsocketHandler<TestCommand>, Receiver<TestCommand>, Arc<RateLimitCounters>) {
+        // Closing the connection goes nowhere, the socket is never run
+        let websocket = WebSocket::new(|_: Sender| |_: Message| -> Result<()> { Ok(()) }).unwrap();
+        let (tx, rx) = channel();
+
+        let rate_limits = RateLimits::new(16, max_violations, 1.0, (100.0, 1.0))
+            .limit("say", 2.0, 0.1);
+        let counters = Arc::new(RateLimitCounters::new());
+
+        let handler = WebsocketHandler::with_rate_limits(websocket.broadcaster(), tx, Arc::new(rate_limits), counters.clone());
+
+        (handler, rx, counters)
+    }
+
+    fn said(rx: &Receiver<TestCommand>) -> Vec<String> {
+        rx.try_iter()
+            .filter_map(|command| match command {
+                TestCommand::Say(text) => Some(text),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn oversized_message() {
+        let (mut handler, rx, counters) = create_handler(10.0);
+
+        handler.on_message(Message::text("say hello")).unwrap();
+        handler.on_message(Message::text("say hello to everyone here")).unwrap();
+
+        assert_eq!(said(&rx), vec!["hello".to_string()]);
+        assert_eq!(counters.messages.load(Ordering::Relaxed), 2);
+        assert_eq!(counters.oversized.load(Ordering::Relaxed), 1);
+        assert_eq!(handler.violations, 1.0);
+    }
+
+    #[test]
+    fn action_limit() {
+        let (mut handler, rx, counters) = create_handler(10.0);
+
+        for _ in 0..4 {
+            handler.on_message(Message::text("say hi")).unwrap();
+        }
+
+        handler.on_message(Message::text("shout hi")).unwrap();
+
+        assert_eq!(said(&rx).len(), 2);
+        assert_eq!(counters.rate_limited.load(Ordering::Relaxed), 2);
+        assert_eq!(counters.malformed.load(Ordering::Relaxed), 1);
+
+        // Violations decay a little while the test runs
+        assert!((handler.violations - 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn violations_decay() {
+        let (mut handler, _rx, counters) = create_handler(3.0);
+
+        handler.add_violation_at(100.0).unwrap();
+        handler.add_violation_at(100.0).unwrap();
+
+        // One violation per second is forgiven
+        handler.add_violation_at(101.5).unwrap();
+        assert!((handler.violations - 1.5).abs() < 1e-9);
+
+        handler.add_violation_at(200.0).unwrap();
+        assert_eq!(handler.violations, 1.0);
+        assert_eq!(counters.disconnected.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn close_at_threshold() {
+        let (mut handler, _rx, counters) = create_handler(3.0);
+
+        handler.add_violation_at(100.0).unwrap();
+        handler.add_violation_at(100.0).unwrap();
+        assert_eq!(counters.disconnected.load(Ordering::Relaxed), 0);
+
+        // The socket is never run, so the close itself fails to be queued
+        let _ = handler.add_violation_at(100.0);
+        assert_eq!(counters.disconnected.load(Ordering::Relaxed), 1);
+    }
+}
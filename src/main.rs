@@ -1,3 +1,5 @@
+#![cfg_attr(feature = "bench", feature(test))]
+
 extern crate getopts;
 extern crate ws;
 extern crate rand;
@@ -11,6 +13,8 @@ extern crate glium;
 extern crate glium_text_rusttype;
 extern crate image;
 extern crate time;
+extern crate sha2;
+extern crate constant_time_eq;
 
 use getopts::Options;
 use std::env;
@@ -22,10 +26,12 @@ mod common;
 mod client;
 mod server;
 
-fn run_server(server_address: Option<String>) -> Option<JoinHandle<()>> {
+use server::settings::{Settings, VictoryCondition};
+
+fn run_server(server_address: Option<String>, settings: Settings) -> Option<JoinHandle<()>> {
     server_address.map(|address| {
         println!("Starting server on {}", address);
-        thread::spawn(|| server::run(address))
+        thread::spawn(|| server::run(address, settings))
     })
 }
 
@@ -36,6 +42,28 @@ fn main() {
     let mut opts = Options::new();
     opts.optopt("c", "client", "address and port of server to connect", "127.0.0.1:9999");
     opts.optopt("s", "server", "address and port for server binding", "127.0.0.1:9999");
+    opts.optopt("m", "map", "path to map file for server", "map.json");
+    opts.optopt("r", "rules", "path to game rules file for server", "rules.json");
+    opts.optflag("l", "lanes", "restrict squads movement to hyperlanes between waypoints");
+    opts.optopt("", "black-hole-cost", "extra route cost of entering a black hole", "0");
+    opts.optopt("", "hostile-cost", "extra route cost of entering an enemy waypoint", "0");
+    opts.optopt("", "victory", "victory condition: conquest, score, hill or economic", "conquest");
+    opts.optopt("", "victory-target", "time limit, hill hold time in seconds or gold target", "600");
+    opts.optflag("", "diplomacy", "allow players to make alliances and ceasefires during the match");
+    opts.optflag("", "fog", "hide enemy squads out of vision range of own squads and waypoints");
+    opts.optflag("", "shared-vision", "share vision between allies under fog of war");
+    opts.optopt("", "password", "password required to join the server", "PASSWORD");
+    opts.optmulti("", "invite", "single use token allowing to join the server, may be repeated", "TOKEN");
+    opts.optopt("", "admin-token", "token to join the server with admin rights", "TOKEN");
+    opts.optflag("", "console", "read admin commands for server from stdin");
+    opts.optopt("", "dump-dir", "directory where remote admins may dump server state", "DIR");
+    opts.optopt("", "rules-dir", "directory remote admins may reload server rules from", "DIR");
+    opts.optopt("", "stats", "path to file keeping player statistics and ratings for server", "stats.json");
+    opts.optopt("t", "token", "password, invite, admin or session token used by client to join", "TOKEN");
+    opts.optopt("n", "name", "player name used by client", "NAME");
+    opts.optopt("", "profile", "secret token owning the player name in server statistics", "TOKEN");
+    opts.optopt("", "controls", "path to key bindings file for client", "controls.json");
+    opts.optflag("", "colorblind", "use colorblind-safe player colors in client");
     opts.optflag("h", "help", "print this help message");
 
     let matches = match opts.parse(&args[1..]) {
@@ -49,14 +77,45 @@ fn main() {
         return;
     }
 
+    let mut settings = Settings::new();
+    settings.map_path = matches.opt_str("m");
+    settings.rules_path = matches.opt_str("r");
+    settings.hyperlanes = matches.opt_present("l");
+
+    if let Some(cost) = matches.opt_str("black-hole-cost") {
+        settings.black_hole_lane_cost = cost.parse().expect("Invalid black hole cost");
+    }
+
+    if let Some(cost) = matches.opt_str("hostile-cost") {
+        settings.hostile_lane_cost = cost.parse().expect("Invalid hostile cost");
+    }
+
+    if let Some(victory) = matches.opt_str("victory") {
+        let target = matches.opt_str("victory-target")
+            .map(|target| target.parse().expect("Invalid victory target"));
+
+        settings.victory = VictoryCondition::from_name(&victory, target).expect("Unknown victory condition");
+    }
+
+    settings.diplomacy = matches.opt_present("diplomacy");
+    settings.fog_of_war = matches.opt_present("fog");
+    settings.shared_vision = matches.opt_present("shared-vision");
+    settings.password = matches.opt_str("password");
+    settings.invite_tokens = matches.opt_strs("invite");
+    settings.admin_token = matches.opt_str("admin-token");
+    settings.admin_console = matches.opt_present("console");
+    settings.dump_dir = matches.opt_str("dump-dir");
+    settings.rules_dir = matches.opt_str("rules-dir");
+    settings.stats_path = matches.opt_str("stats");
+
     let server_address = matches.opt_str("s");
-    let server_thread = run_server(server_address);
+    let server_thread = run_server(server_address, settings);
 
     let client_address = matches.opt_str("c");
     match client_address {
         Some(address) => {
             thread::sleep(Duration::from_secs(1));
-            client::run(address);
+            client::run(address, matches.opt_str("t"), matches.opt_str("n"), matches.opt_str("profile"), matches.opt_str("controls"), matches.opt_present("colorblind"));
         },
 
         None => {
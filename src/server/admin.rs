@@ -0,0 +1,78 @@
+use std::io;
+use std::io::BufRead;
+use std::sync::mpsc::Sender as ChannelSender;
+use std::thread;
+
+use common::PlayerId;
+
+pub enum AdminCommand {
+    List,
+    Kick { player_id: PlayerId },
+    Pause,
+    Resume,
+    Speed { factor: f64 },
+    /// Starts the match even if not every player is ready
+    Start,
+    /// Finishes the match with standings by current score
+    End,
+    /// Path is any from the console, a remote admin may only give a file name in the dump directory
+    Dump { path: String },
+    /// Reloads rules from the given path or from the one the server has been started with, a remote
+    /// admin may only give a file name in the rules directory
+    ReloadRules { path: Option<String> }
+}
+
+pub const HELP: &'static str = "commands: list, kick <player id>, pause, resume, speed <factor>, start, end, dump <path>, reload-rules [path]";
+
+impl AdminCommand {
+    pub fn parse(line: &str) -> Result<AdminCommand, String> {
+        let mut words = line.split_whitespace();
+
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Err(HELP.to_string())
+        };
+
+        let argument = words.next();
+
+        match (name, argument) {
+            ("list", _) => Ok(AdminCommand::List),
+            ("kick", Some(player_id)) => {
+                player_id.parse()
+                    .map(|player_id| AdminCommand::Kick { player_id })
+                    .map_err(|_| format!("Invalid player id: {}", player_id))
+            },
+            ("pause", _) => Ok(AdminCommand::Pause),
+            ("resume", _) => Ok(AdminCommand::Resume),
+            ("speed", Some(factor)) => {
+                factor.parse()
+                    .map(|factor| AdminCommand::Speed { factor })
+                    .map_err(|_| format!("Invalid speed: {}", factor))
+            },
+            ("start", _) => Ok(AdminCommand::Start),
+            ("end", _) => Ok(AdminCommand::End),
+            ("dump", Some(path)) => Ok(AdminCommand::Dump { path: path.to_string() }),
+            ("reload-rules", path) => Ok(AdminCommand::ReloadRules { path: path.map(|path| path.to_string()) }),
+            _ => Err(HELP.to_string())
+        }
+    }
+}
+
+/// Reads admin commands from stdin line by line, the server replies to stdout
+pub fn run_console(tx: ChannelSender<String>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                },
+
+                Err(_) => break
+            }
+        }
+    });
+}
@@ -0,0 +1,98 @@
+use common::{PlayerId, Position};
+
+/// Longer messages are cut to this count of characters
+pub const MAX_MESSAGE_LENGTH: usize = 200;
+
+/// Messages burst and refill per second allowed for a single player
+pub const MESSAGES_BURST: f64 = 5.0;
+pub const MESSAGES_RATE: f64 = 0.5;
+
+/// Pings burst and refill per second allowed for a single player
+pub const PINGS_BURST: f64 = 3.0;
+pub const PINGS_RATE: f64 = 0.5;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ChatChannel {
+    All,
+    /// Teammates and allies
+    Team,
+    Private { player_id: PlayerId }
+}
+
+impl ChatChannel {
+    pub fn from_name(name: &str, player_id: Option<PlayerId>) -> Option<ChatChannel> {
+        match (name, player_id) {
+            ("all", _) => Some(ChatChannel::All),
+            ("team", _) => Some(ChatChannel::Team),
+            ("private", Some(player_id)) => Some(ChatChannel::Private { player_id }),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ChatChannel::All => "all",
+            ChatChannel::Team => "team",
+            ChatChannel::Private { .. } => "private"
+        }
+    }
+}
+
+pub struct ChatMessage {
+    pub from: PlayerId,
+    pub channel: ChatChannel,
+    pub text: String
+}
+
+impl ChatMessage {
+    /// Trims the text and cuts it to `MAX_MESSAGE_LENGTH`, returns `None` for blank messages
+    pub fn new(from: PlayerId, channel: ChatChannel, text: &str) -> Option<ChatMessage> {
+        let text = text.trim();
+
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(ChatMessage {
+            from,
+            channel,
+            text: text.chars().take(MAX_MESSAGE_LENGTH).collect()
+        })
+    }
+}
+
+pub struct Ping {
+    pub from: PlayerId,
+    pub position: Position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChatChannel, ChatMessage, MAX_MESSAGE_LENGTH};
+
+    #[test]
+    fn text_is_trimmed_and_cut() {
+        let message = ChatMessage::new(0, ChatChannel::All, "  gg wp \n").unwrap();
+        assert_eq!(message.text, "gg wp");
+
+        // Characters are counted rather than bytes, so a long message is never cut inside one
+        let long_text = "ж".repeat(MAX_MESSAGE_LENGTH + 10);
+        let message = ChatMessage::new(0, ChatChannel::All, &long_text).unwrap();
+        assert_eq!(message.text.chars().count(), MAX_MESSAGE_LENGTH);
+    }
+
+    #[test]
+    fn blank_message() {
+        assert!(ChatMessage::new(0, ChatChannel::All, "").is_none());
+        assert!(ChatMessage::new(0, ChatChannel::Team, " \t\n ").is_none());
+    }
+
+    #[test]
+    fn channel_names() {
+        assert!(ChatChannel::from_name("all", None) == Some(ChatChannel::All));
+        assert!(ChatChannel::from_name("team", Some(3)) == Some(ChatChannel::Team));
+        assert!(ChatChannel::from_name("private", Some(3)) == Some(ChatChannel::Private { player_id: 3 }));
+        assert!(ChatChannel::from_name("private", None).is_none());
+        assert!(ChatChannel::from_name("shout", None).is_none());
+    }
+}
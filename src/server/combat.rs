@@ -0,0 +1,392 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use common::{Id, PlayerId, Position};
+use server::research::Modifiers;
+use server::rules::Rules;
+use server::squad::{Squad, SquadState};
+use server::waypoint::{Waypoint, WaypointType};
+
+/// Uniform grid bucketing squads by position, so that only nearby cells are scanned for targets
+pub struct SpatialGrid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<Id>>
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> Self {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new()
+        }
+    }
+
+    pub fn insert(&mut self, id: Id, position: Position) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_insert_with(Vec::new).push(id);
+    }
+
+    /// Returns ids from every cell overlapping the square around `position`. Candidates must
+    /// still be checked against the exact distance.
+    pub fn query(&self, position: Position, radius: f64) -> Vec<Id> {
+        let Position(x, y) = position;
+
+        let (min_x, min_y) = self.cell(Position(x - radius, y - radius));
+        let (max_x, max_y) = self.cell(Position(x + radius, y + radius));
+
+        let mut ids = vec![];
+        for cell_x in min_x..(max_x + 1) {
+            for cell_y in min_y..(max_y + 1) {
+                if let Some(cell_ids) = self.cells.get(&(cell_x, cell_y)) {
+                    ids.extend(cell_ids.iter().cloned());
+                }
+            }
+        }
+
+        ids
+    }
+
+    fn cell(&self, position: Position) -> (i64, i64) {
+        let Position(x, y) = position;
+        ((x / self.cell_size).floor() as i64, (y / self.cell_size).floor() as i64)
+    }
+}
+
+/// Damage dealt to a squad during a tick along with the share of every attacking player
+pub struct Hit {
+    pub damage: f64,
+    damage_by_player: HashMap<PlayerId, f64>
+}
+
+impl Hit {
+    fn new() -> Self {
+        Hit {
+            damage: 0.0,
+            damage_by_player: HashMap::new()
+        }
+    }
+
+    fn add(&mut self, player_id: PlayerId, damage: f64) {
+        self.damage += damage;
+        *self.damage_by_player.entry(player_id).or_insert(0_f64) += damage;
+    }
+
+    /// Player who has dealt the most damage, credited with the kill
+    pub fn main_attacker(&self) -> Option<PlayerId> {
+        self.damage_by_player
+            .iter()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(Ordering::Equal))
+            .map(|(&player_id, _)| player_id)
+    }
+}
+
+/// Each squad focuses fire on the single enemy within its weapon range chosen by
+/// `compare_targets`. Moving squads fight too, with reduced attack, so they can be intercepted.
+/// `is_hostile` tells whether squads of the two players fight each other.
+pub fn get_squads_hits<F>(
+    squads: &HashMap<Id, Squad>,
+    waypoints: &HashMap<Id, Waypoint>,
+    rules: &Rules,
+    modifiers: &HashMap<PlayerId, Modifiers>,
+    is_hostile: F
+) -> HashMap<Id, Hit>
+    where F: Fn(PlayerId, PlayerId) -> bool
+{
+    let mut hits: HashMap<Id, Hit> = HashMap::new();
+
+    let max_weapon_range = squads
+        .values()
+        .map(|squad| rules.class(squad.class()).weapon_range)
+        .fold(0_f64, f64::max);
+
+    if max_weapon_range <= 0_f64 {
+        return hits;
+    }
+
+    let mut grid = SpatialGrid::new(max_weapon_range);
+    for squad in squads.values() {
+        grid.insert(squad.id(), squad.position());
+    }
+
+    for combat_squad in squads.values() {
+        let weapon_range = rules.class(combat_squad.class()).weapon_range;
+
+        let target = grid.query(combat_squad.position(), weapon_range)
+            .into_iter()
+            .filter_map(|squad_id| squads.get(&squad_id))
+            .filter(|attacked_squad| {
+                is_hostile(attacked_squad.owner(), combat_squad.owner()) &&
+                    attacked_squad.position().distance_to(combat_squad.position()) <= weapon_range
+            })
+            .min_by(|a, b| compare_targets(rules, combat_squad, a, b));
+
+        if let Some(target) = target {
+            let damage_modifier = modifiers.get(&combat_squad.owner())
+                .map_or(1_f64, |modifiers| modifiers.damage);
+
+            let mut attack = rules.class(combat_squad.class()).damage * damage_modifier * combat_squad.life().ceil();
+
+            if !combat_squad.is_standing() {
+                attack *= rules.moving_attack_factor;
+            }
+
+            attack *= get_defense_factor(rules, target, waypoints);
+
+            hits.entry(target.id()).or_insert_with(Hit::new).add(combat_squad.owner(), attack);
+        }
+    }
+
+    hits
+}
+
+/// Prefers enemies able to shoot back, then the weakest ones, then the nearest ones
+fn compare_targets(rules: &Rules, combat_squad: &Squad, a: &Squad, b: &Squad) -> Ordering {
+    let a_distance = a.position().distance_to(combat_squad.position());
+    let b_distance = b.position().distance_to(combat_squad.position());
+
+    let a_is_threat = a_distance <= rules.class(a.class()).weapon_range;
+    let b_is_threat = b_distance <= rules.class(b.class()).weapon_range;
+
+    b_is_threat.cmp(&a_is_threat)
+        .then_with(|| a.life().partial_cmp(&b.life()).unwrap_or(Ordering::Equal))
+        .then_with(|| a_distance.partial_cmp(&b_distance).unwrap_or(Ordering::Equal))
+}
+
+fn get_defense_factor(rules: &Rules, squad: &Squad, waypoints: &HashMap<Id, Waypoint>) -> f64 {
+    let waypoint = match squad.state() {
+        SquadState::OnOrbit { waypoint_id } => waypoints.get(&waypoint_id),
+        _ => None
+    };
+
+    match waypoint {
+        Some(waypoint) if waypoint.owner() == Some(squad.owner()) => {
+            match waypoint.waypoint_type() {
+                WaypointType::Planet => rules.planet_defense_factor,
+                WaypointType::Planetoid => rules.planetoid_defense_factor,
+                WaypointType::Asteroid | WaypointType::BlackHole => 1.0
+            }
+        },
+
+        _ => 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use common::{Id, PlayerId, Position, SquadClass};
+    use server::research::Modifiers;
+    use server::rules::Rules;
+    use server::squad::{Squad, SquadState};
+    use server::waypoint::{Waypoint, WaypointType};
+    use super::{compare_targets, get_squads_hits};
+
+    /// Reference targeting which checks every squad against every other one. Returns attacking
+    /// players of every target, damage is left to the tests with hand-computed values.
+    fn get_targets_brute_force<F>(squads: &HashMap<Id, Squad>, rules: &Rules, is_hostile: F) -> HashMap<Id, HashSet<PlayerId>>
+        where F: Fn(PlayerId, PlayerId) -> bool
+    {
+        let mut targets: HashMap<Id, HashSet<PlayerId>> = HashMap::new();
+
+        for combat_squad in squads.values() {
+            let weapon_range = rules.class(combat_squad.class()).weapon_range;
+
+            let target = squads
+                .values()
+                .filter(|attacked_squad| {
+                    is_hostile(attacked_squad.owner(), combat_squad.owner()) &&
+                        attacked_squad.position().distance_to(combat_squad.position()) <= weapon_range
+                })
+                .min_by(|a, b| compare_targets(rules, combat_squad, a, b));
+
+            if let Some(target) = target {
+                targets.entry(target.id()).or_insert_with(HashSet::new).insert(combat_squad.owner());
+            }
+        }
+
+        targets
+    }
+
+    fn insert_squad(squads: &mut HashMap<Id, Squad>, id: Id, owner: PlayerId, class: SquadClass, position: Position, life: f64) {
+        squads.insert(id, Squad::new(id, owner, class, position, life));
+    }
+
+    fn assert_damage(damage: f64, expected_damage: f64) {
+        assert!((damage - expected_damage).abs() < 1e-9, "damage {} instead of {}", damage, expected_damage);
+    }
+
+    #[test]
+    fn grid_matches_brute_force() {
+        let mut rules = Rules::new();
+        rules.cruiser.weapon_range = 40.0;
+
+        let waypoints = HashMap::new();
+        let modifiers = HashMap::new();
+
+        // Every squad has an owner of its own, so attacking players name the attacking squads.
+        // Squads are scattered over a few grid cells with distinct lives, so that targets are
+        // never tied and both versions must pick the same ones.
+        let mut squads = HashMap::new();
+        for i in 0..80 {
+            let id = i as Id;
+            let position = Position(((i * 37) % 101) as f64 * 1.5 - 75.0, ((i * 53) % 97) as f64 * 1.5 - 75.0);
+            let class = if i % 3 == 0 { SquadClass::Cruiser } else { SquadClass::Fighter };
+
+            insert_squad(&mut squads, id, i, class, position, 1.0 + i as f64 * 0.13);
+        }
+
+        // Owners fall into four sides, the first two of which are allies
+        let is_hostile = |a: PlayerId, b: PlayerId| a % 4 != b % 4 && !(a % 4 < 2 && b % 4 < 2);
+
+        let hits = get_squads_hits(&squads, &waypoints, &rules, &modifiers, is_hostile);
+        let expected_targets = get_targets_brute_force(&squads, &rules, is_hostile);
+
+        assert!(expected_targets.len() > 10);
+        assert_eq!(hits.len(), expected_targets.len());
+
+        for (squad_id, expected_attackers) in &expected_targets {
+            let attackers = hits[squad_id].damage_by_player.keys().cloned().collect::<HashSet<_>>();
+            assert_eq!(&attackers, expected_attackers);
+        }
+    }
+
+    #[test]
+    fn target_priority() {
+        let rules = Rules::new();
+        let waypoints = HashMap::new();
+        let modifiers = HashMap::new();
+
+        let mut squads = HashMap::new();
+        insert_squad(&mut squads, 1, 0, SquadClass::Cruiser, Position(0.0, 0.0), 10.0);
+        // Out of fighters' range, cannot shoot back
+        insert_squad(&mut squads, 2, 1, SquadClass::Fighter, Position(20.0, 0.0), 1.0);
+        insert_squad(&mut squads, 3, 1, SquadClass::Fighter, Position(0.0, 8.0), 5.0);
+        insert_squad(&mut squads, 4, 1, SquadClass::Fighter, Position(0.0, -5.0), 3.0);
+        // Out of the cruiser's range
+        insert_squad(&mut squads, 5, 1, SquadClass::Cruiser, Position(-30.0, 0.0), 0.5);
+
+        let hits = get_squads_hits(&squads, &waypoints, &rules, &modifiers, |a, b| a != b);
+
+        // The weakest of the squads able to shoot back is chosen by the cruiser, 10 ships by 0.6
+        assert!(!hits.contains_key(&2) && !hits.contains_key(&3) && !hits.contains_key(&5));
+        assert_damage(hits[&4].damage, 6.0);
+        assert_eq!(hits[&4].main_attacker(), Some(0));
+
+        // Only the fighters close enough shoot back, 5 and 3 ships by 1.0
+        assert_damage(hits[&1].damage, 8.0);
+        assert_eq!(hits[&1].main_attacker(), Some(1));
+    }
+
+    #[test]
+    fn defense() {
+        let rules = Rules::new();
+        let modifiers = HashMap::new();
+
+        let mut waypoints = HashMap::new();
+        let mut planet = Waypoint::new(10, WaypointType::Planet, Position(5.0, 0.0));
+        planet.set_owner(Some(1));
+        waypoints.insert(10, planet);
+        waypoints.insert(11, Waypoint::new(11, WaypointType::Planet, Position(-5.0, 0.0)));
+
+        let mut squads = HashMap::new();
+        insert_squad(&mut squads, 1, 0, SquadClass::Fighter, Position(0.0, 0.0), 4.0);
+        insert_squad(&mut squads, 2, 1, SquadClass::Fighter, Position(5.0, 0.0), 4.0);
+        squads.get_mut(&1).unwrap().set_state(SquadState::OnOrbit { waypoint_id: 11 });
+        squads.get_mut(&2).unwrap().set_state(SquadState::OnOrbit { waypoint_id: 10 });
+
+        let hits = get_squads_hits(&squads, &waypoints, &rules, &modifiers, |a, b| a != b);
+
+        // Only the owner of the planet is defended by it, halving 4 damage
+        assert_damage(hits[&2].damage, 2.0);
+        assert_damage(hits[&1].damage, 4.0);
+
+        // A moving squad attacks with half strength
+        squads.get_mut(&1).unwrap().move_to(Position(10.0, 0.0));
+
+        let hits = get_squads_hits(&squads, &waypoints, &rules, &modifiers, |a, b| a != b);
+        assert_damage(hits[&2].damage, 1.0);
+    }
+
+    #[test]
+    fn damage_factors_combine() {
+        let rules = Rules::new();
+
+        let mut waypoints = HashMap::new();
+        let mut planetoid = Waypoint::new(10, WaypointType::Planetoid, Position(5.0, 0.0));
+        planetoid.set_owner(Some(1));
+        waypoints.insert(10, planetoid);
+
+        let mut modifiers = HashMap::new();
+        let mut modifier = Modifiers::new();
+        modifier.damage = 1.5;
+        modifiers.insert(0, modifier);
+
+        let mut squads = HashMap::new();
+        // A damaged ship still fires with full strength
+        insert_squad(&mut squads, 1, 0, SquadClass::Fighter, Position(0.0, 0.0), 3.2);
+        insert_squad(&mut squads, 2, 1, SquadClass::Cruiser, Position(5.0, 0.0), 2.0);
+        squads.get_mut(&1).unwrap().move_to(Position(10.0, 0.0));
+        squads.get_mut(&2).unwrap().set_state(SquadState::OnOrbit { waypoint_id: 10 });
+
+        let hits = get_squads_hits(&squads, &waypoints, &rules, &modifiers, |a, b| a != b);
+
+        // 4 ships by 1.0 damage, 1.5 modifier, 0.5 while moving and 0.75 planetoid defense
+        assert_damage(hits[&2].damage, 2.25);
+        // 2 ships by 0.6 damage against a squad in space
+        assert_damage(hits[&1].damage, 1.2);
+    }
+}
+
+#[cfg(all(feature = "bench", test))]
+mod bench {
+    extern crate test;
+
+    use std::collections::HashMap;
+
+    use rand::random;
+
+    use common::{Id, Position, SquadClass};
+    use server::rules::Rules;
+    use server::squad::Squad;
+    use super::get_squads_hits;
+
+    fn generate_squads(count: usize) -> HashMap<Id, Squad> {
+        let mut squads = HashMap::new();
+
+        for i in 0..count {
+            let id = i as Id;
+            let position = Position(random::<f64>() * 2000.0 - 1000.0, random::<f64>() * 2000.0 - 1000.0);
+            let class = if i % 3 == 0 { SquadClass::Cruiser } else { SquadClass::Fighter };
+
+            squads.insert(id, Squad::new(id, i % 4, class, position, 10.0));
+        }
+
+        squads
+    }
+
+    fn bench_squads_hits(bencher: &mut test::Bencher, count: usize) {
+        let squads = generate_squads(count);
+        let waypoints = HashMap::new();
+        let rules = Rules::new();
+
+        let modifiers = HashMap::new();
+
+        bencher.iter(|| get_squads_hits(&squads, &waypoints, &rules, &modifiers, |a, b| a != b));
+    }
+
+    #[bench]
+    fn squads_hits_1000(bencher: &mut test::Bencher) {
+        bench_squads_hits(bencher, 1000);
+    }
+
+    #[bench]
+    fn squads_hits_5000(bencher: &mut test::Bencher) {
+        bench_squads_hits(bencher, 5000);
+    }
+
+    #[bench]
+    fn squads_hits_10000(bencher: &mut test::Bencher) {
+        bench_squads_hits(bencher, 10000);
+    }
+}
@@ -1,7 +1,10 @@
 use ws::{Message, Sender};
 
 use common::to_command::ToCommand;
-use common::{Id, ParseCommandError, ParseCommandResult};
+use common::{ColorId, Id, ParseCommandError, ParseCommandResult, PlayerId, Position, SquadClass, TeamId};
+use server::chat::ChatChannel;
+use server::diplomacy::Relation;
+use server::research::Tech;
 use server::json;
 
 pub enum Command {
@@ -9,19 +12,108 @@ pub enum Command {
         sender: Sender
     },
 
+    /// First message of a connection, carries the join password, invite, admin or session token
+    /// and optionally a stable name with the profile token owning it
+    Hello {
+        sender: Sender,
+        token: Option<String>,
+        name: Option<String>,
+        profile: Option<String>
+    },
+
     Ready {
         sender: Sender
     },
 
+    /// Back from the results screen to the lobby of the next match
+    Lobby {
+        sender: Sender
+    },
+
+    /// Leaving the room from the lobby, the player is removed instead of waiting for a reconnect
+    Leave {
+        sender: Sender
+    },
+
     SquadSpawn {
         sender: Sender,
-        planet_id: Id
+        planet_id: Id,
+        class: SquadClass
     },
 
+    /// With `split` only half of every squad moves, the other half stays
     SquadMove {
         sender: Sender,
-        squad_id: Id,
-        waypoint_id: Id
+        squad_ids: Vec<Id>,
+        waypoint_id: Id,
+        split: bool
+    },
+
+    Research {
+        sender: Sender,
+        tech: Tech
+    },
+
+    Team {
+        sender: Sender,
+        team: Option<TeamId>
+    },
+
+    /// Player color picked in the lobby
+    Color {
+        sender: Sender,
+        color: ColorId
+    },
+
+    DiplomacyPropose {
+        sender: Sender,
+        player_id: PlayerId,
+        relation: Relation
+    },
+
+    DiplomacyAccept {
+        sender: Sender,
+        player_id: PlayerId,
+        relation: Relation
+    },
+
+    DiplomacyBreak {
+        sender: Sender,
+        player_id: PlayerId
+    },
+
+    Chat {
+        sender: Sender,
+        channel: ChatChannel,
+        text: String
+    },
+
+    Ping {
+        sender: Sender,
+        position: Position
+    },
+
+    /// Pause or resume request, applied at once for the host and admins, otherwise a vote
+    Pause {
+        sender: Sender,
+        paused: bool
+    },
+
+    /// Simulation speed multiplier, allowed for the host and admins
+    Speed {
+        sender: Sender,
+        speed: f64
+    },
+
+    /// Admin command line, executed only for players with admin rights
+    Admin {
+        sender: Sender,
+        line: String
+    },
+
+    /// Request for the best rated players
+    Leaderboard {
+        sender: Sender
     },
 
     Disconnect {
@@ -41,29 +133,156 @@ impl ToCommand for Command {
         let (action, data) = json::parse_command(raw)?;
 
         let command = match action.as_ref() {
+            "hello" => {
+                let (token, name, profile) = json::parse_hello_command_data(&data)?;
+
+                Command::Hello {
+                    sender,
+                    token,
+                    name,
+                    profile
+                }
+            },
+
             "ready" => {
                 Command::Ready { sender: sender }
             },
 
+            "lobby" => {
+                Command::Lobby { sender }
+            },
+
+            "leave" => {
+                Command::Leave { sender }
+            },
+
             "squad_spawn" => {
-                let planet_id = json::parse_squad_spawn_command_data(&data)?;
+                let (planet_id, class) = json::parse_squad_spawn_command_data(&data)?;
 
                 Command::SquadSpawn {
                     sender: sender,
-                    planet_id: planet_id
+                    planet_id: planet_id,
+                    class: class
                 }
             },
 
             "squad_move" => {
-                let (squad_id, waypoint_id) = json::parse_squad_move_command_data(&data)?;
+                let (squad_ids, waypoint_id, split) = json::parse_squad_move_command_data(&data)?;
 
                 Command::SquadMove {
                     sender,
-                    squad_id,
-                    waypoint_id
+                    squad_ids,
+                    waypoint_id,
+                    split
                 }
             },
 
+            "research" => {
+                let tech = json::parse_research_command_data(&data)?;
+
+                Command::Research {
+                    sender,
+                    tech
+                }
+            },
+
+            "team" => {
+                let team = json::parse_team_command_data(&data)?;
+
+                Command::Team {
+                    sender,
+                    team
+                }
+            },
+
+            "color" => {
+                let color = json::parse_color_command_data(&data)?;
+
+                Command::Color {
+                    sender,
+                    color
+                }
+            },
+
+            "diplomacy_propose" => {
+                let (player_id, relation) = json::parse_diplomacy_command_data(&data)?;
+
+                Command::DiplomacyPropose {
+                    sender,
+                    player_id,
+                    relation
+                }
+            },
+
+            "diplomacy_accept" => {
+                let (player_id, relation) = json::parse_diplomacy_command_data(&data)?;
+
+                Command::DiplomacyAccept {
+                    sender,
+                    player_id,
+                    relation
+                }
+            },
+
+            "diplomacy_break" => {
+                let player_id = json::parse_diplomacy_break_command_data(&data)?;
+
+                Command::DiplomacyBreak {
+                    sender,
+                    player_id
+                }
+            },
+
+            "chat" => {
+                let (channel, text) = json::parse_chat_command_data(&data)?;
+
+                Command::Chat {
+                    sender,
+                    channel,
+                    text
+                }
+            },
+
+            "ping" => {
+                let position = json::parse_ping_command_data(&data)?;
+
+                Command::Ping {
+                    sender,
+                    position
+                }
+            },
+
+            "pause" => {
+                let paused = json::parse_pause_command_data(&data)?;
+
+                Command::Pause {
+                    sender,
+                    paused
+                }
+            },
+
+            "speed" => {
+                let speed = json::parse_speed_command_data(&data)?;
+
+                Command::Speed {
+                    sender,
+                    speed
+                }
+            },
+
+            "admin" => {
+                let line = json::parse_admin_command_data(&data)?;
+
+                Command::Admin {
+                    sender,
+                    line
+                }
+            },
+
+            "leaderboard" => {
+                Command::Leaderboard { sender }
+            },
+
             _ => return Err(ParseCommandError::UnsupportedAction)
         };
 
@@ -73,4 +292,56 @@ impl ToCommand for Command {
     fn disconnect(sender: Sender) -> Self {
         Command::Disconnect { sender: sender }
     }
+
+    fn action(&self) -> &'static str {
+        match *self {
+            Command::Connect { .. } => "connect",
+            Command::Hello { .. } => "hello",
+            Command::Ready { .. } => "ready",
+            Command::Lobby { .. } => "lobby",
+            Command::Leave { .. } => "leave",
+            Command::SquadSpawn { .. } => "squad_spawn",
+            Command::SquadMove { .. } => "squad_move",
+            Command::Research { .. } => "research",
+            Command::Team { .. } => "team",
+            Command::Color { .. } => "color",
+            Command::DiplomacyPropose { .. } => "diplomacy_propose",
+            Command::DiplomacyAccept { .. } => "diplomacy_accept",
+            Command::DiplomacyBreak { .. } => "diplomacy_break",
+            Command::Chat { .. } => "chat",
+            Command::Ping { .. } => "ping",
+            Command::Pause { .. } => "pause",
+            Command::Speed { .. } => "speed",
+            Command::Admin { .. } => "admin",
+            Command::Leaderboard { .. } => "leaderboard",
+            Command::Disconnect { .. } => "disconnect"
+        }
+    }
 }
+
+impl Command {
+    pub fn sender(&self) -> &Sender {
+        match *self {
+            Command::Connect { ref sender } |
+            Command::Hello { ref sender, .. } |
+            Command::Ready { ref sender } |
+            Command::Lobby { ref sender } |
+            Command::Leave { ref sender } |
+            Command::SquadSpawn { ref sender, .. } |
+            Command::SquadMove { ref sender, .. } |
+            Command::Research { ref sender, .. } |
+            Command::Team { ref sender, .. } |
+            Command::Color { ref sender, .. } |
+            Command::DiplomacyPropose { ref sender, .. } |
+            Command::DiplomacyAccept { ref sender, .. } |
+            Command::DiplomacyBreak { ref sender, .. } |
+            Command::Chat { ref sender, .. } |
+            Command::Ping { ref sender, .. } |
+            Command::Pause { ref sender, .. } |
+            Command::Speed { ref sender, .. } |
+            Command::Admin { ref sender, .. } |
+            Command::Leaderboard { ref sender } |
+            Command::Disconnect { ref sender } => sender
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use common::PlayerId;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Relation {
+    Alliance,
+    Ceasefire
+}
+
+impl Relation {
+    pub fn from_name(name: &str) -> Option<Relation> {
+        match name {
+            "alliance" => Some(Relation::Alliance),
+            "ceasefire" => Some(Relation::Ceasefire),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Relation::Alliance => "alliance",
+            Relation::Ceasefire => "ceasefire"
+        }
+    }
+}
+
+/// In-match relations between players. A relation is established only when the other side
+/// accepts a matching proposal, and either side may break it at any time.
+pub struct Diplomacy {
+    relations: HashMap<(PlayerId, PlayerId), Relation>,
+    proposals: HashMap<(PlayerId, PlayerId), Relation>
+}
+
+impl Diplomacy {
+    pub fn new() -> Self {
+        Diplomacy {
+            relations: HashMap::new(),
+            proposals: HashMap::new()
+        }
+    }
+
+    pub fn relation(&self, a: PlayerId, b: PlayerId) -> Option<Relation> {
+        self.relations.get(&Self::pair(a, b)).cloned()
+    }
+
+    pub fn relations(&self) -> Vec<(PlayerId, PlayerId, Relation)> {
+        self.relations
+            .iter()
+            .map(|(&(a, b), &relation)| (a, b, relation))
+            .collect()
+    }
+
+    /// Proposals received by the player as pairs of proposer and relation
+    pub fn proposals_to(&self, player_id: PlayerId) -> Vec<(PlayerId, Relation)> {
+        self.proposals
+            .iter()
+            .filter(|&(&(_, to), _)| to == player_id)
+            .map(|(&(from, _), &relation)| (from, relation))
+            .collect()
+    }
+
+    pub fn propose(&mut self, from: PlayerId, to: PlayerId, relation: Relation) {
+        if from == to || self.relation(from, to) == Some(relation) {
+            return;
+        }
+
+        self.proposals.insert((from, to), relation);
+    }
+
+    pub fn accept(&mut self, from: PlayerId, to: PlayerId, relation: Relation) -> bool {
+        if self.proposals.get(&(to, from)) != Some(&relation) {
+            return false;
+        }
+
+        self.proposals.remove(&(to, from));
+        self.proposals.remove(&(from, to));
+        self.relations.insert(Self::pair(from, to), relation);
+
+        true
+    }
+
+    pub fn break_relation(&mut self, from: PlayerId, to: PlayerId) {
+        self.proposals.remove(&(from, to));
+        self.proposals.remove(&(to, from));
+        self.relations.remove(&Self::pair(from, to));
+    }
+
+    pub fn remove_player(&mut self, player_id: PlayerId) {
+        self.relations.retain(|&(a, b), _| a != player_id && b != player_id);
+        self.proposals.retain(|&(a, b), _| a != player_id && b != player_id);
+    }
+
+    fn pair(a: PlayerId, b: PlayerId) -> (PlayerId, PlayerId) {
+        if a < b { (a, b) } else { (b, a) }
+    }
+}
@@ -0,0 +1,46 @@
+use common::{Id, PlayerId, Position, SquadClass};
+use server::research::Tech;
+
+/// Things happened during a tick, sent to clients along with the state snapshot
+pub enum Event {
+    SquadSpawned {
+        squad_id: Id,
+        owner: PlayerId,
+        class: SquadClass,
+        waypoint_id: Id
+    },
+
+    /// `killer` is `None` for squads lost without a fight, e.g. to debt or elimination
+    SquadDestroyed {
+        squad_id: Id,
+        owner: PlayerId,
+        killer: Option<PlayerId>,
+        /// Last position of the squad, tells who has seen it destroyed under fog of war
+        position: Position
+    },
+
+    SquadsMerged {
+        squad_id: Id,
+        merged_squad_ids: Vec<Id>
+    },
+
+    WaypointCaptured {
+        waypoint_id: Id,
+        owner: PlayerId,
+        previous_owner: Option<PlayerId>
+    },
+
+    /// Seen only by the researching player
+    ResearchCompleted {
+        player_id: PlayerId,
+        tech: Tech
+    },
+
+    PlayerEliminated {
+        player_id: PlayerId
+    },
+
+    MatchFinished {
+        winners: Vec<PlayerId>
+    }
+}
@@ -0,0 +1,262 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use common::Id;
+use server::waypoint::Waypoint;
+
+pub struct HyperlaneGraph {
+    lanes: HashMap<Id, Vec<Id>>
+}
+
+#[derive(PartialEq)]
+struct RouteCandidate {
+    cost: f64,
+    waypoint_id: Id
+}
+
+impl Eq for RouteCandidate { }
+
+impl Ord for RouteCandidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed to turn std max-heap into min-heap by cost
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for RouteCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl HyperlaneGraph {
+    pub fn new() -> Self {
+        HyperlaneGraph {
+            lanes: HashMap::new()
+        }
+    }
+
+    pub fn generate(waypoints: &HashMap<Id, Waypoint>) -> Self {
+        const NEAREST_WAYPOINTS_COUNT: usize = 2;
+
+        let mut graph = Self::new();
+
+        for waypoint in waypoints.values() {
+            let mut other_waypoints = waypoints
+                .values()
+                .filter(|other_waypoint| other_waypoint.id() != waypoint.id())
+                .collect::<Vec<_>>();
+
+            other_waypoints.sort_by(|a, b| {
+                let a_distance = a.position().distance_to(waypoint.position());
+                let b_distance = b.position().distance_to(waypoint.position());
+
+                a_distance.partial_cmp(&b_distance).unwrap_or(Ordering::Equal)
+            });
+
+            for other_waypoint in other_waypoints.iter().take(NEAREST_WAYPOINTS_COUNT) {
+                graph.add_lane(waypoint.id(), other_waypoint.id());
+            }
+        }
+
+        loop {
+            let components = graph.components(waypoints);
+            if components.len() <= 1 {
+                break;
+            }
+
+            let ref first_component = components[0];
+
+            let mut nearest_lane: Option<(Id, Id, f64)> = None;
+            for waypoint_id in first_component {
+                let position = waypoints[waypoint_id].position();
+
+                for other_waypoint in waypoints.values().filter(|waypoint| !first_component.contains(&waypoint.id())) {
+                    let distance = other_waypoint.position().distance_to(position);
+
+                    if nearest_lane.map_or(true, |(_, _, nearest_distance)| distance < nearest_distance) {
+                        nearest_lane = Some((*waypoint_id, other_waypoint.id(), distance));
+                    }
+                }
+            }
+
+            match nearest_lane {
+                Some((from, to, _)) => graph.add_lane(from, to),
+                None => break
+            }
+        }
+
+        graph
+    }
+
+    pub fn add_lane(&mut self, from: Id, to: Id) {
+        if from == to || self.has_lane(from, to) {
+            return;
+        }
+
+        self.lanes.entry(from).or_insert_with(Vec::new).push(to);
+        self.lanes.entry(to).or_insert_with(Vec::new).push(from);
+    }
+
+    pub fn has_lane(&self, from: Id, to: Id) -> bool {
+        self.lanes.get(&from).map_or(false, |neighbours| neighbours.contains(&to))
+    }
+
+    pub fn lanes(&self) -> Vec<(Id, Id)> {
+        let mut lanes = vec![];
+
+        for (from, neighbours) in &self.lanes {
+            for to in neighbours {
+                if from < to {
+                    lanes.push((*from, *to));
+                }
+            }
+        }
+
+        lanes
+    }
+
+    /// Finds the cheapest route between two waypoints with Dijkstra's algorithm. The route
+    /// excludes `from` and ends with `to`; `lane_cost` is called for each traversed lane.
+    pub fn find_path<F>(&self, from: Id, to: Id, lane_cost: F) -> Option<VecDeque<Id>>
+        where F: Fn(Id, Id) -> f64
+    {
+        let mut costs: HashMap<Id, f64> = HashMap::new();
+        let mut previous: HashMap<Id, Id> = HashMap::new();
+        let mut candidates = BinaryHeap::new();
+
+        costs.insert(from, 0.0);
+        candidates.push(RouteCandidate { cost: 0.0, waypoint_id: from });
+
+        while let Some(RouteCandidate { cost, waypoint_id }) = candidates.pop() {
+            if waypoint_id == to {
+                let mut path = VecDeque::new();
+
+                let mut current = to;
+                while current != from {
+                    path.push_front(current);
+                    current = previous[&current];
+                }
+
+                return Some(path);
+            }
+
+            if costs.get(&waypoint_id).map_or(false, |best_cost| cost > *best_cost) {
+                continue;
+            }
+
+            if let Some(neighbours) = self.lanes.get(&waypoint_id) {
+                for neighbour in neighbours {
+                    let neighbour_cost = cost + lane_cost(waypoint_id, *neighbour);
+
+                    if costs.get(neighbour).map_or(true, |best_cost| neighbour_cost < *best_cost) {
+                        costs.insert(*neighbour, neighbour_cost);
+                        previous.insert(*neighbour, waypoint_id);
+                        candidates.push(RouteCandidate { cost: neighbour_cost, waypoint_id: *neighbour });
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn components(&self, waypoints: &HashMap<Id, Waypoint>) -> Vec<HashSet<Id>> {
+        let mut visited = HashSet::new();
+        let mut components = vec![];
+
+        for waypoint_id in waypoints.keys() {
+            if visited.contains(waypoint_id) {
+                continue;
+            }
+
+            let mut component = HashSet::new();
+            let mut queue = VecDeque::new();
+
+            queue.push_back(*waypoint_id);
+            visited.insert(*waypoint_id);
+
+            while let Some(current) = queue.pop_front() {
+                component.insert(current);
+
+                if let Some(neighbours) = self.lanes.get(&current) {
+                    for neighbour in neighbours {
+                        if visited.insert(*neighbour) {
+                            queue.push_back(*neighbour);
+                        }
+                    }
+                }
+            }
+
+            components.push(component);
+        }
+
+        components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use common::{Id, Position};
+    use server::waypoint::{Waypoint, WaypointType};
+    use super::HyperlaneGraph;
+
+    fn create_waypoints(positions: &[(Id, f64, f64)]) -> HashMap<Id, Waypoint> {
+        positions
+            .iter()
+            .map(|&(id, x, y)| (id, Waypoint::new(id, WaypointType::Planet, Position(x, y))))
+            .collect()
+    }
+
+    #[test]
+    fn cheapest_path() {
+        let mut graph = HyperlaneGraph::new();
+        graph.add_lane(1, 2);
+        graph.add_lane(2, 4);
+        graph.add_lane(1, 3);
+        graph.add_lane(3, 4);
+
+        // The route through the third waypoint is longer by hops but cheaper
+        let path = graph.find_path(1, 4, |from, to| if from == 2 || to == 2 { 10.0 } else { 1.0 });
+        assert_eq!(path.map(|path| path.into_iter().collect::<Vec<_>>()), Some(vec![3, 4]));
+
+        let path = graph.find_path(4, 1, |_, _| 1.0);
+        assert_eq!(path.map(|path| path.len()), Some(2));
+    }
+
+    #[test]
+    fn unreachable_target() {
+        let mut graph = HyperlaneGraph::new();
+        graph.add_lane(1, 2);
+        graph.add_lane(3, 4);
+
+        assert!(graph.find_path(1, 4, |_, _| 1.0).is_none());
+        assert!(graph.find_path(1, 5, |_, _| 1.0).is_none());
+    }
+
+    #[test]
+    fn generated_graph_is_connected() {
+        // Two distant clusters are not linked by nearest neighbours alone
+        let waypoints = create_waypoints(&[
+            (1, 0.0, 0.0), (2, 10.0, 0.0), (3, 0.0, 10.0),
+            (4, 1000.0, 0.0), (5, 1010.0, 0.0), (6, 1000.0, 10.0),
+            (7, 0.0, 1000.0)
+        ]);
+
+        let graph = HyperlaneGraph::generate(&waypoints);
+
+        assert_eq!(graph.components(&waypoints).len(), 1);
+
+        for from in waypoints.keys() {
+            for to in waypoints.keys() {
+                assert!(graph.find_path(*from, *to, |_, _| 1.0).is_some());
+            }
+        }
+
+        for &(from, to) in &graph.lanes() {
+            assert!(graph.has_lane(from, to) && graph.has_lane(to, from));
+        }
+    }
+}
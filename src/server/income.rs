@@ -0,0 +1,24 @@
+/// Gold per second of a player by source
+#[derive(Copy, Clone)]
+pub struct Income {
+    pub planetoids: f64,
+    pub planets: f64,
+    pub upkeep: f64,
+    /// Research multiplier already applied to planetoids and planets income
+    pub modifier: f64
+}
+
+impl Income {
+    pub fn new() -> Self {
+        Income {
+            planetoids: 0.0,
+            planets: 0.0,
+            upkeep: 0.0,
+            modifier: 1.0
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.planetoids + self.planets - self.upkeep
+    }
+}
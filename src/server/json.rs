@@ -2,10 +2,20 @@ use std::collections::HashMap;
 
 use rustc_serialize::json::{Json, Object};
 
-use common::{Id, PlayerId, ParseCommandResult, Position, utils};
+use common::{ColorId, Id, PlayerId, ParseCommandError, ParseCommandResult, Position, SquadClass, TeamId, PLAYER_COLORS_COUNT, utils};
 use common::utils::json;
+use server::chat::{ChatChannel, ChatMessage, Ping};
+use server::diplomacy::{Diplomacy, Relation};
+use server::event::Event;
+use server::hyperlane::HyperlaneGraph;
+use server::income::Income;
 use server::player::{Player, PlayerState};
-use server::squad::Squad;
+use server::research::{Tech, TechState, TECHS};
+use server::rules::{ClassRules, Rules};
+use server::settings::VictoryCondition;
+use server::squad::{Squad, SquadState};
+use server::standing::Standing;
+use server::stats::PlayerRecord;
 use server::waypoint::{Waypoint, WaypointType};
 
 type Result<T> = ParseCommandResult<T>;
@@ -20,46 +30,203 @@ pub fn parse_command(string: &str) -> Result<(String, Object)> {
     return Ok((command.to_string(), data.to_owned()));
 }
 
-pub fn parse_squad_spawn_command_data(data: &Object) -> Result<Id> {
-    json::parse_id_from_json_object(data, "planet_id")
+/// Returns the join token, the player name and the profile token proving the name
+pub fn parse_hello_command_data(data: &Object) -> Result<(Option<String>, Option<String>, Option<String>)> {
+    Ok((
+        parse_option_string(data, "token")?,
+        parse_option_string(data, "name")?,
+        parse_option_string(data, "profile")?
+    ))
 }
 
-pub fn parse_squad_move_command_data(data: &Object) -> Result<(Id, Id)> {
-    let squad_id = json::parse_id_from_json_object(data, "squad_id")?;
+fn parse_option_string(data: &Object, property: &str) -> Result<Option<String>> {
+    match data.get(property) {
+        Some(&Json::Null) | None => Ok(None),
+        Some(_) => Ok(Some(json::parse_string_from_json_object(data, property)?.to_string()))
+    }
+}
+
+pub fn parse_squad_spawn_command_data(data: &Object) -> Result<(Id, SquadClass)> {
+    let planet_id = json::parse_id_from_json_object(data, "planet_id")?;
+
+    let class = match data.get("class") {
+        Some(_) => {
+            let class_name = json::parse_string_from_json_object(data, "class")?;
+
+            SquadClass::from_name(class_name)
+                .ok_or(ParseCommandError::IncompatibleType("class".to_string()))?
+        },
+
+        None => SquadClass::Fighter
+    };
+
+    return Ok((planet_id, class));
+}
+
+/// Accepts either a single squad id or the ids of every selected squad
+pub fn parse_squad_move_command_data(data: &Object) -> Result<(Vec<Id>, Id, bool)> {
+    let squad_ids = match data.get("squad_ids") {
+        Some(_) => {
+            json::parse_array_from_json_object(data, "squad_ids")?
+                .iter()
+                .map(|squad_id| squad_id.as_u64().ok_or(ParseCommandError::IncompatibleType("squad_ids".to_string())))
+                .collect::<Result<Vec<Id>>>()?
+        },
+
+        None => vec![json::parse_id_from_json_object(data, "squad_id")?]
+    };
+
     let waypoint_id = json::parse_id_from_json_object(data, "waypoint_id")?;
 
-    return Ok((squad_id, waypoint_id));
+    let split = match data.get("split") {
+        Some(split) => split.as_boolean().ok_or(ParseCommandError::IncompatibleType("split".to_string()))?,
+        None => false
+    };
+
+    return Ok((squad_ids, waypoint_id, split));
 }
 
-pub fn format_process_command(
-    player: &Player,
-    waypoints_json: &String,
-    players_json: &String,
-    squads_json: &String
-) -> String {
+pub fn parse_research_command_data(data: &Object) -> Result<Tech> {
+    let tech_name = json::parse_string_from_json_object(data, "tech")?;
+
+    Tech::from_name(tech_name)
+        .ok_or(ParseCommandError::IncompatibleType("tech".to_string()))
+}
+
+pub fn parse_team_command_data(data: &Object) -> Result<Option<TeamId>> {
+    json::parse_option_u64_from_json_object(data, "team")
+}
+
+pub fn parse_color_command_data(data: &Object) -> Result<ColorId> {
+    let color = json::parse_u64_from_json_object(data, "color")?;
+
+    if color >= PLAYER_COLORS_COUNT {
+        return Err(ParseCommandError::IncompatibleType("color".to_string()));
+    }
+
+    Ok(color)
+}
+
+pub fn parse_diplomacy_command_data(data: &Object) -> Result<(PlayerId, Relation)> {
+    let player_id = json::parse_player_id_from_json_object(data, "player_id")?;
+
+    let relation_name = json::parse_string_from_json_object(data, "relation")?;
+    let relation = Relation::from_name(relation_name)
+        .ok_or(ParseCommandError::IncompatibleType("relation".to_string()))?;
+
+    return Ok((player_id, relation));
+}
+
+pub fn parse_diplomacy_break_command_data(data: &Object) -> Result<PlayerId> {
+    json::parse_player_id_from_json_object(data, "player_id")
+}
+
+pub fn parse_chat_command_data(data: &Object) -> Result<(ChatChannel, String)> {
+    let channel_name = json::parse_string_from_json_object(data, "channel")?;
+
+    let player_id = match data.get("player_id") {
+        Some(_) => Some(json::parse_player_id_from_json_object(data, "player_id")?),
+        None => None
+    };
+
+    let channel = ChatChannel::from_name(channel_name, player_id)
+        .ok_or(ParseCommandError::IncompatibleType("channel".to_string()))?;
+
+    let text = json::parse_string_from_json_object(data, "text")?;
+
+    return Ok((channel, text.to_string()));
+}
+
+pub fn parse_ping_command_data(data: &Object) -> Result<Position> {
+    let x = json::parse_f64_from_json_object(data, "x")?;
+    let y = json::parse_f64_from_json_object(data, "y")?;
+
+    return Ok(Position(x, y));
+}
+
+pub fn parse_pause_command_data(data: &Object) -> Result<bool> {
+    data.get("paused")
+        .and_then(|paused| paused.as_boolean())
+        .ok_or(ParseCommandError::IncompatibleType("paused".to_string()))
+}
+
+pub fn parse_speed_command_data(data: &Object) -> Result<f64> {
+    json::parse_f64_from_json_object(data, "speed")
+}
+
+pub fn parse_admin_command_data(data: &Object) -> Result<String> {
+    json::parse_string_from_json_object(data, "command")
+        .map(|command| command.to_string())
+}
+
+/// Formatted parts of the state sent to a player during a tick, most of them are shared by all
+/// players and formatted once
+pub struct Snapshot<'a> {
+    pub tick_json: &'a String,
+    pub waypoints_json: &'a String,
+    pub lanes_json: &'a String,
+    pub players_json: &'a String,
+    pub squads_json: &'a String,
+    pub events_json: &'a String,
+    pub match_json: &'a String,
+    pub standings_json: &'a String,
+    pub relations_json: &'a String,
+    pub proposals_json: &'a String,
+    pub chat_json: &'a String,
+    pub pings_json: &'a String
+}
+
+pub fn format_process_command(player: &Player, rules: &Rules, snapshot: &Snapshot) -> String {
     format!(
-        r#"{{"waypoints":{},"players":{},"squads":{},"id":{},"gold":{}}}"#,
-        waypoints_json,
-        players_json,
-        squads_json,
+        r#"{{"tick":{},"waypoints":{},"lanes":{},"players":{},"squads":{},"events":{},"match":{},"standings":{},"relations":{},"proposals":{},"chat":{},"pings":{},"research":{},"id":{},"gold":{},"income":{},"supply":{{"used":{},"cap":{}}}}}"#,
+        snapshot.tick_json,
+        snapshot.waypoints_json,
+        snapshot.lanes_json,
+        snapshot.players_json,
+        snapshot.squads_json,
+        snapshot.events_json,
+        snapshot.match_json,
+        snapshot.standings_json,
+        snapshot.relations_json,
+        snapshot.proposals_json,
+        snapshot.chat_json,
+        snapshot.pings_json,
+        format_research(player, rules),
         player.id(),
-        player.gold()
+        player.gold(),
+        format_income(player.income()),
+        player.supply_used(),
+        player.supply_cap()
     )
 }
 
-pub fn format_waypoints(waypoints: &HashMap<Id, Waypoint>) -> String {
+pub fn format_tick(tick: u64, time: f64) -> String {
+    format!(r#"{{"number":{},"time":{}}}"#, tick, time)
+}
+
+pub fn format_waypoints(waypoints: &HashMap<Id, Waypoint>, rules: &Rules) -> String {
     let formatted_waypoints = waypoints
         .values()
         .map(|waypoint| {
             let Position(x, y) = waypoint.position();
-            let owner = waypoint.owner().map_or("null".to_string(), |owner| owner.to_string());
+            let owner = format_option_player_id(waypoint.owner());
+
+            let capture = match (waypoint.capturer(), rules.capture_threshold(waypoint.waypoint_type())) {
+                (Some(capturer), Some(capture_threshold)) => format!(
+                    r#"{{"player":{},"progress":{}}}"#,
+                    capturer,
+                    waypoint.capture_progress() / capture_threshold
+                ),
+                _ => "null".to_string()
+            };
 
             format!(
-                r#"{{"id":{},"x":{},"y":{},"owner":{},"type":"{}"}}"#,
+                r#"{{"id":{},"x":{},"y":{},"owner":{},"capture":{},"type":"{}"}}"#,
                 waypoint.id(),
                 x,
                 y,
                 owner,
+                capture,
                 match waypoint.waypoint_type() {
                     WaypointType::Planetoid => "planetoid",
                     WaypointType::Asteroid => "asteroid",
@@ -73,6 +240,17 @@ pub fn format_waypoints(waypoints: &HashMap<Id, Waypoint>) -> String {
     format!("[{}]", utils::join(formatted_waypoints, ","))
 }
 
+pub fn format_lanes(hyperlanes: &Option<HyperlaneGraph>) -> String {
+    let formatted_lanes = hyperlanes
+        .as_ref()
+        .map_or(vec![], |hyperlanes| hyperlanes.lanes())
+        .into_iter()
+        .map(|(from, to)| format!("[{},{}]", from, to))
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_lanes, ","))
+}
+
 pub fn format_players(players: &HashMap<PlayerId, Player>) -> String {
     let formatted_players = players
         .values()
@@ -80,10 +258,12 @@ pub fn format_players(players: &HashMap<PlayerId, Player>) -> String {
             let player_state = format_player_state(&player);
 
             format!(
-                r#"{{"id":{},"name":"{}","state":"{}"}}"#,
+                r#"{{"id":{},"name":{},"state":"{}","team":{},"color":{}}}"#,
                 player.id(),
-                player.name(),
-                player_state
+                Json::String(player.name().clone()),
+                player_state,
+                player.team().map_or("null".to_string(), |team| team.to_string()),
+                player.color()
             )
         })
         .collect::<Vec<String>>();
@@ -91,7 +271,7 @@ pub fn format_players(players: &HashMap<PlayerId, Player>) -> String {
     format!("[{}]", utils::join(formatted_players, ","))
 }
 
-fn format_player_state(player: &Player) -> String {
+pub fn format_player_state(player: &Player) -> String {
     let state = match *player.state() {
         PlayerState::Pending => "pending",
         PlayerState::Playing => "playing",
@@ -103,22 +283,330 @@ fn format_player_state(player: &Player) -> String {
     state.to_string()
 }
 
-pub fn format_squads(squads: &HashMap<Id, Squad>) -> String {
+/// Destination of a moving squad is sent only to players for whom `is_ally` holds with its owner
+pub fn format_squads<F: Fn(PlayerId) -> bool>(squads: &Vec<&Squad>, is_ally: F) -> String {
     let formatted_squads = squads
-        .values()
+        .iter()
         .map(|squad| {
             let Position(x, y) = squad.position();
 
+            // Orbited waypoint or the destination of a moving squad
+            let (state, waypoint_id) = match squad.state() {
+                SquadState::InSpace => ("in_space", None),
+                SquadState::Moving { .. } if is_ally(squad.owner()) => ("moving", squad.destination()),
+                SquadState::Moving { .. } => ("moving", None),
+                SquadState::OnOrbit { waypoint_id } => ("orbiting", Some(waypoint_id))
+            };
+
             format!(
-                r#"{{"id":{},"owner":{},"x":{},"y":{},"count":{}}}"#,
+                r#"{{"id":{},"owner":{},"class":"{}","x":{},"y":{},"count":{},"state":"{}","waypoint":{}}}"#,
                 squad.id(),
                 squad.owner(),
+                squad.class().name(),
                 x,
                 y,
-                squad.life().ceil()
+                squad.life().ceil(),
+                state,
+                waypoint_id.map_or("null".to_string(), |waypoint_id| waypoint_id.to_string())
             )
         })
         .collect::<Vec<String>>();
 
     format!("[{}]", utils::join(formatted_squads, ","))
-}
\ No newline at end of file
+}
+
+pub fn format_events(events: &Vec<&Event>) -> String {
+    let formatted_events = events
+        .iter()
+        .map(|event| {
+            match **event {
+                Event::SquadSpawned { squad_id, owner, class, waypoint_id } => format!(
+                    r#"{{"type":"squad_spawned","squad_id":{},"owner":{},"class":"{}","waypoint_id":{}}}"#,
+                    squad_id,
+                    owner,
+                    class.name(),
+                    waypoint_id
+                ),
+
+                Event::SquadDestroyed { squad_id, owner, killer, .. } => format!(
+                    r#"{{"type":"squad_destroyed","squad_id":{},"owner":{},"killer":{}}}"#,
+                    squad_id,
+                    owner,
+                    format_option_player_id(killer)
+                ),
+
+                Event::SquadsMerged { squad_id, ref merged_squad_ids } => format!(
+                    r#"{{"type":"squads_merged","squad_id":{},"merged_squad_ids":[{}]}}"#,
+                    squad_id,
+                    utils::join(merged_squad_ids.clone(), ",")
+                ),
+
+                Event::WaypointCaptured { waypoint_id, owner, previous_owner } => format!(
+                    r#"{{"type":"waypoint_captured","waypoint_id":{},"owner":{},"previous_owner":{}}}"#,
+                    waypoint_id,
+                    owner,
+                    format_option_player_id(previous_owner)
+                ),
+
+                Event::ResearchCompleted { player_id, tech } => format!(
+                    r#"{{"type":"research_completed","player_id":{},"tech":"{}"}}"#,
+                    player_id,
+                    tech.name()
+                ),
+
+                Event::PlayerEliminated { player_id } => format!(
+                    r#"{{"type":"player_eliminated","player_id":{}}}"#,
+                    player_id
+                ),
+
+                Event::MatchFinished { ref winners } => format!(
+                    r#"{{"type":"match_finished","winners":[{}]}}"#,
+                    utils::join(winners.clone(), ",")
+                )
+            }
+        })
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_events, ","))
+}
+
+pub fn format_chat(messages: &Vec<&ChatMessage>) -> String {
+    let formatted_messages = messages
+        .iter()
+        .map(|message| {
+            format!(
+                r#"{{"from":{},"channel":"{}","text":{}}}"#,
+                message.from,
+                message.channel.name(),
+                Json::String(message.text.clone())
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_messages, ","))
+}
+
+pub fn format_pings(pings: &Vec<&Ping>) -> String {
+    let formatted_pings = pings
+        .iter()
+        .map(|ping| {
+            let Position(x, y) = ping.position;
+
+            format!(r#"{{"from":{},"x":{},"y":{}}}"#, ping.from, x, y)
+        })
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_pings, ","))
+}
+
+pub fn format_match(victory: &VictoryCondition, match_time: f64, is_paused: bool, speed: f64) -> String {
+    format!(
+        r#"{{"victory":"{}","target":{},"time":{},"paused":{},"speed":{}}}"#,
+        victory.name(),
+        victory.target().map_or("null".to_string(), |target| target.to_string()),
+        match_time,
+        is_paused,
+        speed
+    )
+}
+
+pub fn format_standings(standings: &Option<Vec<Standing>>, players: &HashMap<PlayerId, Player>) -> String {
+    let standings = match *standings {
+        Some(ref standings) => standings,
+        None => return "null".to_string()
+    };
+
+    let formatted_standings = standings
+        .iter()
+        .enumerate()
+        .map(|(index, standing)| {
+            let name = players.get(&standing.player_id)
+                .map_or("".to_string(), |player| player.name().to_string());
+
+            format!(
+                r#"{{"id":{},"name":{},"place":{},"winner":{},"score":{},"planets":{},"squads":{},"squads_built":{},"squads_destroyed":{},"planets_captured":{},"rating":{},"rating_change":{}}}"#,
+                standing.player_id,
+                Json::String(name),
+                index + 1,
+                standing.is_winner,
+                standing.score.floor(),
+                standing.planets_count,
+                standing.squads_count,
+                standing.stats.squads_built,
+                standing.stats.squads_destroyed,
+                standing.stats.planets_captured,
+                standing.rating.map_or("null".to_string(), |rating| rating.round().to_string()),
+                standing.rating_change.map_or("null".to_string(), |rating_change| rating_change.round().to_string())
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_standings, ","))
+}
+
+pub fn format_relations(diplomacy: &Diplomacy) -> String {
+    let formatted_relations = diplomacy
+        .relations()
+        .into_iter()
+        .map(|(a, b, relation)| format!(r#"{{"players":[{},{}],"relation":"{}"}}"#, a, b, relation.name()))
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_relations, ","))
+}
+
+pub fn format_proposals(diplomacy: &Diplomacy, player_id: PlayerId) -> String {
+    let formatted_proposals = diplomacy
+        .proposals_to(player_id)
+        .into_iter()
+        .map(|(from, relation)| format!(r#"{{"from":{},"relation":"{}"}}"#, from, relation.name()))
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_proposals, ","))
+}
+
+pub fn format_session_command(player: &Player) -> String {
+    format!(
+        r#"{{"session":{{"id":{},"token":"{}","admin":{}}}}}"#,
+        player.id(),
+        player.session_token(),
+        player.is_admin()
+    )
+}
+
+pub fn format_error_command(error: &str) -> String {
+    format!(r#"{{"error":"{}"}}"#, error)
+}
+
+pub fn format_admin_command(output: &str) -> String {
+    format!(r#"{{"admin":{}}}"#, Json::String(output.to_string()))
+}
+
+pub fn format_leaderboard_command(records: &Vec<&PlayerRecord>) -> String {
+    let formatted_records = records
+        .iter()
+        .map(|record| {
+            format!(
+                r#"{{"name":{},"rating":{},"wins":{},"losses":{}}}"#,
+                Json::String(record.name.clone()),
+                record.rating.round(),
+                record.wins,
+                record.losses
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!(r#"{{"leaderboard":[{}]}}"#, utils::join(formatted_records, ","))
+}
+
+/// Contents of the stats file, token hashes are kept to check the names on later matches
+pub fn format_stats(records: &Vec<&PlayerRecord>) -> String {
+    let formatted_records = records
+        .iter()
+        .map(|record| {
+            format!(
+                r#"{{"name":{},"token_salt":{},"token_hash":{},"wins":{},"losses":{},"squads_built":{},"squads_destroyed":{},"planets_captured":{},"play_time":{},"rating":{}}}"#,
+                Json::String(record.name.clone()),
+                Json::String(record.token_salt.clone()),
+                Json::String(record.token_hash.clone()),
+                record.wins,
+                record.losses,
+                record.squads_built,
+                record.squads_destroyed,
+                record.planets_captured,
+                record.play_time,
+                record.rating
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!(r#"{{"players":[{}]}}"#, utils::join(formatted_records, ","))
+}
+
+pub fn format_dump(
+    waypoints_json: &String,
+    players_json: &String,
+    squads_json: &String,
+    match_json: &String,
+    standings_json: &String
+) -> String {
+    format!(
+        r#"{{"waypoints":{},"players":{},"squads":{},"match":{},"standings":{}}}"#,
+        waypoints_json,
+        players_json,
+        squads_json,
+        match_json,
+        standings_json
+    )
+}
+
+pub fn format_rules_command(rules: &Rules) -> String {
+    format!(
+        r#"{{"rules":{{"starting_gold":{},"income_rate":{},"planet_income":{},"upkeep_per_supply":{},"supply_base":{},"supply_per_planet":{},"merge_distance":{},"orbit_distance":{},"planet_capture_threshold":{},"planetoid_capture_threshold":{},"classes":{{"fighter":{},"cruiser":{}}}}}}}"#,
+        rules.starting_gold,
+        rules.income_rate,
+        rules.planet_income,
+        rules.upkeep_per_supply,
+        rules.supply_base,
+        rules.supply_per_planet,
+        rules.merge_distance,
+        rules.orbit_distance,
+        rules.planet_capture_threshold,
+        rules.planetoid_capture_threshold,
+        format_class_rules(&rules.fighter),
+        format_class_rules(&rules.cruiser)
+    )
+}
+
+fn format_class_rules(class_rules: &ClassRules) -> String {
+    format!(
+        r#"{{"cost":{},"supply":{},"life":{},"speed":{},"weapon_range":{},"damage":{}}}"#,
+        class_rules.cost,
+        class_rules.supply,
+        class_rules.life,
+        class_rules.speed,
+        class_rules.weapon_range,
+        class_rules.damage
+    )
+}
+
+fn format_income(income: Income) -> String {
+    format!(
+        r#"{{"planetoids":{},"planets":{},"upkeep":{},"modifier":{}}}"#,
+        income.planetoids,
+        income.planets,
+        income.upkeep,
+        income.modifier
+    )
+}
+
+fn format_research(player: &Player, rules: &Rules) -> String {
+    let formatted_techs = TECHS
+        .iter()
+        .map(|&tech| {
+            let (state, progress) = match player.research().tech_state(tech) {
+                TechState::Locked => ("locked", 0_f64),
+                TechState::Available => ("available", 0_f64),
+                TechState::Researching { progress } => ("researching", progress),
+                TechState::Completed => ("completed", 1_f64)
+            };
+
+            let tech_rules = rules.tech(tech);
+
+            format!(
+                r#"{{"tech":"{}","title":"{}","cost":{},"time":{},"state":"{}","progress":{}}}"#,
+                tech.name(),
+                tech.title(),
+                tech_rules.cost,
+                tech_rules.time,
+                state,
+                progress
+            )
+        })
+        .collect::<Vec<String>>();
+
+    format!("[{}]", utils::join(formatted_techs, ","))
+}
+
+fn format_option_player_id(player_id: Option<PlayerId>) -> String {
+    player_id.map_or("null".to_string(), |player_id| player_id.to_string())
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+
+use rustc_serialize::json::Json;
+
+use common::{Id, ParseCommandError, Position};
+use common::utils::json;
+use server::hyperlane::HyperlaneGraph;
+use server::waypoint::{Waypoint, WaypointType};
+
+#[derive(Debug)]
+pub enum LoadMapError {
+    Io(io::Error),
+    Parse(ParseCommandError),
+    DuplicateWaypoint(Id),
+    UnknownWaypoint(Id)
+}
+
+pub type LoadMapResult<T> = Result<T, LoadMapError>;
+
+pub struct Map {
+    pub waypoints: HashMap<Id, Waypoint>,
+    pub hyperlanes: Option<HyperlaneGraph>
+}
+
+pub fn load_map(path: &str) -> LoadMapResult<Map> {
+    let mut raw = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut raw))
+        .map_err(LoadMapError::Io)?;
+
+    parse_map(&raw)
+}
+
+fn parse_map(raw: &str) -> LoadMapResult<Map> {
+    let json = json::parse_json(raw).map_err(LoadMapError::Parse)?;
+    let params = json::parse_json_as_object(&json).map_err(LoadMapError::Parse)?;
+
+    let waypoints_json_array = json::parse_array_from_json_object(params, "waypoints")
+        .map_err(LoadMapError::Parse)?;
+
+    let mut waypoints = HashMap::new();
+    for waypoint_json in waypoints_json_array {
+        let waypoint = parse_waypoint(waypoint_json).map_err(LoadMapError::Parse)?;
+        let waypoint_id = waypoint.id();
+
+        // A later waypoint would replace an earlier one, leaving its lanes pointing elsewhere
+        if waypoints.insert(waypoint_id, waypoint).is_some() {
+            return Err(LoadMapError::DuplicateWaypoint(waypoint_id));
+        }
+    }
+
+    let hyperlanes = match params.get("lanes") {
+        Some(_) => {
+            let lanes_json_array = json::parse_array_from_json_object(params, "lanes")
+                .map_err(LoadMapError::Parse)?;
+
+            let mut hyperlanes = HyperlaneGraph::new();
+            for lane_json in lanes_json_array {
+                let (from, to) = json::parse_id_pair(lane_json).map_err(LoadMapError::Parse)?;
+
+                for waypoint_id in &[from, to] {
+                    if !waypoints.contains_key(waypoint_id) {
+                        return Err(LoadMapError::UnknownWaypoint(*waypoint_id));
+                    }
+                }
+
+                hyperlanes.add_lane(from, to);
+            }
+
+            Some(hyperlanes)
+        },
+
+        None => None
+    };
+
+    Ok(Map { waypoints, hyperlanes })
+}
+
+fn parse_waypoint(waypoint_json: &Json) -> Result<Waypoint, ParseCommandError> {
+    let waypoint_json_object = json::parse_json_as_object(waypoint_json)?;
+
+    let id = json::parse_id_from_json_object(waypoint_json_object, "id")?;
+    let x = json::parse_f64_from_json_object(waypoint_json_object, "x")?;
+    let y = json::parse_f64_from_json_object(waypoint_json_object, "y")?;
+
+    let waypoint_type = match json::parse_string_from_json_object(waypoint_json_object, "type")? {
+        "asteroid" => WaypointType::Asteroid,
+        "black_hole" => WaypointType::BlackHole,
+        "planetoid" => WaypointType::Planetoid,
+        "planet" => WaypointType::Planet,
+        _ => return Err(ParseCommandError::IncompatibleType("type".to_string()))
+    };
+
+    Ok(Waypoint::new(id, waypoint_type, Position(x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadMapError, parse_map};
+
+    const WAYPOINTS: &'static str = r#"[{"id":1,"x":0,"y":0,"type":"planet"},{"id":2,"x":100,"y":0,"type":"asteroid"}]"#;
+
+    #[test]
+    fn lanes() {
+        let map = parse_map(&format!(r#"{{"waypoints":{},"lanes":[[1,2]]}}"#, WAYPOINTS)).unwrap();
+
+        assert_eq!(map.waypoints.len(), 2);
+        assert_eq!(map.hyperlanes.map(|hyperlanes| hyperlanes.lanes().len()), Some(1));
+
+        let map = parse_map(&format!(r#"{{"waypoints":{}}}"#, WAYPOINTS)).unwrap();
+        assert!(map.hyperlanes.is_none());
+    }
+
+    #[test]
+    fn duplicate_waypoint() {
+        let raw = r#"{"waypoints":[{"id":1,"x":0,"y":0,"type":"planet"},{"id":1,"x":100,"y":0,"type":"asteroid"}]}"#;
+
+        match parse_map(raw) {
+            Err(LoadMapError::DuplicateWaypoint(1)) => { },
+            _ => panic!("waypoints with the same id are accepted")
+        }
+    }
+
+    #[test]
+    fn bad_map() {
+        match parse_map(&format!(r#"{{"waypoints":{},"lanes":[[1,3]]}}"#, WAYPOINTS)) {
+            Err(LoadMapError::UnknownWaypoint(3)) => { },
+            _ => panic!("lane to an unknown waypoint is accepted")
+        }
+
+        let broken_maps = vec![
+            "not a map".to_string(),
+            r#"{"lanes":[]}"#.to_string(),
+            r#"{"waypoints":[{"id":1,"x":0,"y":0,"type":"star"}]}"#.to_string(),
+            format!(r#"{{"waypoints":{},"lanes":[[1]]}}"#, WAYPOINTS),
+            format!(r#"{{"waypoints":{},"lanes":[[1,"2"]]}}"#, WAYPOINTS)
+        ];
+
+        for raw in &broken_maps {
+            match parse_map(raw) {
+                Err(LoadMapError::Parse(_)) => { },
+                _ => panic!("broken map is accepted: {}", raw)
+            }
+        }
+    }
+}
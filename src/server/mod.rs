@@ -1,13 +1,27 @@
+mod admin;
+mod chat;
+mod combat;
 mod command;
+mod diplomacy;
+mod event;
+mod hyperlane;
+mod income;
 mod json;
+mod map;
 mod player;
+mod research;
+mod rules;
 mod server;
+pub mod settings;
 mod squad;
+mod standing;
+mod stats;
 mod waypoint;
 
 use server::server::Server;
+use server::settings::Settings;
 
-pub fn run(address: String) {
-    let mut server = Server::new();
+pub fn run(address: String, settings: Settings) {
+    let mut server = Server::new(settings);
     server.run(address);
 }
@@ -1,12 +1,32 @@
-use ws::Sender;
+use ws::{CloseCode, Sender};
 
-use common::PlayerId;
+use common::{ColorId, PlayerId, TeamId};
+use common::utils::token_bucket::TokenBucket;
+use server::chat;
+use server::income::Income;
+use server::research::Research;
+use server::stats::MatchStats;
 
 pub struct Player {
+    id: PlayerId,
     sender: Sender,
+    /// Secret given to the client to reconnect as the same player
+    session_token: String,
+    is_admin: bool,
     state: PlayerState,
     name: String,
-    gold: f64
+    /// Token owning the name in stats, players without it are not rated
+    profile: Option<String>,
+    team: Option<TeamId>,
+    color: ColorId,
+    gold: f64,
+    income: Income,
+    supply_used: f64,
+    supply_cap: f64,
+    research: Research,
+    match_stats: MatchStats,
+    chat_bucket: TokenBucket,
+    ping_bucket: TokenBucket
 }
 
 pub enum PlayerState {
@@ -18,23 +38,53 @@ pub enum PlayerState {
 }
 
 impl Player {
-    pub fn new(sender: Sender, name: String) -> Player {
+    pub fn new(id: PlayerId, sender: Sender, session_token: String, is_admin: bool, name: String, gold: f64) -> Player {
         Player {
+            id: id,
             sender: sender,
+            session_token: session_token,
+            is_admin: is_admin,
             state: PlayerState::Pending,
             name: name,
-            gold: 15.0
+            profile: None,
+            team: None,
+            color: 0,
+            gold: gold,
+            income: Income::new(),
+            supply_used: 0.0,
+            supply_cap: 0.0,
+            research: Research::new(),
+            match_stats: MatchStats::new(),
+            chat_bucket: TokenBucket::new(chat::MESSAGES_BURST, chat::MESSAGES_RATE),
+            ping_bucket: TokenBucket::new(chat::PINGS_BURST, chat::PINGS_RATE)
         }
     }
 
     pub fn id(&self) -> PlayerId {
-        self.sender.token().0
+        self.id
     }
 
     pub fn send(&self, message: String) {
         self.sender.send(message);
     }
 
+    pub fn close(&self, reason: &'static str) {
+        self.sender.close_with_reason(CloseCode::Policy, reason);
+    }
+
+    /// Binds the player to a new connection after reconnect
+    pub fn set_sender(&mut self, sender: Sender) {
+        self.sender = sender;
+    }
+
+    pub fn session_token(&self) -> &String {
+        &self.session_token
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.is_admin
+    }
+
     pub fn state(&self) -> &PlayerState {
         &self.state
     }
@@ -43,6 +93,30 @@ impl Player {
         &self.name
     }
 
+    pub fn profile(&self) -> Option<&String> {
+        self.profile.as_ref()
+    }
+
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    pub fn team(&self) -> Option<TeamId> {
+        self.team
+    }
+
+    pub fn set_team(&mut self, team: Option<TeamId>) {
+        self.team = team;
+    }
+
+    pub fn color(&self) -> ColorId {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: ColorId) {
+        self.color = color;
+    }
+
     pub fn gold(&self) -> f64 {
         self.gold
     }
@@ -51,6 +125,53 @@ impl Player {
         self.gold = gold;
     }
 
+    pub fn income(&self) -> Income {
+        self.income
+    }
+
+    pub fn set_income(&mut self, income: Income) {
+        self.income = income;
+    }
+
+    pub fn supply_used(&self) -> f64 {
+        self.supply_used
+    }
+
+    pub fn supply_cap(&self) -> f64 {
+        self.supply_cap
+    }
+
+    pub fn set_supply(&mut self, supply_used: f64, supply_cap: f64) {
+        self.supply_used = supply_used;
+        self.supply_cap = supply_cap;
+    }
+
+    /// Returns whether the player is allowed to send one more chat message now
+    pub fn take_chat_message(&mut self) -> bool {
+        self.chat_bucket.take()
+    }
+
+    /// Returns whether the player is allowed to send one more ping now
+    pub fn take_ping(&mut self) -> bool {
+        self.ping_bucket.take()
+    }
+
+    pub fn match_stats(&self) -> MatchStats {
+        self.match_stats
+    }
+
+    pub fn match_stats_mut(&mut self) -> &mut MatchStats {
+        &mut self.match_stats
+    }
+
+    pub fn research(&self) -> &Research {
+        &self.research
+    }
+
+    pub fn research_mut(&mut self) -> &mut Research {
+        &mut self.research
+    }
+
     pub fn is_pending(&self) -> bool {
         match self.state {
             PlayerState::Pending => true,
@@ -79,6 +200,13 @@ impl Player {
         }
     }
 
+    pub fn is_loose(&self) -> bool {
+        match self.state {
+            PlayerState::Loose => true,
+            _ => false
+        }
+    }
+
     pub fn set_ready_state(&mut self) {
         if self.is_pending() {
             self.state = PlayerState::Ready;
@@ -91,6 +219,13 @@ impl Player {
         }
     }
 
+    /// Puts the player into the match started by an admin whether ready or not
+    pub fn force_playing_state(&mut self) {
+        if self.is_pending() || self.is_ready() {
+            self.state = PlayerState::Playing;
+        }
+    }
+
     pub fn set_win_state(&mut self) {
         if self.is_playing() {
             self.state = PlayerState::Win;
@@ -102,4 +237,21 @@ impl Player {
             self.state = PlayerState::Loose;
         }
     }
+
+    /// Returns the player from the results of the finished match to the lobby
+    pub fn set_pending_state(&mut self) {
+        if self.is_win() || self.is_loose() {
+            self.state = PlayerState::Pending;
+        }
+    }
+
+    /// Clears everything gained in the previous match, the state and the team are kept
+    pub fn reset(&mut self, gold: f64) {
+        self.gold = gold;
+        self.income = Income::new();
+        self.supply_used = 0.0;
+        self.supply_cap = 0.0;
+        self.research = Research::new();
+        self.match_stats = MatchStats::new();
+    }
 }
\ No newline at end of file
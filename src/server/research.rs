@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use common::SquadClass;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Tech {
+    Engines,
+    Weapons,
+    Mining,
+    Boarding,
+    Sensors,
+    Cruisers,
+    AdvancedEngines
+}
+
+pub const TECHS: [Tech; 7] = [
+    Tech::Engines,
+    Tech::Weapons,
+    Tech::Mining,
+    Tech::Boarding,
+    Tech::Sensors,
+    Tech::Cruisers,
+    Tech::AdvancedEngines
+];
+
+impl Tech {
+    pub fn from_name(name: &str) -> Option<Tech> {
+        TECHS.iter().cloned().find(|tech| tech.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Tech::Engines => "engines",
+            Tech::Weapons => "weapons",
+            Tech::Mining => "mining",
+            Tech::Boarding => "boarding",
+            Tech::Sensors => "sensors",
+            Tech::Cruisers => "cruisers",
+            Tech::AdvancedEngines => "advanced_engines"
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match *self {
+            Tech::Engines => "Engines: +20% speed",
+            Tech::Weapons => "Weapons: +20% damage",
+            Tech::Mining => "Mining: +50% income",
+            Tech::Boarding => "Boarding: +50% capture",
+            Tech::Sensors => "Sensors: +50% vision",
+            Tech::Cruisers => "Cruisers",
+            Tech::AdvancedEngines => "Advanced engines: +20% speed"
+        }
+    }
+
+    pub fn requirement(&self) -> Option<Tech> {
+        match *self {
+            Tech::Cruisers => Some(Tech::Weapons),
+            Tech::AdvancedEngines => Some(Tech::Engines),
+            _ => None
+        }
+    }
+}
+
+/// Multipliers applied to player's squads and economy by completed research
+#[derive(Copy, Clone)]
+pub struct Modifiers {
+    pub speed: f64,
+    pub damage: f64,
+    pub income: f64,
+    pub capture: f64,
+    pub vision: f64
+}
+
+impl Modifiers {
+    pub fn new() -> Self {
+        Modifiers {
+            speed: 1.0,
+            damage: 1.0,
+            income: 1.0,
+            capture: 1.0,
+            vision: 1.0
+        }
+    }
+}
+
+pub enum TechState {
+    Locked,
+    Available,
+    Researching { progress: f64 },
+    Completed
+}
+
+pub struct Research {
+    completed: HashSet<Tech>,
+    /// Tech being researched with elapsed and total research time
+    current: Option<(Tech, f64, f64)>,
+    modifiers: Modifiers
+}
+
+impl Research {
+    pub fn new() -> Self {
+        Research {
+            completed: HashSet::new(),
+            current: None,
+            modifiers: Modifiers::new()
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn tech_state(&self, tech: Tech) -> TechState {
+        if self.completed.contains(&tech) {
+            return TechState::Completed;
+        }
+
+        if let Some((current_tech, time, total_time)) = self.current {
+            if current_tech == tech {
+                return TechState::Researching { progress: time / total_time };
+            }
+        }
+
+        let is_unlocked = tech.requirement()
+            .map_or(true, |requirement| self.completed.contains(&requirement));
+
+        if is_unlocked { TechState::Available } else { TechState::Locked }
+    }
+
+    pub fn can_start(&self, tech: Tech) -> bool {
+        match (self.current, self.tech_state(tech)) {
+            (None, TechState::Available) => true,
+            _ => false
+        }
+    }
+
+    /// Starts researching the tech for the given time in seconds
+    pub fn start(&mut self, tech: Tech, total_time: f64) {
+        if self.can_start(tech) {
+            self.current = Some((tech, 0.0, total_time));
+        }
+    }
+
+    /// Advances current research, returns the tech if it has just been completed
+    pub fn update(&mut self, dt: f64) -> Option<Tech> {
+        let (tech, time, total_time) = match self.current {
+            Some((tech, time, total_time)) => (tech, time + dt, total_time),
+            None => return None
+        };
+
+        if time < total_time {
+            self.current = Some((tech, time, total_time));
+            return None;
+        }
+
+        self.current = None;
+        self.completed.insert(tech);
+        self.apply(tech);
+
+        Some(tech)
+    }
+
+    pub fn is_class_unlocked(&self, class: SquadClass) -> bool {
+        match class {
+            SquadClass::Fighter => true,
+            SquadClass::Cruiser => self.completed.contains(&Tech::Cruisers)
+        }
+    }
+
+    fn apply(&mut self, tech: Tech) {
+        match tech {
+            Tech::Engines | Tech::AdvancedEngines => self.modifiers.speed += 0.2,
+            Tech::Weapons => self.modifiers.damage += 0.2,
+            Tech::Mining => self.modifiers.income += 0.5,
+            Tech::Boarding => self.modifiers.capture += 0.5,
+            Tech::Sensors => self.modifiers.vision += 0.5,
+            Tech::Cruisers => { }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::SquadClass;
+    use super::{Research, Tech, TechState};
+
+    #[test]
+    fn requirements() {
+        let mut research = Research::new();
+
+        assert!(research.can_start(Tech::Weapons));
+        assert!(!research.can_start(Tech::Cruisers));
+        assert!(!research.can_start(Tech::AdvancedEngines));
+
+        research.start(Tech::Cruisers, 10.0);
+        assert!(research.update(20.0).is_none());
+
+        research.start(Tech::Weapons, 10.0);
+
+        // A single tech is researched at a time
+        assert!(!research.can_start(Tech::Engines));
+        assert!(!research.can_start(Tech::Weapons));
+
+        assert_eq!(research.update(10.0), Some(Tech::Weapons));
+
+        assert!(research.can_start(Tech::Cruisers));
+        assert!(!research.can_start(Tech::Weapons));
+    }
+
+    #[test]
+    fn completion() {
+        let mut research = Research::new();
+        research.start(Tech::Mining, 10.0);
+
+        assert_eq!(research.update(4.0), None);
+
+        match research.tech_state(Tech::Mining) {
+            TechState::Researching { progress } => assert!((progress - 0.4).abs() < 1e-9),
+            _ => panic!("tech is not being researched")
+        }
+
+        assert_eq!(research.modifiers().income, 1.0);
+
+        // Completion is reported once
+        assert_eq!(research.update(6.0), Some(Tech::Mining));
+        assert_eq!(research.update(6.0), None);
+
+        match research.tech_state(Tech::Mining) {
+            TechState::Completed => { },
+            _ => panic!("tech is not completed")
+        }
+
+        assert_eq!(research.modifiers().income, 1.5);
+    }
+
+    #[test]
+    fn class_unlock() {
+        let mut research = Research::new();
+
+        assert!(research.is_class_unlocked(SquadClass::Fighter));
+        assert!(!research.is_class_unlocked(SquadClass::Cruiser));
+
+        research.start(Tech::Weapons, 1.0);
+        research.update(1.0);
+        assert!(!research.is_class_unlocked(SquadClass::Cruiser));
+
+        research.start(Tech::Cruisers, 1.0);
+        research.update(1.0);
+        assert!(research.is_class_unlocked(SquadClass::Cruiser));
+    }
+}
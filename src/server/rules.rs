@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+
+use rustc_serialize::json::Object;
+
+use common::{ParseCommandError, ParseCommandResult, SquadClass};
+use common::utils::json;
+use server::research::{Tech, TECHS};
+use server::waypoint::WaypointType;
+
+#[derive(Debug)]
+pub enum LoadRulesError {
+    Io(io::Error),
+    Parse(ParseCommandError),
+    Invalid(String)
+}
+
+pub type LoadRulesResult<T> = Result<T, LoadRulesError>;
+
+const RULE_NAMES: [&'static str; 19] = [
+    "starting_gold",
+    "income_rate",
+    "planet_income",
+    "upkeep_per_supply",
+    "supply_base",
+    "supply_per_planet",
+    "debt_attrition",
+    "merge_distance",
+    "orbit_distance",
+    "planet_capture_threshold",
+    "planetoid_capture_threshold",
+    "capture_rate",
+    "capture_decay",
+    "moving_attack_factor",
+    "planet_defense_factor",
+    "planetoid_defense_factor",
+    "waypoint_vision_range",
+    "classes",
+    "techs"
+];
+
+const CLASS_RULE_NAMES: [&'static str; 7] = ["cost", "supply", "life", "speed", "weapon_range", "damage", "vision_range"];
+
+const TECH_RULE_NAMES: [&'static str; 2] = ["cost", "time"];
+
+#[derive(Clone)]
+pub struct ClassRules {
+    pub cost: f64,
+    /// Supply taken by a full squad of the class
+    pub supply: f64,
+    pub life: f64,
+    pub speed: f64,
+    pub weapon_range: f64,
+    /// Damage per second of a single ship of the class
+    pub damage: f64,
+    /// Distance at which enemy squads are seen under fog of war
+    pub vision_range: f64
+}
+
+#[derive(Clone)]
+pub struct TechRules {
+    pub cost: f64,
+    /// Research time in seconds
+    pub time: f64
+}
+
+/// Balance constants of a match. Every value may be overridden by a JSON rules file, missing
+/// values keep their defaults.
+#[derive(Clone)]
+pub struct Rules {
+    pub starting_gold: f64,
+    /// Gold per second for the cube root of owned planetoids count
+    pub income_rate: f64,
+    /// Gold per second for each owned planet
+    pub planet_income: f64,
+    /// Gold per second paid for each used supply point
+    pub upkeep_per_supply: f64,
+    pub supply_base: f64,
+    pub supply_per_planet: f64,
+    /// Share of full squad life lost per second while player's gold is negative
+    pub debt_attrition: f64,
+    pub merge_distance: f64,
+    /// Distance at which an arrived squad is put on orbit of a waypoint
+    pub orbit_distance: f64,
+    pub planet_capture_threshold: f64,
+    pub planetoid_capture_threshold: f64,
+    /// Capture progress per second for each point of squads life
+    pub capture_rate: f64,
+    /// Capture progress lost per second while nobody is on orbit
+    pub capture_decay: f64,
+    pub moving_attack_factor: f64,
+    pub planet_defense_factor: f64,
+    pub planetoid_defense_factor: f64,
+    /// Distance at which owned waypoints see enemy squads under fog of war
+    pub waypoint_vision_range: f64,
+    pub fighter: ClassRules,
+    pub cruiser: ClassRules,
+    pub techs: HashMap<Tech, TechRules>
+}
+
+impl Rules {
+    pub fn new() -> Self {
+        Rules {
+            starting_gold: 15.0,
+            income_rate: 1.0,
+            planet_income: 0.0,
+            upkeep_per_supply: 0.02,
+            supply_base: 10.0,
+            supply_per_planet: 10.0,
+            debt_attrition: 0.05,
+            merge_distance: 5.0,
+            orbit_distance: 10.0,
+            planet_capture_threshold: 100.0,
+            planetoid_capture_threshold: 50.0,
+            capture_rate: 1.0,
+            capture_decay: 5.0,
+            moving_attack_factor: 0.5,
+            planet_defense_factor: 0.5,
+            planetoid_defense_factor: 0.75,
+            waypoint_vision_range: 60.0,
+            fighter: ClassRules {
+                cost: 10.0,
+                supply: 1.0,
+                life: 10.0,
+                speed: 50.0,
+                weapon_range: 10.0,
+                damage: 1.0,
+                vision_range: 80.0
+            },
+            cruiser: ClassRules {
+                cost: 20.0,
+                supply: 2.0,
+                life: 10.0,
+                speed: 35.0,
+                weapon_range: 25.0,
+                damage: 0.6,
+                vision_range: 100.0
+            },
+            techs: TECHS.iter().map(|&tech| (tech, default_tech_rules(tech))).collect()
+        }
+    }
+
+    pub fn load(path: &str) -> LoadRulesResult<Rules> {
+        let mut raw = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut raw))
+            .map_err(LoadRulesError::Io)?;
+
+        Self::parse(&raw)
+    }
+
+    pub fn class(&self, class: SquadClass) -> &ClassRules {
+        match class {
+            SquadClass::Fighter => &self.fighter,
+            SquadClass::Cruiser => &self.cruiser
+        }
+    }
+
+    pub fn tech(&self, tech: Tech) -> &TechRules {
+        &self.techs[&tech]
+    }
+
+    pub fn capture_threshold(&self, waypoint_type: WaypointType) -> Option<f64> {
+        match waypoint_type {
+            WaypointType::Planet => Some(self.planet_capture_threshold),
+            WaypointType::Planetoid => Some(self.planetoid_capture_threshold),
+            WaypointType::Asteroid | WaypointType::BlackHole => None
+        }
+    }
+
+    fn parse(raw: &str) -> LoadRulesResult<Rules> {
+        let json = json::parse_json(raw).map_err(LoadRulesError::Parse)?;
+        let params = json::parse_json_as_object(&json).map_err(LoadRulesError::Parse)?;
+
+        let rules = Self::parse_params(params).map_err(LoadRulesError::Parse)?;
+
+        check_rule_names(params).map_err(LoadRulesError::Invalid)?;
+        rules.validate().map_err(LoadRulesError::Invalid)?;
+
+        Ok(rules)
+    }
+
+    fn parse_params(params: &Object) -> ParseCommandResult<Rules> {
+        let defaults = Self::new();
+
+        let (fighter, cruiser) = match params.get("classes") {
+            Some(_) => {
+                let classes = json::parse_object_from_json_object(params, "classes")?;
+
+                (
+                    parse_class_rules(classes, SquadClass::Fighter, &defaults.fighter)?,
+                    parse_class_rules(classes, SquadClass::Cruiser, &defaults.cruiser)?
+                )
+            },
+
+            None => (defaults.fighter.clone(), defaults.cruiser.clone())
+        };
+
+        let techs = match params.get("techs") {
+            Some(_) => {
+                let techs_object = json::parse_object_from_json_object(params, "techs")?;
+
+                let mut techs = HashMap::new();
+                for &tech in TECHS.iter() {
+                    techs.insert(tech, parse_tech_rules(techs_object, tech, defaults.tech(tech))?);
+                }
+
+                techs
+            },
+
+            None => defaults.techs.clone()
+        };
+
+        Ok(Rules {
+            starting_gold: parse_f64_or(params, "starting_gold", defaults.starting_gold)?,
+            income_rate: parse_f64_or(params, "income_rate", defaults.income_rate)?,
+            planet_income: parse_f64_or(params, "planet_income", defaults.planet_income)?,
+            upkeep_per_supply: parse_f64_or(params, "upkeep_per_supply", defaults.upkeep_per_supply)?,
+            supply_base: parse_f64_or(params, "supply_base", defaults.supply_base)?,
+            supply_per_planet: parse_f64_or(params, "supply_per_planet", defaults.supply_per_planet)?,
+            debt_attrition: parse_f64_or(params, "debt_attrition", defaults.debt_attrition)?,
+            merge_distance: parse_f64_or(params, "merge_distance", defaults.merge_distance)?,
+            orbit_distance: parse_f64_or(params, "orbit_distance", defaults.orbit_distance)?,
+            planet_capture_threshold: parse_f64_or(params, "planet_capture_threshold", defaults.planet_capture_threshold)?,
+            planetoid_capture_threshold: parse_f64_or(params, "planetoid_capture_threshold", defaults.planetoid_capture_threshold)?,
+            capture_rate: parse_f64_or(params, "capture_rate", defaults.capture_rate)?,
+            capture_decay: parse_f64_or(params, "capture_decay", defaults.capture_decay)?,
+            moving_attack_factor: parse_f64_or(params, "moving_attack_factor", defaults.moving_attack_factor)?,
+            planet_defense_factor: parse_f64_or(params, "planet_defense_factor", defaults.planet_defense_factor)?,
+            planetoid_defense_factor: parse_f64_or(params, "planetoid_defense_factor", defaults.planetoid_defense_factor)?,
+            waypoint_vision_range: parse_f64_or(params, "waypoint_vision_range", defaults.waypoint_vision_range)?,
+            fighter,
+            cruiser,
+            techs
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let positive_values = [
+            ("income_rate", self.income_rate),
+            ("merge_distance", self.merge_distance),
+            ("orbit_distance", self.orbit_distance),
+            ("planet_capture_threshold", self.planet_capture_threshold),
+            ("planetoid_capture_threshold", self.planetoid_capture_threshold),
+            ("capture_rate", self.capture_rate),
+            ("waypoint_vision_range", self.waypoint_vision_range)
+        ];
+
+        for &(name, value) in positive_values.iter() {
+            if !(value > 0_f64) {
+                return Err(format!("{} must be positive", name));
+            }
+        }
+
+        let non_negative_values = [
+            ("starting_gold", self.starting_gold),
+            ("capture_decay", self.capture_decay),
+            ("planet_income", self.planet_income),
+            ("upkeep_per_supply", self.upkeep_per_supply),
+            ("supply_base", self.supply_base),
+            ("supply_per_planet", self.supply_per_planet),
+            ("debt_attrition", self.debt_attrition)
+        ];
+
+        for &(name, value) in non_negative_values.iter() {
+            if !(value >= 0_f64) {
+                return Err(format!("{} must not be negative", name));
+            }
+        }
+
+        let factors = [
+            ("moving_attack_factor", self.moving_attack_factor),
+            ("planet_defense_factor", self.planet_defense_factor),
+            ("planetoid_defense_factor", self.planetoid_defense_factor)
+        ];
+
+        for &(name, value) in factors.iter() {
+            if !(value >= 0_f64 && value <= 1_f64) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+
+        for &class in [SquadClass::Fighter, SquadClass::Cruiser].iter() {
+            let class_rules = self.class(class);
+
+            let class_values = [
+                ("cost", class_rules.cost),
+                ("supply", class_rules.supply),
+                ("life", class_rules.life),
+                ("speed", class_rules.speed),
+                ("weapon_range", class_rules.weapon_range),
+                ("damage", class_rules.damage),
+                ("vision_range", class_rules.vision_range)
+            ];
+
+            for &(name, value) in class_values.iter() {
+                if !(value > 0_f64) {
+                    return Err(format!("{} {} must be positive", class.name(), name));
+                }
+            }
+        }
+
+        for &tech in TECHS.iter() {
+            let tech_rules = self.tech(tech);
+
+            if !(tech_rules.cost >= 0_f64) {
+                return Err(format!("{} cost must not be negative", tech.name()));
+            }
+
+            if !(tech_rules.time > 0_f64) {
+                return Err(format!("{} time must be positive", tech.name()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Misspelled keys would silently keep defaults, so every key must be a known rule
+fn check_rule_names(params: &Object) -> Result<(), String> {
+    check_names(params, &RULE_NAMES, "")?;
+
+    if let Some(classes) = params.get("classes").and_then(|classes| classes.as_object()) {
+        let class_names = [SquadClass::Fighter, SquadClass::Cruiser]
+            .iter()
+            .map(|class| class.name())
+            .collect::<Vec<_>>();
+
+        check_names(classes, &class_names, "classes.")?;
+
+        for (name, class) in classes.iter() {
+            if let Some(class_object) = class.as_object() {
+                check_names(class_object, &CLASS_RULE_NAMES, &format!("classes.{}.", name))?;
+            }
+        }
+    }
+
+    if let Some(techs) = params.get("techs").and_then(|techs| techs.as_object()) {
+        let tech_names = TECHS.iter().map(|tech| tech.name()).collect::<Vec<_>>();
+
+        check_names(techs, &tech_names, "techs.")?;
+
+        for (name, tech) in techs.iter() {
+            if let Some(tech_object) = tech.as_object() {
+                check_names(tech_object, &TECH_RULE_NAMES, &format!("techs.{}.", name))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_names(object: &Object, names: &[&str], prefix: &str) -> Result<(), String> {
+    match object.keys().find(|key| !names.contains(&key.as_str())) {
+        Some(key) => Err(format!("unknown rule {}{}", prefix, key)),
+        None => Ok(())
+    }
+}
+
+fn parse_class_rules(classes: &Object, class: SquadClass, defaults: &ClassRules) -> ParseCommandResult<ClassRules> {
+    let class_object = match classes.get(class.name()) {
+        Some(_) => json::parse_object_from_json_object(classes, class.name())?,
+        None => return Ok(defaults.clone())
+    };
+
+    Ok(ClassRules {
+        cost: parse_f64_or(class_object, "cost", defaults.cost)?,
+        supply: parse_f64_or(class_object, "supply", defaults.supply)?,
+        life: parse_f64_or(class_object, "life", defaults.life)?,
+        speed: parse_f64_or(class_object, "speed", defaults.speed)?,
+        weapon_range: parse_f64_or(class_object, "weapon_range", defaults.weapon_range)?,
+        damage: parse_f64_or(class_object, "damage", defaults.damage)?,
+        vision_range: parse_f64_or(class_object, "vision_range", defaults.vision_range)?
+    })
+}
+
+fn parse_f64_or(object: &Object, property: &str, default: f64) -> ParseCommandResult<f64> {
+    match object.get(property) {
+        Some(_) => json::parse_f64_from_json_object(object, property),
+        None => Ok(default)
+    }
+}
+
+fn parse_tech_rules(techs: &Object, tech: Tech, defaults: &TechRules) -> ParseCommandResult<TechRules> {
+    let tech_object = match techs.get(tech.name()) {
+        Some(_) => json::parse_object_from_json_object(techs, tech.name())?,
+        None => return Ok(defaults.clone())
+    };
+
+    Ok(TechRules {
+        cost: parse_f64_or(tech_object, "cost", defaults.cost)?,
+        time: parse_f64_or(tech_object, "time", defaults.time)?
+    })
+}
+
+fn default_tech_rules(tech: Tech) -> TechRules {
+    let (cost, time) = match tech {
+        Tech::Engines => (20.0, 30.0),
+        Tech::Weapons => (25.0, 40.0),
+        Tech::Mining => (15.0, 30.0),
+        Tech::Boarding => (20.0, 30.0),
+        Tech::Sensors => (15.0, 30.0),
+        Tech::Cruisers => (30.0, 45.0),
+        Tech::AdvancedEngines => (40.0, 60.0)
+    };
+
+    TechRules { cost, time }
+}
+#[cfg(test)]
+mod tests {
+    use server::research::Tech;
+    use super::{LoadRulesError, Rules};
+
+    #[test]
+    fn defaults() {
+        let rules = Rules::parse("{}").unwrap();
+        let defaults = Rules::new();
+
+        assert_eq!(rules.starting_gold, defaults.starting_gold);
+        assert_eq!(rules.fighter.damage, defaults.fighter.damage);
+        assert_eq!(rules.tech(Tech::Mining).time, defaults.tech(Tech::Mining).time);
+    }
+
+    #[test]
+    fn partial_override() {
+        let raw = r#"{"starting_gold":40,"classes":{"cruiser":{"speed":20}},"techs":{"mining":{"cost":5}}}"#;
+        let rules = Rules::parse(raw).unwrap();
+        let defaults = Rules::new();
+
+        assert_eq!(rules.starting_gold, 40.0);
+        assert_eq!(rules.income_rate, defaults.income_rate);
+
+        assert_eq!(rules.cruiser.speed, 20.0);
+        assert_eq!(rules.cruiser.damage, defaults.cruiser.damage);
+        assert_eq!(rules.fighter.speed, defaults.fighter.speed);
+
+        assert_eq!(rules.tech(Tech::Mining).cost, 5.0);
+        assert_eq!(rules.tech(Tech::Mining).time, defaults.tech(Tech::Mining).time);
+        assert_eq!(rules.tech(Tech::Engines).cost, defaults.tech(Tech::Engines).cost);
+    }
+
+    #[test]
+    fn wrong_type() {
+        let broken_rules = [
+            "[]",
+            r#"{"starting_gold":"a lot"}"#,
+            r#"{"classes":[]}"#,
+            r#"{"classes":{"fighter":{"life":true}}}"#,
+            r#"{"techs":{"mining":5}}"#
+        ];
+
+        for raw in broken_rules.iter() {
+            match Rules::parse(raw) {
+                Err(LoadRulesError::Parse(_)) => { },
+                _ => panic!("rules of a wrong type are accepted: {}", raw)
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        let invalid_rules = [
+            r#"{"starting_gold":-1}"#,
+            r#"{"orbit_distance":0}"#,
+            r#"{"planet_defense_factor":1.5}"#,
+            r#"{"classes":{"cruiser":{"life":-10}}}"#,
+            r#"{"techs":{"sensors":{"time":0}}}"#
+        ];
+
+        for raw in invalid_rules.iter() {
+            match Rules::parse(raw) {
+                Err(LoadRulesError::Invalid(_)) => { },
+                _ => panic!("invalid rules are accepted: {}", raw)
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_names() {
+        let misspelled_rules = [
+            (r#"{"startng_gold":40}"#, "unknown rule startng_gold"),
+            (r#"{"classes":{"figther":{}}}"#, "unknown rule classes.figther"),
+            (r#"{"classes":{"fighter":{"sped":60}}}"#, "unknown rule classes.fighter.sped"),
+            (r#"{"techs":{"minning":{}}}"#, "unknown rule techs.minning"),
+            (r#"{"techs":{"mining":{"costs":5}}}"#, "unknown rule techs.mining.costs")
+        ];
+
+        for &(raw, expected_error) in misspelled_rules.iter() {
+            match Rules::parse(raw) {
+                Err(LoadRulesError::Invalid(ref error)) if error == expected_error => { },
+                _ => panic!("misspelled rule is accepted: {}", raw)
+            }
+        }
+    }
+}
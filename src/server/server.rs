@@ -1,20 +1,53 @@
-use std::cmp::min;
-use std::collections::HashMap;
+use std::cmp::{min, Ordering};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Receiver as ChannelReceiver};
 use std::thread;
 
+use constant_time_eq::constant_time_eq;
 use time;
 use rand::{random, thread_rng, Rng};
-use ws::{listen, Sender};
+use ws::{Builder, CloseCode, Sender, Settings as WebsocketSettings};
 
-use common::{Id, PlayerId, Position};
+use common::{ColorId, Id, PlayerId, Position, SquadClass, PLAYER_COLORS_COUNT};
+use common::rate_limit::{RateLimitCounters, RateLimits, MAX_MESSAGE_SIZE};
 use common::websocket_handler::WebsocketHandler;
+use server::admin;
+use server::admin::AdminCommand;
+use server::chat::{ChatChannel, ChatMessage, Ping};
+use server::combat;
 use server::command::Command;
+use server::diplomacy::{Diplomacy, Relation};
+use server::event::Event;
+use server::hyperlane::HyperlaneGraph;
+use server::income::Income;
 use server::json;
+use server::map;
 use server::player::Player;
+use server::rules::Rules;
+use server::settings::{Settings, VictoryCondition};
+use server::standing::Standing;
+use server::stats::{create_salt, hash_token, MatchResult, StatsStore};
 use server::squad::{Squad, SquadState};
 use server::waypoint::{Waypoint, WaypointType};
 
+/// Seconds between reports of rate limit counters in the server log
+const RATE_LIMIT_REPORT_INTERVAL: f64 = 60.0;
+
+/// Longest player name in characters
+const MAX_NAME_LENGTH: usize = 24;
+
+/// Count of players listed in the leaderboard
+const LEADERBOARD_SIZE: usize = 10;
+
+/// Bounds of the simulation speed multiplier
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 8.0;
+
 enum ServerState {
     Waiting,
     Playing,
@@ -22,30 +55,141 @@ enum ServerState {
 }
 
 pub struct Server {
+    settings: Settings,
+    rules: Rules,
     state: ServerState,
     players: HashMap<PlayerId, Player>,
+    /// Connection tokens of authenticated players
+    connections: HashMap<usize, PlayerId>,
+    /// Connections which have not sent a valid hello yet
+    pending_connections: HashMap<usize, Sender>,
+    /// Players by salted hashes of their session tokens, so that a token is never compared as is
+    sessions: HashMap<String, PlayerId>,
+    session_salt: String,
     squads: HashMap<Id, Squad>,
-    waypoints: HashMap<Id, Waypoint>
+    waypoints: HashMap<Id, Waypoint>,
+    hyperlanes: Option<HyperlaneGraph>,
+    diplomacy: Diplomacy,
+    events: Vec<Event>,
+    /// Number of leading events already counted in players' match stats
+    counted_events_count: usize,
+    chat_messages: Vec<ChatMessage>,
+    pings: Vec<Ping>,
+    match_time: f64,
+    hill_id: Option<Id>,
+    hill_hold_times: HashMap<PlayerId, f64>,
+    standings: Option<Vec<Standing>>,
+    is_paused: bool,
+    /// Simulation steps per tick, every step advances the same time as at normal speed
+    speed: f64,
+    /// Fraction of a step carried over to the next tick at speeds below 1x
+    pending_steps: f64,
+    /// Players who voted to toggle the pause state
+    pause_votes: HashSet<PlayerId>,
+    /// First player to join, allowed to pause and change speed without a vote
+    host_id: Option<PlayerId>,
+    stats: Option<StatsStore>,
+    /// Number of the latest snapshot sent to clients
+    tick: u64,
+    rate_limit_counters: Arc<RateLimitCounters>
 }
 
 impl Server {
-    pub fn new() -> Self {
+    pub fn new(settings: Settings) -> Self {
+        let (waypoints, hyperlanes) = Self::create_map(&settings);
+
+        let rules = match settings.rules_path {
+            Some(ref rules_path) => Rules::load(rules_path).expect("Unable to load rules"),
+            None => Rules::new()
+        };
+
+        let hill_id = Self::find_hill(&waypoints, &rules);
+
+        let stats = settings.stats_path
+            .as_ref()
+            .map(|stats_path| StatsStore::load(stats_path).expect("Unable to load stats"));
+
         Server {
+            settings,
+            rules,
             state: ServerState::Waiting,
             players: HashMap::new(),
+            connections: HashMap::new(),
+            pending_connections: HashMap::new(),
+            sessions: HashMap::new(),
+            session_salt: create_salt(),
             squads: HashMap::new(),
-            waypoints: Self::generate_waypoints()
+            waypoints,
+            hyperlanes,
+            diplomacy: Diplomacy::new(),
+            events: vec![],
+            counted_events_count: 0,
+            chat_messages: vec![],
+            pings: vec![],
+            match_time: 0.0,
+            hill_id,
+            hill_hold_times: HashMap::new(),
+            standings: None,
+            is_paused: false,
+            speed: 1.0,
+            pending_steps: 0.0,
+            pause_votes: HashSet::new(),
+            host_id: None,
+            stats,
+            tick: 0,
+            rate_limit_counters: Arc::new(RateLimitCounters::new())
         }
     }
 
     pub fn run(&mut self, address: String) {
         let (tx, rx) = channel::<Command>();
-        thread::spawn(move || listen(&address[..], |sender| WebsocketHandler::new(sender, tx.clone())).unwrap());
+
+        let rate_limits = Arc::new(Self::create_rate_limits());
+        let rate_limit_counters = self.rate_limit_counters.clone();
+
+        // Frames longer than the input buffer close the connection before they are read whole
+        let websocket_settings = WebsocketSettings {
+            fragments_capacity: 4,
+            fragments_grow: false,
+            in_buffer_capacity: 2 * rate_limits.max_message_size,
+            in_buffer_grow: false,
+            .. WebsocketSettings::default()
+        };
+
+        thread::spawn(move || {
+            Builder::new()
+                .with_settings(websocket_settings)
+                .build(|sender| {
+                    WebsocketHandler::with_rate_limits(sender, tx.clone(), rate_limits.clone(), rate_limit_counters.clone())
+                })
+                .and_then(|websocket| websocket.listen(&address[..]))
+                .unwrap();
+        });
+
+        let console_rx = if self.settings.admin_console {
+            let (console_tx, console_rx) = channel::<String>();
+            admin::run_console(console_tx);
+            Some(console_rx)
+        } else {
+            None
+        };
 
         let mut time = time::precise_time_s();
+        let mut rate_limit_report_time = time;
         loop {
             self.process(&rx);
 
+            if let Some(ref console_rx) = console_rx {
+                while let Ok(line) = console_rx.try_recv() {
+                    println!("{}", self.execute_admin_line(&line, true));
+                }
+            }
+
+            if time - rate_limit_report_time >= RATE_LIMIT_REPORT_INTERVAL {
+                println!("Rate limits: {}", self.rate_limit_counters.describe());
+                rate_limit_report_time = time;
+            }
+
             let dt = time::precise_time_s() - time;
             time = time::precise_time_s();
 
@@ -56,73 +200,262 @@ impl Server {
         }
     }
 
+    /// Bursts and refills per second are generous enough for a player clicking fast
+    fn create_rate_limits() -> RateLimits {
+        RateLimits::new(MAX_MESSAGE_SIZE, 50.0, 0.5, (10.0, 5.0))
+            .limit("squad_move", 20.0, 10.0)
+            .limit("squad_spawn", 10.0, 5.0)
+            .limit("research", 5.0, 1.0)
+            .limit("chat", 5.0, 1.0)
+            .limit("ping", 5.0, 1.0)
+            .limit("leaderboard", 2.0, 0.2)
+    }
+
     fn process(&mut self, rx: &ChannelReceiver<Command>) {
         while let Ok(command) = rx.try_recv() {
             match command {
-                Command::Connect { sender } => self.add_player(sender),
-
-                Command::Ready { sender } => {
-                    let player_id = sender.token().0 as PlayerId;
-                    self.players.get_mut(&player_id)
-                        .map(|player| player.set_ready_state());
+                Command::Connect { sender } => {
+                    self.pending_connections.insert(sender.token().0, sender);
                 },
 
-                Command::SquadMove { sender, squad_id, waypoint_id } => {
-                    let player_id = sender.token().0 as PlayerId;
+                Command::Hello { sender, token, name, profile } => self.authenticate(sender, token, name, profile),
 
-                    if let Some(waypoint) = self.waypoints.get(&waypoint_id) {
-                        self.squads.get_mut(&squad_id).map(|squad| {
-                            if squad.owner() == player_id {
-                                squad.move_to(waypoint.position());
-                            }
-                        });
-                    }
+                Command::Disconnect { sender } => {
+                    self.pending_connections.remove(&sender.token().0);
+                    self.connections.remove(&sender.token().0);
                 },
 
-                Command::SquadSpawn { sender, planet_id } => {
-                    let player_id = sender.token().0 as PlayerId;
+                command => self.process_player_command(command)
+            }
+        }
+    }
+
+    fn process_player_command(&mut self, command: Command) {
+        // Commands of connections which have not been authenticated are dropped
+        let player_id = match self.connections.get(&command.sender().token().0) {
+            Some(&player_id) => player_id,
+            None => return
+        };
+
+        match command {
+            Command::Ready { .. } => {
+                self.players.get_mut(&player_id)
+                    .map(|player| player.set_ready_state());
+            },
+
+            Command::Lobby { .. } => {
+                if let ServerState::Finished = self.state {
+                    self.players.get_mut(&player_id)
+                        .map(|player| player.set_pending_state());
+                }
+            },
+
+            Command::Leave { .. } => {
+                if !self.is_playing() {
+                    self.remove_player(player_id);
+                }
+            },
+
+            Command::SquadMove { squad_ids, waypoint_id, split, .. } => {
+                // A selection never holds the same squad twice nor more squads than the player owns
+                let owned_squads_count = self.squads
+                    .values()
+                    .filter(|squad| squad.owner() == player_id)
+                    .count();
+
+                let mut unique_squad_ids = HashSet::new();
+                let squad_ids = squad_ids
+                    .into_iter()
+                    .filter(|&squad_id| unique_squad_ids.insert(squad_id))
+                    .take(owned_squads_count)
+                    .collect::<Vec<_>>();
+
+                for squad_id in squad_ids {
+                    let moved_squad_id = if split {
+                        match self.split_squad(player_id, squad_id) {
+                            Some(split_squad_id) => split_squad_id,
+                            None => continue
+                        }
+                    } else {
+                        squad_id
+                    };
+
+                    self.move_squad(player_id, moved_squad_id, waypoint_id);
+                }
+            },
+
+            Command::SquadSpawn { planet_id, class, .. } => {
+                if let Some(waypoint) = self.waypoints.get(&planet_id) {
+                    if waypoint.waypoint_type() == WaypointType::Planet {
+                        let planet = waypoint;
+
+                        if let Some(player) = self.players.get_mut(&player_id) {
+                            let is_owner = planet.owner().map_or(false, |owner| owner == player_id);
+                            let is_unlocked = player.research().is_class_unlocked(class);
 
-                    if let Some(waypoint) = self.waypoints.get(&planet_id) {
-                        if waypoint.waypoint_type() == WaypointType::Planet {
-                            let planet = waypoint;
+                            let gold = player.gold();
+                            let cost = self.rules.class(class).cost;
+                            let has_gold = gold > cost;
 
-                            if let Some(player) = self.players.get_mut(&player_id) {
-                                let is_owner = planet.owner().map_or(false, |owner| owner == player_id);
+                            let supply_used = Self::get_supply_used(&self.squads, &self.rules, player_id);
+                            let supply_cap = Self::get_supply_cap(&self.waypoints, &self.rules, player_id);
+                            let has_supply = supply_used + self.rules.class(class).supply <= supply_cap;
 
-                                let gold = player.gold();
-                                let has_gold = gold > 10_f64;
+                            if is_owner && is_unlocked && has_gold && has_supply {
+                                let squad_id = random::<Id>();
+                                let position = planet.position();
 
-                                if is_owner && has_gold {
-                                    let squad_id = random::<Id>();
-                                    let position = planet.position();
+                                let life = self.rules.class(class).life;
 
-                                    let mut squad = Squad::new(squad_id, player_id, position);
-                                    squad.set_state(SquadState::OnOrbit { waypoint_id: planet.id() });
+                                let mut squad = Squad::new(squad_id, player_id, class, position, life);
+                                squad.set_state(SquadState::OnOrbit { waypoint_id: planet.id() });
 
-                                    self.squads.insert(squad_id, squad);
+                                self.squads.insert(squad_id, squad);
+                                self.events.push(Event::SquadSpawned { squad_id, owner: player_id, class, waypoint_id: planet.id() });
 
-                                    player.set_gold(gold - 10.0);
-                                }
+                                player.set_gold(gold - cost);
                             }
                         }
                     }
-                },
+                }
+            },
 
-                _ => { }
-            }
+            Command::Research { tech, .. } => {
+                let tech_rules = self.rules.tech(tech).clone();
+
+                // Starting gold is kept for the match, not spent in the lobby
+                if let ServerState::Playing = self.state {
+                    if let Some(player) = self.players.get_mut(&player_id) {
+                        let gold = player.gold();
+
+                        if player.research().can_start(tech) && gold >= tech_rules.cost {
+                            player.set_gold(gold - tech_rules.cost);
+                            player.research_mut().start(tech, tech_rules.time);
+                        }
+                    }
+                }
+            },
+
+            Command::Team { team, .. } => {
+                if let ServerState::Waiting = self.state {
+                    self.players.get_mut(&player_id)
+                        .map(|player| player.set_team(team));
+                }
+            },
+
+            Command::Color { color, .. } => {
+                let is_taken = self.players
+                    .values()
+                    .any(|player| player.id() != player_id && player.color() == color);
+
+                if let ServerState::Waiting = self.state {
+                    if !is_taken {
+                        self.players.get_mut(&player_id)
+                            .map(|player| player.set_color(color));
+                    }
+                }
+            },
+
+            Command::DiplomacyPropose { player_id: other_player_id, relation, .. } => {
+                if self.settings.diplomacy && self.players.contains_key(&other_player_id) {
+                    self.diplomacy.propose(player_id, other_player_id, relation);
+                }
+            },
+
+            Command::DiplomacyAccept { player_id: other_player_id, relation, .. } => {
+                if self.settings.diplomacy {
+                    self.diplomacy.accept(player_id, other_player_id, relation);
+                }
+            },
+
+            Command::DiplomacyBreak { player_id: other_player_id, .. } => {
+                if self.settings.diplomacy {
+                    self.diplomacy.break_relation(player_id, other_player_id);
+                }
+            },
+
+            Command::Chat { channel, text, .. } => {
+                self.add_chat_message(player_id, channel, &text);
+            },
+
+            // A pause voted in the lobby would carry over into the match
+            Command::Pause { .. } if !self.is_playing() => { },
+
+            Command::Pause { paused, .. } => {
+                if self.is_privileged(player_id) {
+                    self.set_paused(paused);
+                } else if paused != self.is_paused {
+                    self.pause_votes.insert(player_id);
+
+                    // Majority of players still in the match toggles the pause state
+                    let voters_count = self.players.values().filter(|player| !player.is_loose()).count();
+                    if self.pause_votes.len() * 2 > voters_count {
+                        self.set_paused(paused);
+                    }
+                }
+            },
+
+            Command::Speed { speed, .. } => {
+                if self.is_playing() && self.is_privileged(player_id) {
+                    self.set_speed(speed);
+                }
+            },
+
+            Command::Admin { line, .. } => {
+                let is_admin = self.players.get(&player_id)
+                    .map_or(false, |player| player.is_admin());
+
+                if is_admin {
+                    let output = self.execute_admin_line(&line, false);
+
+                    self.players.get(&player_id)
+                        .map(|player| player.send(json::format_admin_command(&output)));
+                }
+            },
+
+            Command::Leaderboard { .. } => {
+                let leaderboard_json = match self.stats {
+                    Some(ref stats) => json::format_leaderboard_command(&stats.leaderboard(LEADERBOARD_SIZE)),
+                    None => json::format_leaderboard_command(&vec![])
+                };
+
+                self.players.get(&player_id)
+                    .map(|player| player.send(leaderboard_json));
+            },
+
+            Command::Ping { position, .. } => {
+                let is_allowed = self.players.get_mut(&player_id)
+                    .map_or(false, |player| player.take_ping());
+
+                if is_allowed {
+                    self.pings.push(Ping { from: player_id, position });
+                }
+            },
+
+            _ => { }
         }
     }
 
     fn update(&mut self, dt: f64) {
         self.update_server_state();
 
-        if !self.is_playing() {
+        if !self.is_playing() || self.is_paused {
             return;
         }
 
+        // Faster speeds run more steps rather than longer ones, so results do not depend on speed
+        self.pending_steps += self.speed;
+
+        while self.pending_steps >= 1_f64 && self.is_playing() {
+            self.pending_steps -= 1_f64;
+            self.step(dt);
+        }
+    }
+
+    fn step(&mut self, dt: f64) {
         self.update_players(dt);
         self.update_squads(dt);
-        self.update_waypoints();
+        self.update_waypoints(dt);
 
         self.merge_squads();
         self.update_fight(dt);
@@ -140,11 +473,7 @@ impl Server {
                     .all(|player| player.is_ready());
 
                 if is_all_ready {
-                    self.state = ServerState::Playing;
-
-                    for player in self.players.values_mut() {
-                        player.set_playing_state();
-                    }
+                    self.start_match();
                 }
             },
 
@@ -153,227 +482,1114 @@ impl Server {
                     .values()
                     .any(|player| player.is_win());
 
-                if has_winner {
-                    self.state = ServerState::Finished
+                // The last players eliminated in the same tick leave nobody to win, it is a draw
+                let is_draw = self.players
+                    .values()
+                    .all(|player| !player.is_playing());
+
+                if has_winner || is_draw {
+                    self.finish_match();
                 }
             },
 
-            ServerState::Finished => { }
+            ServerState::Finished => {
+                // Disconnected players would keep the room on the results screen forever
+                let is_all_returned = self.players
+                    .values()
+                    .filter(|player| self.connections.values().any(|&player_id| player_id == player.id()))
+                    .all(|player| player.is_pending() || player.is_ready());
+
+                if is_all_returned {
+                    self.reset_match();
+                }
+            }
         }
     }
 
-    fn is_playing(&self) -> bool {
-        match self.state {
-            ServerState::Playing => true,
-            _ => false
+    fn start_match(&mut self) {
+        self.state = ServerState::Playing;
+
+        let rules_command_json = json::format_rules_command(&self.rules);
+
+        for player in self.players.values_mut() {
+            player.set_playing_state();
+            player.send(rules_command_json.clone());
         }
     }
 
-    fn update_players(&mut self, dt: f64) {
+    fn finish_match(&mut self) {
+        self.state = ServerState::Finished;
+
+        // Players still playing when an admin ends the match lose it, so they can return to the lobby
         for player in self.players.values_mut() {
-            let planets_count = self.waypoints
-                .values()
-                .filter(|waypoint| waypoint.waypoint_type() == WaypointType::Planet)
-                .filter(|planet| planet.owner().map_or(false, |owner| player.id() == owner))
-                .count();
+            player.set_loose_state();
+        }
 
-            if planets_count == 0 {
-                player.set_loose_state();
-            }
+        // Kills and captures of the final tick have to be counted before stats are recorded
+        self.update_match_stats();
 
-            let total_planets_count = self.waypoints
-                .values()
-                .filter(|waypoint| waypoint.waypoint_type() == WaypointType::Planet)
-                .count();
+        let mut standings = self.get_standings();
+        self.record_stats(&mut standings);
+        self.standings = Some(standings);
 
-            if planets_count == total_planets_count {
-                player.set_win_state();
-            }
+        let winners = self.players
+            .values()
+            .filter(|player| player.is_win())
+            .map(|player| player.id())
+            .collect::<Vec<_>>();
 
-            let planetoids_count = self.waypoints
-                .values()
-                .filter(|waypoint| waypoint.waypoint_type() == WaypointType::Planetoid)
-                .filter(|planetoid| planetoid.owner().map_or(false, |owner| player.id() == owner))
-                .count();
+        self.events.push(Event::MatchFinished { winners });
+    }
 
-            let gold = player.gold() + 1_f64 * (planetoids_count as f64).powf(1_f64 / 3_f64) * dt;
-            player.set_gold(gold);
+    /// Starts the lobby of the next match on a fresh map, players who are not connected are dropped
+    fn reset_match(&mut self) {
+        let (waypoints, hyperlanes) = Self::create_map(&self.settings);
+
+        self.hill_id = Self::find_hill(&waypoints, &self.rules);
+        self.waypoints = waypoints;
+        self.hyperlanes = hyperlanes;
+
+        self.state = ServerState::Waiting;
+        self.squads.clear();
+        self.diplomacy = Diplomacy::new();
+        self.match_time = 0.0;
+        self.hill_hold_times.clear();
+        self.standings = None;
+        self.is_paused = false;
+        self.speed = 1.0;
+        self.pending_steps = 0.0;
+        self.pause_votes.clear();
+
+        let connected_player_ids = self.connections
+            .values()
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        self.players.retain(|player_id, _| connected_player_ids.contains(player_id));
+        self.sessions.retain(|_, player_id| connected_player_ids.contains(player_id));
+
+        if self.host_id.map_or(false, |host_id| !self.players.contains_key(&host_id)) {
+            self.host_id = self.players.keys().next().cloned();
         }
-    }
 
-    fn update_squads(&mut self, dt: f64) {
-        for squad in self.squads.values_mut() {
-            match squad.state() {
-                SquadState::InSpace => { },
+        let player_ids = self.players.keys().cloned().collect::<Vec<_>>();
 
-                SquadState::Moving { destination } => {
-                    let Position(x, y) = squad.position();
-                    let Position(destination_x, destination_y) = destination;
+        for player_id in player_ids {
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player.reset(self.rules.starting_gold);
+            }
 
-                    let target = (destination_x - x, destination_y - y);
-                    let distance = (target.0.powi(2) + target.1.powi(2)).sqrt();
+            self.assign_planet(player_id);
+        }
+    }
 
-                    let max_step_distance = 50_f64 * dt;
+    /// Adds the match to the stats of named players and puts their new ratings into standings
+    fn record_stats(&mut self, standings: &mut Vec<Standing>) {
+        let results = self.players
+            .values()
+            .filter_map(|player| {
+                player.profile().map(|profile| {
+                    MatchResult {
+                        name: player.name().clone(),
+                        token: profile.clone(),
+                        is_winner: player.is_win(),
+                        stats: player.match_stats()
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
 
-                    if distance < max_step_distance {
-                        squad.set_position(destination);
+        if results.is_empty() {
+            return;
+        }
 
-                        let state = Self::find_waypoint_by_position(&self.waypoints, destination)
-                            .map_or(SquadState::InSpace, |waypoint| SquadState::OnOrbit { waypoint_id: waypoint.id() });
+        let stats = match self.stats {
+            Some(ref mut stats) => stats,
+            None => return
+        };
 
-                        squad.set_state(state);
-                    } else {
-                        let direction = (target.0 / distance, target.1 / distance);
-                        let position = Position(
-                            x + max_step_distance * direction.0,
-                            y + max_step_distance * direction.1
-                        );
+        let previous_ratings = results
+            .iter()
+            .map(|result| (result.name.clone(), stats.rating(&result.name)))
+            .collect::<HashMap<_, _>>();
 
-                        squad.set_position(position);
-                    }
-                },
+        let ratings = stats.record_match(&results, self.match_time);
 
-                SquadState::OnOrbit { .. } => { }
-            }
+        if let Err(error) = stats.save() {
+            println!("Unable to save stats: {}", error);
         }
-    }
 
-    fn update_waypoints(&mut self) {
-        for waypoint in self.waypoints.values_mut() {
-            let squads_on_orbit = self.squads
-                .values()
-                .filter(|squad| squad.is_on_orbit(waypoint.id()))
-                .collect::<Vec<_>>();
+        for standing in standings.iter_mut() {
+            let name = match self.players.get(&standing.player_id) {
+                Some(player) => player.name(),
+                None => continue
+            };
 
-            if let Some(first_squad) = squads_on_orbit.first() {
-                let owner = first_squad.owner();
-                if squads_on_orbit.iter().all(|squad| squad.owner() == owner) &&
-                    (waypoint.waypoint_type() == WaypointType::Planet || waypoint.waypoint_type() == WaypointType::Planetoid) {
-                    waypoint.set_owner(Some(owner));
-                }
+            if let (Some(&rating), Some(&previous_rating)) = (ratings.get(name), previous_ratings.get(name)) {
+                standing.rating = Some(rating);
+                standing.rating_change = Some(rating - previous_rating);
             }
         }
     }
 
-    fn merge_squads(&mut self) {
-        let merged_squads = self.get_merged_squads();
+    /// Counts squads built and destroyed and planets captured for stats at the end of the match
+    fn update_match_stats(&mut self) {
+        for event in self.events[self.counted_events_count..].iter() {
+            match *event {
+                Event::SquadSpawned { owner, .. } => {
+                    self.players.get_mut(&owner)
+                        .map(|player| player.match_stats_mut().squads_built += 1);
+                },
 
-        for (squad_id, squad_life) in merged_squads {
-            match squad_life {
-                Some(life) => {
-                    self.squads.get_mut(&squad_id)
-                        .map(|squad| squad.set_life(life));
+                Event::SquadDestroyed { killer: Some(killer), .. } => {
+                    self.players.get_mut(&killer)
+                        .map(|player| player.match_stats_mut().squads_destroyed += 1);
                 },
 
-                None => {
-                    self.squads.remove(&squad_id);
-                }
+                Event::WaypointCaptured { waypoint_id, owner, .. } => {
+                    let is_planet = self.waypoints.get(&waypoint_id)
+                        .map_or(false, |waypoint| waypoint.waypoint_type() == WaypointType::Planet);
+
+                    if is_planet {
+                        self.players.get_mut(&owner)
+                            .map(|player| player.match_stats_mut().planets_captured += 1);
+                    }
+                },
+
+                _ => { }
             }
         }
+
+        self.counted_events_count = self.events.len();
     }
 
-    fn get_merged_squads(&mut self) -> HashMap<Id, Option<f64>> {
-        let mut merged_squads = HashMap::new();
+    fn is_privileged(&self, player_id: PlayerId) -> bool {
+        self.host_id == Some(player_id) || self.players.get(&player_id).map_or(false, |player| player.is_admin())
+    }
 
-        let squads = self.squads
-            .values()
-            .filter(|squad| squad.is_standing())
-            .collect::<Vec<_>>();
+    fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+        self.pause_votes.clear();
+    }
 
-        for squad in &squads {
-            if merged_squads.contains_key(&squad.id()) {
-                continue;
-            }
+    fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(MIN_SPEED).min(MAX_SPEED);
+    }
 
-            let other_squads = squads
-                .iter()
-                .filter(|other_squad| {
-                    other_squad.id() != squad.id() &&
-                        other_squad.owner() == squad.owner() &&
-                        other_squad.position().distance_to(squad.position()) < 5_f64
-                })
-                .collect::<Vec<_>>();
+    /// `is_console` tells whether the line comes from stdin of the server rather than from a
+    /// remote admin, who may only touch files inside the configured directories
+    fn execute_admin_line(&mut self, line: &str, is_console: bool) -> String {
+        match AdminCommand::parse(line) {
+            Ok(command) => self.execute_admin_command(command, is_console),
+            Err(error) => error
+        }
+    }
 
-            if other_squads.is_empty() {
-                continue;
-            }
+    fn execute_admin_command(&mut self, command: AdminCommand, is_console: bool) -> String {
+        match command {
+            AdminCommand::List => self.describe_room(),
 
-            let other_squads_life = other_squads
-                .iter()
-                .fold(0_f64, |life, squad| life + squad.life());
+            AdminCommand::Kick { player_id } => {
+                let player_name = match self.players.get(&player_id) {
+                    Some(player) => {
+                        player.close("kicked");
+                        player.name().clone()
+                    },
 
-            merged_squads.insert(squad.id(), Some(squad.life() + other_squads_life));
+                    None => return format!("No player #{}", player_id)
+                };
 
-            for other_squad in other_squads {
-                merged_squads.insert(other_squad.id(), None);
-            }
-        }
+                self.remove_player(player_id);
 
-        merged_squads
-    }
+                format!("Kicked {} (#{})", player_name, player_id)
+            },
 
-    fn update_fight(&mut self, dt: f64) {
-        let hits = self.get_squads_hits();
+            AdminCommand::Pause | AdminCommand::Resume | AdminCommand::Speed { .. } if !self.is_playing() => {
+                "Pause and speed can be changed only during the match".to_string()
+            },
 
-        for (squad_id, hit) in hits {
-            let squad_life = self.squads.get(&squad_id)
-                .map(|squad| squad.life());
+            AdminCommand::Pause => {
+                self.set_paused(true);
+                "Paused".to_string()
+            },
 
-            if let Some(mut squad_life) = squad_life {
-                squad_life -= hit.min(squad_life.ceil()) * dt;
-                if squad_life < 0_f64 {
-                    self.squads.remove(&squad_id);
-                } else {
-                    self.squads.get_mut(&squad_id)
-                        .map(|squad| squad.set_life(squad_life));
+            AdminCommand::Resume => {
+                self.set_paused(false);
+                "Resumed".to_string()
+            },
+
+            AdminCommand::Speed { factor } => {
+                self.set_speed(factor);
+                format!("Speed is {}x", self.speed)
+            },
+
+            AdminCommand::Start => {
+                match self.state {
+                    ServerState::Waiting if !self.players.is_empty() => {
+                        // Players who are not ready would stay out of the match and it could never end
+                        for player in self.players.values_mut() {
+                            player.force_playing_state();
+                        }
+
+                        self.start_match();
+                        "Match started".to_string()
+                    },
+
+                    _ => "Match can be started only while waiting for players".to_string()
+                }
+            },
+
+            AdminCommand::End => {
+                match self.state {
+                    ServerState::Playing => {
+                        self.finish_match();
+                        "Match finished".to_string()
+                    },
+
+                    _ => "Match is not running".to_string()
+                }
+            },
+
+            AdminCommand::Dump { path } => {
+                let path = match Self::resolve_admin_path(self.settings.dump_dir.as_ref(), &path, is_console) {
+                    Ok(path) => path,
+                    Err(error) => return error
+                };
+
+                let dump = json::format_dump(
+                    &json::format_waypoints(&self.waypoints, &self.rules),
+                    &json::format_players(&self.players),
+                    &json::format_squads(&self.squads.values().collect(), |_| true),
+                    &json::format_match(&self.settings.victory, self.match_time, self.is_paused, self.speed),
+                    &json::format_standings(&self.standings, &self.players)
+                );
+
+                match File::create(&path).and_then(|mut file| file.write_all(dump.as_bytes())) {
+                    Ok(_) => format!("State dumped to {}", path),
+                    Err(error) => format!("Unable to dump state to {}: {}", path, error)
+                }
+            },
+
+            AdminCommand::ReloadRules { path } => {
+                let path = match path {
+                    Some(path) => match Self::resolve_admin_path(self.settings.rules_dir.as_ref(), &path, is_console) {
+                        Ok(path) => path,
+                        Err(error) => return error
+                    },
+
+                    None => match self.settings.rules_path.clone() {
+                        Some(path) => path,
+                        None => return "No rules file to reload".to_string()
+                    }
+                };
+
+                match Rules::load(&path) {
+                    Ok(rules) => {
+                        self.rules = rules;
+
+                        if self.is_playing() {
+                            let rules_command_json = json::format_rules_command(&self.rules);
+
+                            for player in self.players.values() {
+                                player.send(rules_command_json.clone());
+                            }
+                        }
+
+                        format!("Rules reloaded from {}", path)
+                    },
+
+                    Err(error) => format!("Unable to load rules from {}: {:?}", path, error)
                 }
             }
         }
     }
 
-    fn get_squads_hits(&self) -> HashMap<Id, f64> {
-        let mut hits: HashMap<Id, f64> = HashMap::new();
+    /// Console may use any path, a remote admin only a bare file name inside the directory
+    fn resolve_admin_path(directory: Option<&String>, name: &str, is_console: bool) -> Result<String, String> {
+        if is_console {
+            return Ok(name.to_string());
+        }
 
-        let combat_squads = self.squads
-            .values()
-            .filter(|squad| squad.is_standing())
-            .collect::<Vec<_>>();
+        let directory = match directory {
+            Some(directory) => directory,
+            None => return Err("Only allowed from the server console".to_string())
+        };
 
-        for combat_squad in &combat_squads {
-            let attacked_squads = combat_squads
-                .iter()
-                .filter(|attacked_squad| {
-                    attacked_squad.owner() != combat_squad.owner() &&
-                        attacked_squad.id() != combat_squad.id() &&
-                        attacked_squad.position().distance_to(combat_squad.position()) < 10_f64
-                })
-                .collect::<Vec<_>>();
+        let is_bare_name = Path::new(name).file_name() == Some(OsStr::new(name)) &&
+            !name.contains(|c: char| c == '/' || c == '\\' || c == ':');
 
-            let attack = 1_f64 * combat_squad.life().ceil() / attacked_squads.len() as f64;
-            for attacked_squad in attacked_squads {
-                let hit = hits.get(&attacked_squad.id()).unwrap_or(&0_f64) + attack;
-                hits.insert(attacked_squad.id(), hit);
-            }
+        if !is_bare_name {
+            return Err(format!("Expected a file name without directories: {}", name));
         }
 
-        hits
+        Ok(Path::new(directory).join(name).to_string_lossy().into_owned())
+    }
+
+    /// The server hosts a single match, listed as the only room
+    fn describe_room(&self) -> String {
+        let state = match self.state {
+            ServerState::Waiting => "waiting",
+            ServerState::Playing => "playing",
+            ServerState::Finished => "finished"
+        };
+
+        let mut lines = vec![
+            format!(
+                "Room main: {}, {} players, time {:.0}s, speed {}x{}",
+                state,
+                self.players.len(),
+                self.match_time,
+                self.speed,
+                if self.is_paused { ", paused" } else { "" }
+            )
+        ];
+
+        let mut players = self.players.values().collect::<Vec<_>>();
+        players.sort_by_key(|player| player.id());
+
+        for player in players {
+            lines.push(format!(
+                "#{} {}: {}, team {}, gold {:.0}{}",
+                player.id(),
+                player.name(),
+                json::format_player_state(player),
+                player.team().map_or("-".to_string(), |team| team.to_string()),
+                player.gold(),
+                if player.is_admin() { ", admin" } else { "" }
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    fn is_playing(&self) -> bool {
+        match self.state {
+            ServerState::Playing => true,
+            _ => false
+        }
+    }
+
+    fn update_players(&mut self, dt: f64) {
+        self.match_time += dt;
+
+        let mut eliminated_players = vec![];
+        let mut indebted_players = vec![];
+
+        for player in self.players.values_mut() {
+            let planets_count = Self::count_owned_waypoints(&self.waypoints, player.id(), WaypointType::Planet);
+
+            if planets_count == 0 && player.is_playing() {
+                player.set_loose_state();
+                eliminated_players.push(player.id());
+                self.events.push(Event::PlayerEliminated { player_id: player.id() });
+            }
+
+            let planetoids_count = Self::count_owned_waypoints(&self.waypoints, player.id(), WaypointType::Planetoid);
+
+            let income_modifier = player.research().modifiers().income;
+
+            let supply_used = Self::get_supply_used(&self.squads, &self.rules, player.id());
+            let supply_cap = Self::get_supply_cap(&self.waypoints, &self.rules, player.id());
+            player.set_supply(supply_used, supply_cap);
+
+            let income = Income {
+                planetoids: self.rules.income_rate * income_modifier * (planetoids_count as f64).powf(1_f64 / 3_f64),
+                planets: self.rules.planet_income * income_modifier * planets_count as f64,
+                upkeep: self.rules.upkeep_per_supply * supply_used,
+                modifier: income_modifier
+            };
+            player.set_income(income);
+
+            let gold = player.gold() + income.total() * dt;
+            player.set_gold(gold);
+
+            if gold < 0_f64 {
+                indebted_players.push(player.id());
+            }
+
+            if let Some(tech) = player.research_mut().update(dt) {
+                self.events.push(Event::ResearchCompleted { player_id: player.id(), tech });
+            }
+        }
+
+        for player_id in eliminated_players {
+            self.remove_player_assets(player_id);
+        }
+
+        self.apply_debt_attrition(&indebted_players, dt);
+
+        self.update_victory(dt);
+    }
+
+    fn update_victory(&mut self, dt: f64) {
+        let playing_players = self.players
+            .values()
+            .filter(|player| player.is_playing())
+            .map(|player| player.id())
+            .collect::<Vec<_>>();
+
+        let mut winners = match self.settings.victory {
+            VictoryCondition::Conquest => {
+                let total_planets_count = self.waypoints
+                    .values()
+                    .filter(|waypoint| waypoint.waypoint_type() == WaypointType::Planet)
+                    .count();
+
+                playing_players
+                    .iter()
+                    .cloned()
+                    .filter(|&player_id| {
+                        let allied_planets_count = self.waypoints
+                            .values()
+                            .filter(|waypoint| waypoint.waypoint_type() == WaypointType::Planet)
+                            .filter(|planet| {
+                                planet.owner().map_or(false, |owner| Self::are_allies(&self.players, &self.diplomacy, owner, player_id))
+                            })
+                            .count();
+
+                        allied_planets_count == total_planets_count
+                    })
+                    .collect::<Vec<_>>()
+            },
+
+            VictoryCondition::Score { time_limit } => {
+                if self.match_time < time_limit {
+                    vec![]
+                } else {
+                    let best_score = playing_players
+                        .iter()
+                        .map(|&player_id| self.get_player_score(player_id))
+                        .fold(::std::f64::NEG_INFINITY, f64::max);
+
+                    playing_players
+                        .iter()
+                        .cloned()
+                        .filter(|&player_id| self.get_player_score(player_id) >= best_score)
+                        .collect::<Vec<_>>()
+                }
+            },
+
+            VictoryCondition::KingOfTheHill { hold_time } => {
+                let hill_owner = self.hill_id
+                    .and_then(|hill_id| self.waypoints.get(&hill_id))
+                    .and_then(|hill| hill.owner())
+                    .and_then(|owner| if playing_players.contains(&owner) { Some(owner) } else { None });
+
+                if let Some(hill_owner) = hill_owner {
+                    *self.hill_hold_times.entry(hill_owner).or_insert(0_f64) += dt;
+                }
+
+                playing_players
+                    .iter()
+                    .cloned()
+                    .filter(|player_id| self.hill_hold_times.get(player_id).map_or(false, |&time| time >= hold_time))
+                    .collect::<Vec<_>>()
+            },
+
+            VictoryCondition::Economic { gold_target } => {
+                playing_players
+                    .iter()
+                    .cloned()
+                    .filter(|player_id| self.players[player_id].gold() >= gold_target)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let is_last_alliance_standing = playing_players
+            .iter()
+            .all(|&a| playing_players.iter().all(|&b| Self::are_allies(&self.players, &self.diplomacy, a, b)));
+
+        if winners.is_empty() && is_last_alliance_standing && self.players.len() > playing_players.len() {
+            winners = playing_players.clone();
+        }
+
+        if winners.is_empty() {
+            return;
+        }
+
+        // Allies share the victory
+        let winners = playing_players
+            .iter()
+            .cloned()
+            .filter(|&player_id| winners.iter().any(|&winner| Self::are_allies(&self.players, &self.diplomacy, winner, player_id)))
+            .collect::<Vec<_>>();
+
+        for player in self.players.values_mut() {
+            if winners.contains(&player.id()) {
+                player.set_win_state();
+            } else {
+                player.set_loose_state();
+            }
+        }
+    }
+
+    /// Score for the time limited victory: planets and planetoids owned plus squads life and gold
+    fn get_player_score(&self, player_id: PlayerId) -> f64 {
+        let planets_count = Self::count_owned_waypoints(&self.waypoints, player_id, WaypointType::Planet);
+        let planetoids_count = Self::count_owned_waypoints(&self.waypoints, player_id, WaypointType::Planetoid);
+
+        let squads_life = self.squads
+            .values()
+            .filter(|squad| squad.owner() == player_id)
+            .fold(0_f64, |life, squad| life + squad.life());
+
+        let gold = self.players.get(&player_id).map_or(0_f64, |player| player.gold());
+
+        100_f64 * planets_count as f64 + 30_f64 * planetoids_count as f64 + squads_life + gold
+    }
+
+    /// Squads of players with negative gold slowly fall apart
+    fn apply_debt_attrition(&mut self, player_ids: &[PlayerId], dt: f64) {
+        if player_ids.is_empty() {
+            return;
+        }
+
+        for squad in self.squads.values_mut() {
+            if player_ids.contains(&squad.owner()) {
+                let life = squad.life() - self.rules.class(squad.class()).life * self.rules.debt_attrition * dt;
+                squad.set_life(life);
+            }
+        }
+
+        let lost_squad_ids = self.squads
+            .values()
+            .filter(|squad| squad.life() <= 0_f64)
+            .map(|squad| squad.id())
+            .collect::<Vec<_>>();
+
+        for squad_id in lost_squad_ids {
+            self.remove_squad(squad_id, None);
+        }
+    }
+
+    /// Supply scales with the ships count of a class and is rounded up once, so splitting squads costs nothing
+    fn get_supply_used(squads: &HashMap<Id, Squad>, rules: &Rules, player_id: PlayerId) -> f64 {
+        let supply_used: f64 = [SquadClass::Fighter, SquadClass::Cruiser]
+            .iter()
+            .map(|&class| {
+                let class_rules = rules.class(class);
+                let life: f64 = squads
+                    .values()
+                    .filter(|squad| squad.owner() == player_id && squad.class() == class)
+                    .map(|squad| squad.life())
+                    .sum();
+
+                life / class_rules.life * class_rules.supply
+            })
+            .sum();
+
+        supply_used.ceil()
+    }
+
+    fn get_supply_cap(waypoints: &HashMap<Id, Waypoint>, rules: &Rules, player_id: PlayerId) -> f64 {
+        let planets_count = Self::count_owned_waypoints(waypoints, player_id, WaypointType::Planet);
+        rules.supply_base + rules.supply_per_planet * planets_count as f64
+    }
+
+    fn remove_squad(&mut self, squad_id: Id, killer: Option<PlayerId>) {
+        if let Some(squad) = self.squads.remove(&squad_id) {
+            self.events.push(Event::SquadDestroyed { squad_id, owner: squad.owner(), killer, position: squad.position() });
+        }
+    }
+
+    fn remove_player_assets(&mut self, player_id: PlayerId) {
+        let squad_ids = self.squads
+            .values()
+            .filter(|squad| squad.owner() == player_id)
+            .map(|squad| squad.id())
+            .collect::<Vec<_>>();
+
+        for squad_id in squad_ids {
+            self.remove_squad(squad_id, None);
+        }
+
+        for waypoint in self.waypoints.values_mut() {
+            if waypoint.owner() == Some(player_id) {
+                waypoint.set_owner(None);
+            }
+
+            if waypoint.capturer() == Some(player_id) {
+                waypoint.set_capture(None, 0_f64);
+            }
+        }
+    }
+
+    fn count_owned_waypoints(waypoints: &HashMap<Id, Waypoint>, player_id: PlayerId, waypoint_type: WaypointType) -> usize {
+        waypoints
+            .values()
+            .filter(|waypoint| waypoint.waypoint_type() == waypoint_type)
+            .filter(|waypoint| waypoint.owner().map_or(false, |owner| owner == player_id))
+            .count()
+    }
+
+    fn get_standings(&self) -> Vec<Standing> {
+        let mut standings = self.players
+            .values()
+            .map(|player| {
+                Standing {
+                    player_id: player.id(),
+                    is_winner: player.is_win(),
+                    score: self.get_player_score(player.id()),
+                    planets_count: Self::count_owned_waypoints(&self.waypoints, player.id(), WaypointType::Planet),
+                    squads_count: self.squads.values().filter(|squad| squad.owner() == player.id()).count(),
+                    stats: player.match_stats(),
+                    rating: None,
+                    rating_change: None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        standings.sort_by(|a, b| {
+            b.is_winner.cmp(&a.is_winner)
+                .then_with(|| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal))
+        });
+
+        standings
+    }
+
+    fn update_squads(&mut self, dt: f64) {
+        let waypoints = &self.waypoints;
+
+        for squad in self.squads.values_mut() {
+            match squad.state() {
+                SquadState::InSpace => { },
+
+                SquadState::Moving { destination } => {
+                    let Position(x, y) = squad.position();
+                    let Position(destination_x, destination_y) = destination;
+
+                    let target = (destination_x - x, destination_y - y);
+                    let distance = (target.0.powi(2) + target.1.powi(2)).sqrt();
+
+                    let speed_modifier = self.players.get(&squad.owner())
+                        .map_or(1_f64, |player| player.research().modifiers().speed);
+
+                    let max_step_distance = self.rules.class(squad.class()).speed * speed_modifier * dt;
+
+                    if distance < max_step_distance {
+                        squad.set_position(destination);
+
+                        let state = Self::find_waypoint_by_position(waypoints, destination, self.rules.orbit_distance)
+                            .map_or(SquadState::InSpace, |waypoint| SquadState::OnOrbit { waypoint_id: waypoint.id() });
+
+                        squad.set_state(state);
+
+                        let next_destination = squad.next_route_waypoint()
+                            .and_then(|waypoint_id| waypoints.get(&waypoint_id))
+                            .map(|waypoint| waypoint.position());
+
+                        if let Some(next_destination) = next_destination {
+                            squad.move_to(next_destination);
+                        }
+                    } else {
+                        let direction = (target.0 / distance, target.1 / distance);
+                        let position = Position(
+                            x + max_step_distance * direction.0,
+                            y + max_step_distance * direction.1
+                        );
+
+                        squad.set_position(position);
+                    }
+                },
+
+                SquadState::OnOrbit { .. } => { }
+            }
+        }
+    }
+
+    /// Detaches half of the squad's ships into a new squad and returns its id, a single ship
+    /// cannot be split
+    fn split_squad(&mut self, player_id: PlayerId, squad_id: Id) -> Option<Id> {
+        let split_squad_id = random::<Id>();
+
+        let split_squad = match self.squads.get_mut(&squad_id) {
+            Some(squad) => {
+                let split_life = (squad.life() / 2_f64).floor();
+                if squad.owner() != player_id || split_life < 1_f64 {
+                    return None;
+                }
+
+                let life = squad.life();
+                squad.set_life(life - split_life);
+
+                let mut split_squad = Squad::new(split_squad_id, player_id, squad.class(), squad.position(), split_life);
+                split_squad.set_state(squad.state());
+                split_squad
+            },
+
+            None => return None
+        };
+
+        self.squads.insert(split_squad_id, split_squad);
+
+        Some(split_squad_id)
+    }
+
+    fn move_squad(&mut self, player_id: PlayerId, squad_id: Id, waypoint_id: Id) {
+        let is_owner = self.squads.get(&squad_id)
+            .map_or(false, |squad| squad.owner() == player_id);
+
+        if !is_owner || !self.waypoints.contains_key(&waypoint_id) {
+            return;
+        }
+
+        let route = match self.hyperlanes {
+            Some(ref hyperlanes) => {
+                match self.find_route(hyperlanes, player_id, squad_id, waypoint_id) {
+                    Some(route) => route,
+                    None => return
+                }
+            },
+
+            None => vec![waypoint_id].into_iter().collect::<VecDeque<_>>()
+        };
+
+        let waypoints = &self.waypoints;
+
+        if let Some(squad) = self.squads.get_mut(&squad_id) {
+            let is_moving_by_lane = self.hyperlanes.is_some() && !squad.is_standing();
+
+            squad.set_route(route);
+
+            // A squad already flying along a lane finishes it before following the new route
+            if !is_moving_by_lane {
+                let destination = squad.next_route_waypoint()
+                    .and_then(|waypoint_id| waypoints.get(&waypoint_id))
+                    .map(|waypoint| waypoint.position());
+
+                if let Some(destination) = destination {
+                    squad.move_to(destination);
+                }
+            }
+        }
+    }
+
+    /// A squad left in space off the lanes first flies straight to the nearest waypoint and
+    /// follows the lanes from there
+    fn find_route(&self, hyperlanes: &HyperlaneGraph, player_id: PlayerId, squad_id: Id, waypoint_id: Id) -> Option<VecDeque<Id>> {
+        let origin = self.squads.get(&squad_id)
+            .and_then(|squad| {
+                match squad.state() {
+                    SquadState::OnOrbit { waypoint_id: orbit_waypoint_id } => Some((orbit_waypoint_id, false)),
+
+                    SquadState::Moving { destination } => {
+                        Self::find_waypoint_by_position(&self.waypoints, destination, self.rules.orbit_distance)
+                            .map(|waypoint| (waypoint.id(), false))
+                    },
+
+                    SquadState::InSpace => {
+                        Self::find_nearest_waypoint(&self.waypoints, squad.position())
+                            .map(|waypoint| (waypoint.id(), true))
+                    }
+                }
+            });
+
+        let (origin, is_entering_lanes) = match origin {
+            Some(origin) => origin,
+            None => return None
+        };
+
+        let waypoints = &self.waypoints;
+        let settings = &self.settings;
+        let players = &self.players;
+        let diplomacy = &self.diplomacy;
+
+        hyperlanes.find_path(origin, waypoint_id, |from, to| {
+            let from = &waypoints[&from];
+            let to = &waypoints[&to];
+
+            let mut cost = from.position().distance_to(to.position());
+
+            if to.waypoint_type() == WaypointType::BlackHole {
+                cost += settings.black_hole_lane_cost;
+            }
+
+            if to.owner().map_or(false, |owner| !Self::are_allies(players, diplomacy, owner, player_id)) {
+                cost += settings.hostile_lane_cost;
+            }
+
+            cost
+        }).map(|mut route| {
+            if is_entering_lanes {
+                route.push_front(origin);
+            }
+
+            route
+        })
+    }
+
+    /// Capture progresses with the total life of attacking squads on orbit, reduced by the life of
+    /// defending squads of the owner and its allies. It is paused while hostile attackers contest
+    /// the waypoint, and reversed by defenders or slowly decays when the orbit is empty.
+    fn update_waypoints(&mut self, dt: f64) {
+        let players = &self.players;
+        let rules = &self.rules;
+        let diplomacy = &self.diplomacy;
+
+        for waypoint in self.waypoints.values_mut() {
+            let capture_threshold = match rules.capture_threshold(waypoint.waypoint_type()) {
+                Some(capture_threshold) => capture_threshold,
+                None => continue
+            };
+
+            let mut strengths: HashMap<PlayerId, f64> = HashMap::new();
+            for squad in self.squads.values().filter(|squad| squad.is_on_orbit(waypoint.id())) {
+                *strengths.entry(squad.owner()).or_insert(0_f64) += squad.life();
+            }
+
+            for (player_id, strength) in strengths.iter_mut() {
+                *strength *= players.get(player_id).map_or(1_f64, |player| player.research().modifiers().capture);
+            }
+
+            let owner = waypoint.owner();
+
+            let (defenders, attackers): (Vec<_>, Vec<_>) = strengths
+                .into_iter()
+                .partition(|&(player_id, _)| owner.map_or(false, |owner| Self::are_allies(players, diplomacy, owner, player_id)));
+
+            let is_contested = attackers
+                .iter()
+                .any(|&(a, _)| attackers.iter().any(|&(b, _)| !Self::are_allies(players, diplomacy, a, b)));
+
+            if is_contested {
+                continue;
+            }
+
+            let defense = defenders.iter().fold(0_f64, |defense, &(_, strength)| defense + strength);
+
+            let attack = attackers.iter().fold(0_f64, |attack, &(_, strength)| attack + strength);
+            let main_attacker = attackers
+                .iter()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+                .map(|&(player_id, _)| player_id);
+
+            let capturer = waypoint.capturer();
+            let progress = waypoint.capture_progress();
+
+            let (capturer, progress) = match main_attacker {
+                Some(attacker) => {
+                    let rate = (attack - defense) * rules.capture_rate * dt;
+                    if rate <= 0_f64 {
+                        continue;
+                    }
+
+                    match capturer {
+                        Some(capturer) if !Self::are_allies(players, diplomacy, capturer, attacker) => (Some(capturer), progress - rate),
+                        Some(capturer) => (Some(capturer), progress + rate),
+                        None => (Some(attacker), progress + rate)
+                    }
+                },
+
+                None => {
+                    let rate = if defense > 0_f64 { defense * rules.capture_rate } else { rules.capture_decay };
+                    (capturer, progress - rate * dt)
+                }
+            };
+
+            match capturer {
+                Some(capturer) if progress >= capture_threshold => {
+                    self.events.push(Event::WaypointCaptured {
+                        waypoint_id: waypoint.id(),
+                        owner: capturer,
+                        previous_owner: owner
+                    });
+
+                    waypoint.set_owner(Some(capturer));
+                    waypoint.set_capture(None, 0_f64);
+                },
+
+                Some(capturer) if progress > 0_f64 => waypoint.set_capture(Some(capturer), progress),
+
+                _ => waypoint.set_capture(None, 0_f64)
+            }
+        }
+    }
+
+    fn merge_squads(&mut self) {
+        let merged_squads = self.get_merged_squads();
+
+        for (squad_id, life, merged_squad_ids) in merged_squads {
+            self.squads.get_mut(&squad_id)
+                .map(|squad| squad.set_life(life));
+
+            for merged_squad_id in &merged_squad_ids {
+                self.squads.remove(merged_squad_id);
+            }
+
+            self.events.push(Event::SquadsMerged { squad_id, merged_squad_ids });
+        }
+    }
+
+    /// Returns every squad absorbing others nearby with its new life and ids of the absorbed ones
+    fn get_merged_squads(&mut self) -> Vec<(Id, f64, Vec<Id>)> {
+        let mut merged_squads = vec![];
+        let mut merged_squad_ids = HashSet::new();
+
+        let squads = self.squads
+            .values()
+            .filter(|squad| squad.is_standing())
+            .collect::<Vec<_>>();
+
+        for squad in &squads {
+            if merged_squad_ids.contains(&squad.id()) {
+                continue;
+            }
+
+            let other_squads = squads
+                .iter()
+                .filter(|other_squad| {
+                    other_squad.id() != squad.id() &&
+                        !merged_squad_ids.contains(&other_squad.id()) &&
+                        other_squad.owner() == squad.owner() &&
+                        other_squad.class() == squad.class() &&
+                        other_squad.position().distance_to(squad.position()) < self.rules.merge_distance
+                })
+                .collect::<Vec<_>>();
+
+            if other_squads.is_empty() {
+                continue;
+            }
+
+            let other_squads_life = other_squads
+                .iter()
+                .fold(0_f64, |life, squad| life + squad.life());
+
+            let other_squad_ids = other_squads
+                .iter()
+                .map(|other_squad| other_squad.id())
+                .collect::<Vec<_>>();
+
+            merged_squad_ids.insert(squad.id());
+            merged_squad_ids.extend(other_squad_ids.iter().cloned());
+
+            merged_squads.push((squad.id(), squad.life() + other_squads_life, other_squad_ids));
+        }
+
+        merged_squads
+    }
+
+    fn update_fight(&mut self, dt: f64) {
+        let modifiers = self.players
+            .values()
+            .map(|player| (player.id(), player.research().modifiers()))
+            .collect::<HashMap<_, _>>();
+
+        let hits = {
+            let players = &self.players;
+            let diplomacy = &self.diplomacy;
+
+            combat::get_squads_hits(&self.squads, &self.waypoints, &self.rules, &modifiers, |a, b| Self::are_hostile(players, diplomacy, a, b))
+        };
+
+        for (squad_id, hit) in hits {
+            let squad_life = self.squads.get(&squad_id)
+                .map(|squad| squad.life());
+
+            if let Some(mut squad_life) = squad_life {
+                squad_life -= hit.damage.min(squad_life.ceil()) * dt;
+                if squad_life < 0_f64 {
+                    self.remove_squad(squad_id, hit.main_attacker());
+                } else {
+                    self.squads.get_mut(&squad_id)
+                        .map(|squad| squad.set_life(squad_life));
+                }
+            }
+        }
     }
 
     fn render(&mut self) {
-        let waypoints_json = json::format_waypoints(&self.waypoints);
+        self.tick += 1;
+
+        // Clients interpolate squads between snapshots using the tick and the server clock
+        let tick_json = json::format_tick(self.tick, time::precise_time_s());
+
+        let waypoints_json = json::format_waypoints(&self.waypoints, &self.rules);
+        let lanes_json = json::format_lanes(&self.hyperlanes);
         let players_json = json::format_players(&self.players);
-        let squads_json = json::format_squads(&self.squads);
+        let match_json = json::format_match(&self.settings.victory, self.match_time, self.is_paused, self.speed);
+        let standings_json = json::format_standings(&self.standings, &self.players);
+        let relations_json = json::format_relations(&self.diplomacy);
 
         for player in self.players.values() {
-            let process_command_json = json::format_process_command(
-                player,
-                &waypoints_json,
-                &players_json,
-                &squads_json
-            );
+            let chat_messages = self.chat_messages
+                .iter()
+                .filter(|message| self.is_chat_recipient(message, player.id()))
+                .collect::<Vec<_>>();
+
+            let pings = self.pings
+                .iter()
+                .filter(|ping| self.is_ping_recipient(ping, player.id()))
+                .collect::<Vec<_>>();
+
+            let (squads, events) = if self.settings.fog_of_war {
+                let observers = self.get_observers(player.id());
+                let squads = self.get_visible_squads(player.id(), &observers);
+                let events = self.get_visible_events(player.id(), &observers, &squads);
+
+                (squads, events)
+            } else {
+                // Research of other players stays hidden even without fog of war
+                let events = self.events
+                    .iter()
+                    .filter(|event| {
+                        match **event {
+                            Event::ResearchCompleted { player_id, .. } => player_id == player.id(),
+                            _ => true
+                        }
+                    })
+                    .collect();
+
+                (self.squads.values().collect(), events)
+            };
+
+            let squads_json = json::format_squads(&squads, |owner| {
+                Self::are_allies(&self.players, &self.diplomacy, owner, player.id())
+            });
+
+            let snapshot = json::Snapshot {
+                tick_json: &tick_json,
+                waypoints_json: &waypoints_json,
+                lanes_json: &lanes_json,
+                players_json: &players_json,
+                squads_json: &squads_json,
+                events_json: &json::format_events(&events),
+                match_json: &match_json,
+                standings_json: &standings_json,
+                relations_json: &relations_json,
+                proposals_json: &json::format_proposals(&self.diplomacy, player.id()),
+                chat_json: &json::format_chat(&chat_messages),
+                pings_json: &json::format_pings(&pings)
+            };
+
+            let process_command_json = json::format_process_command(player, &self.rules, &snapshot);
 
             player.send(process_command_json);
         }
+
+        self.update_match_stats();
+
+        self.events.clear();
+        self.counted_events_count = 0;
+        self.chat_messages.clear();
+        self.pings.clear();
+    }
+
+    fn create_map(settings: &Settings) -> (HashMap<Id, Waypoint>, Option<HyperlaneGraph>) {
+        let (waypoints, hyperlanes) = match settings.map_path {
+            Some(ref map_path) => {
+                let map = map::load_map(map_path).expect("Unable to load map");
+                (map.waypoints, map.hyperlanes)
+            },
+
+            None => (Self::generate_waypoints(), None)
+        };
+
+        let hyperlanes = match hyperlanes {
+            None if settings.hyperlanes => Some(HyperlaneGraph::generate(&waypoints)),
+            hyperlanes => hyperlanes
+        };
+
+        (waypoints, hyperlanes)
     }
 
     fn generate_waypoints() -> HashMap<Id, Waypoint> {
@@ -391,52 +1607,334 @@ impl Server {
         let content_short_side = (short_side - grid_step) * 2;
         let max_planets_count = content_short_side / grid_step;
 
-        let mut grid_x_coordinates = vec![];
-        let mut grid_y_coordinates = vec![];
+        let mut grid_x_coordinates = vec![];
+        let mut grid_y_coordinates = vec![];
+
+        for x in (grid_x_start / grid_step)..(grid_x_end / grid_step) {
+            grid_x_coordinates.push((x * grid_step) as f64);
+        }
+        for y in (grid_y_start / grid_step)..(grid_y_end / grid_step) {
+            grid_y_coordinates.push((y * grid_step) as f64);
+        }
+
+        thread_rng().shuffle(&mut grid_x_coordinates);
+        thread_rng().shuffle(&mut grid_y_coordinates);
+
+        let mut waypoints = HashMap::new();
+        for i in 0..(max_planets_count as f64 * planets_density) as usize {
+            let id = random::<u64>();
+            let position = Position(grid_x_coordinates[i], grid_y_coordinates[i]);
+
+            let waypoint_type = match random::<u64>() % 10 {
+                0...4 => WaypointType::Asteroid,
+                5...7 => WaypointType::Planetoid,
+                8 => WaypointType::Planet,
+                9 => WaypointType::BlackHole,
+                _ => unreachable!()
+            };
+
+            let waypoint = Waypoint::new(id, waypoint_type, position);
+            waypoints.insert(id, waypoint);
+        }
+
+        waypoints
+    }
+
+    /// The capturable waypoint nearest to the map center
+    fn find_hill(waypoints: &HashMap<Id, Waypoint>, rules: &Rules) -> Option<Id> {
+        waypoints
+            .values()
+            .filter(|waypoint| rules.capture_threshold(waypoint.waypoint_type()).is_some())
+            .min_by(|a, b| {
+                let a_distance = a.position().distance_to(Position(0.0, 0.0));
+                let b_distance = b.position().distance_to(Position(0.0, 0.0));
+
+                a_distance.partial_cmp(&b_distance).unwrap_or(Ordering::Equal)
+            })
+            .map(|waypoint| waypoint.id())
+    }
+
+    fn add_chat_message(&mut self, player_id: PlayerId, channel: ChatChannel, text: &str) {
+        let player_name = match self.players.get_mut(&player_id) {
+            Some(player) => {
+                if !player.take_chat_message() {
+                    return;
+                }
+
+                player.name().clone()
+            },
+
+            None => return
+        };
+
+        if let ChatChannel::Private { player_id: recipient_id } = channel {
+            if !self.players.contains_key(&recipient_id) {
+                return;
+            }
+        }
+
+        if let Some(message) = ChatMessage::new(player_id, channel, text) {
+            let recipient = match channel {
+                ChatChannel::Private { player_id: recipient_id } => format!("#{}", recipient_id),
+                _ => channel.name().to_string()
+            };
+
+            // Chat log is kept for moderation
+            println!("Chat {} (#{}) -> {}: {}", player_name, player_id, recipient, message.text);
+
+            self.chat_messages.push(message);
+        }
+    }
+
+    /// Positions and vision ranges of the player's squads and owned waypoints, or of allied ones
+    /// with shared vision
+    fn get_observers(&self, player_id: PlayerId) -> Vec<(Position, f64)> {
+        let squad_observers = self.squads
+            .values()
+            .filter(|squad| self.shares_vision(squad.owner(), player_id))
+            .map(|squad| (squad.position(), self.rules.class(squad.class()).vision_range * self.vision_modifier(squad.owner())));
+
+        let waypoint_observers = self.waypoints
+            .values()
+            .filter(|waypoint| waypoint.owner().map_or(false, |owner| self.shares_vision(owner, player_id)))
+            .map(|waypoint| {
+                let vision_modifier = waypoint.owner().map_or(1_f64, |owner| self.vision_modifier(owner));
+                (waypoint.position(), self.rules.waypoint_vision_range * vision_modifier)
+            });
+
+        squad_observers.chain(waypoint_observers).collect()
+    }
+
+    fn get_visible_squads(&self, player_id: PlayerId, observers: &[(Position, f64)]) -> Vec<&Squad> {
+        self.squads
+            .values()
+            .filter(|squad| self.shares_vision(squad.owner(), player_id) || Self::is_in_vision(observers, squad.position()))
+            .collect()
+    }
+
+    /// Events of hidden squads and research of other players are left out, so that they do not
+    /// reveal enemy activity. Captures, eliminations and the match end are public.
+    fn get_visible_events(&self, player_id: PlayerId, observers: &[(Position, f64)], squads: &[&Squad]) -> Vec<&Event> {
+        self.events
+            .iter()
+            .filter(|event| {
+                match **event {
+                    Event::SquadSpawned { owner, waypoint_id, .. } => {
+                        self.shares_vision(owner, player_id) ||
+                            self.waypoints.get(&waypoint_id).map_or(false, |waypoint| Self::is_in_vision(observers, waypoint.position()))
+                    },
+
+                    Event::SquadDestroyed { owner, killer, position, .. } => {
+                        self.shares_vision(owner, player_id) ||
+                            killer.map_or(false, |killer| self.shares_vision(killer, player_id)) ||
+                            Self::is_in_vision(observers, position)
+                    },
+
+                    Event::SquadsMerged { squad_id, .. } => squads.iter().any(|squad| squad.id() == squad_id),
+
+                    Event::ResearchCompleted { player_id: researcher_id, .. } => researcher_id == player_id,
+
+                    Event::WaypointCaptured { .. } |
+                    Event::PlayerEliminated { .. } |
+                    Event::MatchFinished { .. } => true
+                }
+            })
+            .collect()
+    }
+
+    fn is_in_vision(observers: &[(Position, f64)], position: Position) -> bool {
+        observers.iter().any(|&(observer_position, range)| observer_position.distance_to(position) <= range)
+    }
+
+    fn vision_modifier(&self, player_id: PlayerId) -> f64 {
+        self.players.get(&player_id).map_or(1_f64, |player| player.research().modifiers().vision)
+    }
+
+    fn shares_vision(&self, owner: PlayerId, player_id: PlayerId) -> bool {
+        owner == player_id ||
+            (self.settings.shared_vision && Self::are_allies(&self.players, &self.diplomacy, owner, player_id))
+    }
+
+    fn is_chat_recipient(&self, message: &ChatMessage, player_id: PlayerId) -> bool {
+        match message.channel {
+            ChatChannel::All => true,
+            ChatChannel::Team => Self::are_allies(&self.players, &self.diplomacy, message.from, player_id),
+            ChatChannel::Private { player_id: recipient_id } => player_id == message.from || player_id == recipient_id
+        }
+    }
+
+    /// Pings are meant for allies, enemies never see them
+    fn is_ping_recipient(&self, ping: &Ping, player_id: PlayerId) -> bool {
+        Self::are_allies(&self.players, &self.diplomacy, ping.from, player_id)
+    }
+
+    /// Reconnects a known player by session token, otherwise adds a new player if the token
+    /// grants joining and the name is free. Rejected connections are closed.
+    fn authenticate(&mut self, sender: Sender, token: Option<String>, name: Option<String>, profile: Option<String>) {
+        let connection_id = sender.token().0;
+
+        if self.pending_connections.remove(&connection_id).is_none() {
+            return;
+        }
+
+        let reconnected_player_id = token.as_ref()
+            .and_then(|token| self.sessions.get(&hash_token(&self.session_salt, token)))
+            .cloned();
+
+        if let Some(player_id) = reconnected_player_id {
+            self.connections.retain(|_, connected_player_id| *connected_player_id != player_id);
+            self.connections.insert(connection_id, player_id);
+
+            let is_playing = self.is_playing();
+
+            if let Some(player) = self.players.get_mut(&player_id) {
+                player.set_sender(sender);
+                player.send(json::format_session_command(player));
+
+                if is_playing {
+                    player.send(json::format_rules_command(&self.rules));
+                }
+            }
+
+            return;
+        }
+
+        // The admin secret must not leak through response timing
+        let is_admin = match (self.settings.admin_token.as_ref(), token.as_ref()) {
+            (Some(admin_token), Some(token)) => constant_time_eq(admin_token.as_bytes(), token.as_bytes()),
+            _ => false
+        };
+
+        if self.is_playing() {
+            Self::reject_connection(&sender, "match_in_progress");
+            return;
+        }
+
+        if !is_admin && !self.is_join_token_valid(token.as_ref()) {
+            Self::reject_connection(&sender, "unauthorized");
+            return;
+        }
+
+        let name = match name {
+            Some(name) => {
+                let name = name.trim().to_string();
+
+                if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+                    Self::reject_connection(&sender, "invalid_name");
+                    return;
+                }
+
+                if !self.is_name_available(&name, profile.as_ref()) {
+                    Self::reject_connection(&sender, "name_taken");
+                    return;
+                }
+
+                Some(name)
+            },
+
+            None => None
+        };
 
-        for x in (grid_x_start / grid_step)..(grid_x_end / grid_step) {
-            grid_x_coordinates.push((x * grid_step) as f64);
-        }
-        for y in (grid_y_start / grid_step)..(grid_y_end / grid_step) {
-            grid_y_coordinates.push((y * grid_step) as f64);
+        // Only a named player can own a profile, an empty one proves nothing
+        let profile = match profile {
+            Some(ref profile) if name.is_some() && !profile.is_empty() => Some(profile.clone()),
+            _ => None
+        };
+
+        if !is_admin {
+            self.consume_invite_token(token.as_ref());
         }
 
-        thread_rng().shuffle(&mut grid_x_coordinates);
-        thread_rng().shuffle(&mut grid_y_coordinates);
+        self.add_player(sender, is_admin, name, profile);
+    }
 
-        let mut waypoints = HashMap::new();
-        for i in 0..(max_planets_count as f64 * planets_density) as usize {
-            let id = random::<u64>();
-            let position = Position(grid_x_coordinates[i], grid_y_coordinates[i]);
+    /// Name may not be used by two players in the room, nor taken from its owner in stats
+    fn is_name_available(&self, name: &str, profile: Option<&String>) -> bool {
+        let is_in_room = self.players
+            .values()
+            .any(|player| player.name() == name);
 
-            let waypoint_type = match random::<u64>() % 10 {
-                0...4 => WaypointType::Asteroid,
-                5...7 => WaypointType::Planetoid,
-                8 => WaypointType::Planet,
-                9 => WaypointType::BlackHole,
-                _ => unreachable!()
-            };
+        let is_owned_by_other = match (self.stats.as_ref(), profile) {
+            (Some(stats), Some(profile)) => !stats.is_name_available(name, profile),
+            (Some(stats), None) => stats.is_registered(name),
+            (None, _) => false
+        };
 
-            let waypoint = Waypoint::new(id, waypoint_type, position);
-            waypoints.insert(id, waypoint);
+        !is_in_room && !is_owned_by_other
+    }
+
+    fn is_join_token_valid(&self, token: Option<&String>) -> bool {
+        if self.settings.password.is_none() && self.settings.invite_tokens.is_empty() {
+            return true;
         }
 
-        waypoints
+        match token {
+            Some(token) => {
+                self.is_password(token) ||
+                    self.settings.invite_tokens.iter().any(|invite_token| constant_time_eq(invite_token.as_bytes(), token.as_bytes()))
+            },
+
+            None => false
+        }
     }
 
-    fn add_player(&mut self, sender: Sender) {
-        if self.is_playing() {
-            return;
+    /// Password is reusable, invite tokens are consumed once the join is accepted
+    fn consume_invite_token(&mut self, token: Option<&String>) {
+        if let Some(token) = token {
+            if !self.is_password(token) {
+                self.settings.invite_tokens.retain(|invite_token| !constant_time_eq(invite_token.as_bytes(), token.as_bytes()));
+            }
         }
+    }
 
-        let player_name = format!("Player #{}", self.players.len() + 1);
-        let player = Player::new(sender, player_name);
+    fn is_password(&self, token: &str) -> bool {
+        self.settings.password
+            .as_ref()
+            .map_or(false, |password| constant_time_eq(password.as_bytes(), token.as_bytes()))
+    }
 
-        let ref mut players = self.players;
+    fn reject_connection(sender: &Sender, error: &'static str) {
+        println!("Rejecting connection {:?}: {}", sender.token(), error);
+
+        sender.send(json::format_error_command(error));
+        sender.close_with_reason(CloseCode::Policy, error);
+    }
 
-        let player_id = player.id();
+    fn add_player(&mut self, sender: Sender, is_admin: bool, name: Option<String>, profile: Option<String>) {
+        let player_id = sender.token().0 as PlayerId;
+        let session_token = format!("{:016x}{:016x}", random::<u64>(), random::<u64>());
+
+        let player_name = name.unwrap_or_else(|| format!("Player #{}", self.players.len() + 1));
+        let mut player = Player::new(player_id, sender, session_token, is_admin, player_name, self.rules.starting_gold);
+        player.set_profile(profile);
+        player.set_color(self.find_free_color());
+
+        player.send(json::format_session_command(&player));
+
+        let session_hash = hash_token(&self.session_salt, player.session_token());
+        self.sessions.insert(session_hash, player_id);
+
+        self.connections.insert(player_id, player_id);
+
+        if self.host_id.is_none() {
+            self.host_id = Some(player_id);
+        }
+
+        let ref mut players = self.players;
         players.insert(player_id, player);
 
+        self.assign_planet(player_id);
+    }
+
+    /// First color not taken by another player, colors repeat once all of them are taken
+    fn find_free_color(&self) -> ColorId {
+        (0..PLAYER_COLORS_COUNT)
+            .find(|&color| !self.players.values().any(|player| player.color() == color))
+            .unwrap_or(self.players.len() as ColorId % PLAYER_COLORS_COUNT)
+    }
+
+    /// Gives the player a free planet to start from
+    fn assign_planet(&mut self, player_id: PlayerId) {
         let planet = self.waypoints
             .values_mut()
             .filter(|waypoint| waypoint.waypoint_type() == WaypointType::Planet)
@@ -447,14 +1945,642 @@ impl Server {
         }
     }
 
-    fn find_waypoint_by_position(waypoints: &HashMap<Id, Waypoint>, position: Position) -> Option<&Waypoint> {
+    /// Removes the player from the room along with the planet, the session and the relations
+    fn remove_player(&mut self, player_id: PlayerId) {
+        self.remove_player_assets(player_id);
+
+        self.players.remove(&player_id);
+        self.diplomacy.remove_player(player_id);
+        self.connections.retain(|_, connected_player_id| *connected_player_id != player_id);
+        self.sessions.retain(|_, session_player_id| *session_player_id != player_id);
+        self.pause_votes.remove(&player_id);
+
+        if self.host_id == Some(player_id) {
+            self.host_id = self.players.keys().next().cloned();
+        }
+    }
+
+    fn are_allies(players: &HashMap<PlayerId, Player>, diplomacy: &Diplomacy, a: PlayerId, b: PlayerId) -> bool {
+        if a == b {
+            return true;
+        }
+
+        let a_team = players.get(&a).and_then(|player| player.team());
+        let b_team = players.get(&b).and_then(|player| player.team());
+
+        let is_same_team = match (a_team, b_team) {
+            (Some(a_team), Some(b_team)) => a_team == b_team,
+            _ => false
+        };
+
+        is_same_team || diplomacy.relation(a, b) == Some(Relation::Alliance)
+    }
+
+    fn are_hostile(players: &HashMap<PlayerId, Player>, diplomacy: &Diplomacy, a: PlayerId, b: PlayerId) -> bool {
+        !Self::are_allies(players, diplomacy, a, b) && diplomacy.relation(a, b) != Some(Relation::Ceasefire)
+    }
+
+    fn find_waypoint_by_position(waypoints: &HashMap<Id, Waypoint>, position: Position, orbit_distance: f64) -> Option<&Waypoint> {
         let Position(x, y) = position;
 
         waypoints
             .values()
             .find(|waypoint| {
                 let Position(waypoint_x, waypoint_y) = waypoint.position();
-                ((waypoint_x - x).powi(2) + (waypoint_y - y).powi(2)).sqrt() < 10_f64
+                ((waypoint_x - x).powi(2) + (waypoint_y - y).powi(2)).sqrt() < orbit_distance
+            })
+    }
+
+    fn find_nearest_waypoint(waypoints: &HashMap<Id, Waypoint>, position: Position) -> Option<&Waypoint> {
+        waypoints
+            .values()
+            .min_by(|a, b| {
+                let a_distance = a.position().distance_to(position);
+                let b_distance = b.position().distance_to(position);
+
+                a_distance.partial_cmp(&b_distance).unwrap_or(Ordering::Equal)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ws::{Message, Result, Sender, WebSocket};
+
+    use std::collections::HashMap;
+    use std::sync::mpsc::channel;
+
+    use common::{Id, PlayerId, Position, SquadClass};
+    use server::chat::{ChatChannel, Ping, MESSAGES_BURST, PINGS_BURST};
+    use server::combat;
+    use server::command::Command;
+    use server::diplomacy::Relation;
+    use server::event::Event;
+    use server::research::Tech;
+    use server::player::Player;
+    use server::settings::{Settings, VictoryCondition};
+    use server::squad::Squad;
+    use server::waypoint::{Waypoint, WaypointType};
+    use super::{Server, ServerState};
+
+    fn create_server() -> Server {
+        // A random map may have no planet to conquer, no score victory happens in a tick
+        let mut settings = Settings::new();
+        settings.victory = VictoryCondition::Score { time_limit: 600.0 };
+
+        Server::new(settings)
+    }
+
+    fn create_sender() -> Sender {
+        // Messages to players go nowhere, the socket is never run
+        let websocket = WebSocket::new(|_: Sender| |_: Message| -> Result<()> { Ok(()) }).unwrap();
+        websocket.broadcaster()
+    }
+
+    /// Every test sender has the same token, further players are put into the room directly
+    fn add_other_player(server: &mut Server, sender: &Sender, player_id: usize) {
+        let player = Player::new(player_id, sender.clone(), String::new(), false, format!("Player #{}", player_id), 0.0);
+
+        server.players.insert(player_id, player);
+        server.connections.insert(player_id, player_id);
+        server.assign_planet(player_id);
+    }
+
+    #[test]
+    fn admin_end_returns_to_lobby() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        server.process_player_command(Command::Ready { sender: sender.clone() });
+        server.update(0.1);
+
+        assert!(server.is_playing());
+
+        assert_eq!(server.execute_admin_line("end", true), "Match finished");
+        assert!(server.players.values().all(|player| player.is_loose()));
+
+        server.process_player_command(Command::Lobby { sender: sender.clone() });
+        assert!(server.players.values().all(|player| player.is_pending()));
+
+        server.update(0.1);
+
+        match server.state {
+            ServerState::Waiting => { },
+            _ => panic!("match has not been reset")
+        }
+
+        assert_eq!(server.players.len(), 1);
+        assert!(server.squads.is_empty());
+        assert!(server.standings.is_none());
+    }
+
+    #[test]
+    fn simultaneous_elimination_is_draw() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        add_other_player(&mut server, &sender, 1);
+
+        // A random map may have too few planets for everyone
+        let player_ids = server.players.keys().cloned().collect::<Vec<_>>();
+        server.waypoints.clear();
+
+        for (i, &player_id) in player_ids.iter().enumerate() {
+            let mut planet = Waypoint::new(i as Id, WaypointType::Planet, Position(i as f64 * 100.0, 0.0));
+            planet.set_owner(Some(player_id));
+            server.waypoints.insert(i as Id, planet);
+        }
+
+        for player in server.players.values_mut() {
+            player.set_ready_state();
+        }
+
+        server.update(0.1);
+        assert!(server.is_playing());
+
+        // Both players lose their planets during the same tick
+        for waypoint in server.waypoints.values_mut() {
+            waypoint.set_owner(None);
+        }
+
+        server.update(0.1);
+        assert!(server.players.values().all(|player| player.is_loose()));
+
+        server.update(0.1);
+
+        match server.state {
+            ServerState::Finished => { },
+            _ => panic!("match has not been finished")
+        }
+
+        assert!(server.standings.is_some());
+    }
+
+    #[test]
+    fn admin_start_forces_players_into_match() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        add_other_player(&mut server, &sender, 1);
+
+        server.process_player_command(Command::Ready { sender: sender.clone() });
+
+        assert_eq!(server.execute_admin_line("start", true), "Match started");
+        assert!(server.is_playing());
+        assert!(server.players.values().all(|player| player.is_playing()));
+
+        assert_eq!(server.execute_admin_line("start", true), "Match can be started only while waiting for players");
+    }
+
+    #[test]
+    fn kicked_host_is_replaced() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        add_other_player(&mut server, &sender, 1);
+
+        let host_id = server.host_id.unwrap();
+        assert!(host_id != 1);
+
+        assert_eq!(server.execute_admin_line(&format!("kick {}", host_id), true), format!("Kicked Player #1 (#{})", host_id));
+        assert_eq!(server.host_id, Some(1));
+        assert!(!server.players.contains_key(&host_id));
+        assert!(!server.connections.values().any(|&player_id| player_id == host_id));
+    }
+
+    #[test]
+    fn splitting_keeps_supply_used() {
+        let mut server = create_server();
+        let squad_id = 1 as Id;
+
+        server.squads.insert(squad_id, Squad::new(squad_id, 0, SquadClass::Fighter, Position(0.0, 0.0), 70.0));
+        let supply_used = Server::get_supply_used(&server.squads, &server.rules, 0);
+        assert_eq!(supply_used, 7.0);
+
+        // Halves are rounded down, so most squads end up with fractional ships counts
+        for _ in 0..3 {
+            let squad_ids = server.squads.keys().cloned().collect::<Vec<_>>();
+            for squad_id in squad_ids {
+                server.split_squad(0, squad_id);
+            }
+        }
+
+        assert!(server.squads.len() > 1);
+        assert_eq!(Server::get_supply_used(&server.squads, &server.rules, 0), supply_used);
+    }
+
+    #[test]
+    fn allies_and_ceasefire_are_skipped() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        for player_id in 0..5 {
+            add_other_player(&mut server, &sender, player_id);
+        }
+
+        server.diplomacy.propose(0, 1, Relation::Alliance);
+        assert!(server.diplomacy.accept(1, 0, Relation::Alliance));
+        server.diplomacy.propose(0, 2, Relation::Ceasefire);
+        assert!(server.diplomacy.accept(2, 0, Relation::Ceasefire));
+        server.diplomacy.propose(1, 2, Relation::Ceasefire);
+        assert!(server.diplomacy.accept(2, 1, Relation::Ceasefire));
+
+        // Teammates never fight, with no relation between them
+        for &player_id in &[3, 4] {
+            server.players.get_mut(&player_id).unwrap().set_team(Some(1));
+        }
+
+        let mut squads = HashMap::new();
+        for &(squad_id, owner, x, y) in &[(1, 0, 0.0, 0.0), (2, 1, 3.0, 0.0), (3, 2, 0.0, 3.0), (4, 3, 50.0, 0.0), (5, 4, 53.0, 0.0)] {
+            squads.insert(squad_id, Squad::new(squad_id, owner, SquadClass::Fighter, Position(x, y), 5.0));
+        }
+
+        let hits = {
+            let players = &server.players;
+            let diplomacy = &server.diplomacy;
+            combat::get_squads_hits(&squads, &server.waypoints, &server.rules, &HashMap::new(), |a, b| Server::are_hostile(players, diplomacy, a, b))
+        };
+
+        assert!(hits.is_empty());
+
+        // Every squad in range fires at the hostile newcomer, which picks the weakest of them
+        squads.insert(6, Squad::new(6, 4, SquadClass::Fighter, Position(3.0, 3.0), 5.0));
+        squads.get_mut(&2).unwrap().set_life(2.0);
+
+        let hits = {
+            let players = &server.players;
+            let diplomacy = &server.diplomacy;
+            combat::get_squads_hits(&squads, &server.waypoints, &server.rules, &HashMap::new(), |a, b| Server::are_hostile(players, diplomacy, a, b))
+        };
+
+        assert_eq!(hits.len(), 2);
+        assert!((hits[&6].damage - 12.0).abs() < 1e-9);
+        assert!((hits[&2].damage - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn research_is_started_in_match_and_reported_to_its_player() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        let player_id = *server.players.keys().next().unwrap();
+        add_other_player(&mut server, &sender, player_id + 1);
+
+        // A random map may have too few planets for both, ending the match at once
+        server.waypoints.clear();
+
+        for (i, &owner) in [player_id, player_id + 1].iter().enumerate() {
+            let mut planet = Waypoint::new(i as Id, WaypointType::Planet, Position(i as f64 * 100.0, 0.0));
+            planet.set_owner(Some(owner));
+            server.waypoints.insert(i as Id, planet);
+        }
+
+        let starting_gold = server.players[&player_id].gold();
+        assert!(starting_gold >= server.rules.tech(Tech::Mining).cost);
+
+        server.process_player_command(Command::Research { sender: sender.clone(), tech: Tech::Mining });
+        assert_eq!(server.players[&player_id].gold(), starting_gold);
+        assert!(server.players[&player_id].research().can_start(Tech::Mining));
+
+        for player in server.players.values_mut() {
+            player.set_ready_state();
+        }
+
+        server.update(0.1);
+        assert!(server.is_playing());
+
+        server.process_player_command(Command::Research { sender: sender.clone(), tech: Tech::Mining });
+        assert!(!server.players[&player_id].research().can_start(Tech::Mining));
+
+        let research_time = server.rules.tech(Tech::Mining).time;
+        server.update(research_time);
+
+        let is_research_event = |event: &&Event| {
+            match **event {
+                Event::ResearchCompleted { player_id: researcher_id, tech: Tech::Mining } => researcher_id == player_id,
+                _ => false
+            }
+        };
+
+        assert!(server.get_visible_events(player_id, &[], &[]).iter().any(&is_research_event));
+        assert!(!server.get_visible_events(player_id + 1, &[], &[]).iter().any(&is_research_event));
+    }
+
+    /// Players 1 and 2 are teammates, 3 is allied with 1 and 4 is an enemy of everyone
+    fn create_chat_server() -> Server {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        for player_id in 1..5 {
+            add_other_player(&mut server, &sender, player_id);
+        }
+
+        for &player_id in &[1, 2] {
+            server.players.get_mut(&player_id).unwrap().set_team(Some(1));
+        }
+
+        server.diplomacy.propose(1, 3, Relation::Alliance);
+        assert!(server.diplomacy.accept(3, 1, Relation::Alliance));
+
+        server
+    }
+
+    fn get_chat_recipients(server: &Server) -> Vec<Vec<PlayerId>> {
+        server.chat_messages
+            .iter()
+            .map(|message| {
+                let mut recipients = server.players
+                    .keys()
+                    .cloned()
+                    .filter(|&player_id| server.is_chat_recipient(message, player_id))
+                    .collect::<Vec<_>>();
+
+                recipients.sort();
+                recipients
             })
+            .collect()
+    }
+
+    #[test]
+    fn chat_recipients() {
+        let mut server = create_chat_server();
+
+        server.add_chat_message(1, ChatChannel::All, "hi all");
+        server.add_chat_message(1, ChatChannel::Team, "hi team");
+        server.add_chat_message(2, ChatChannel::Team, "hi team from 2");
+        server.add_chat_message(1, ChatChannel::Private { player_id: 4 }, "hi 4");
+
+        assert_eq!(get_chat_recipients(&server), vec![
+            vec![1, 2, 3, 4],
+            vec![1, 2, 3],
+            vec![1, 2],
+            vec![1, 4]
+        ]);
+
+        // Blank messages and messages to players not in the room are dropped
+        server.add_chat_message(1, ChatChannel::All, "   ");
+        server.add_chat_message(1, ChatChannel::Private { player_id: 9 }, "hi 9");
+        assert_eq!(server.chat_messages.len(), 4);
+    }
+
+    #[test]
+    fn ping_recipients() {
+        let mut server = create_chat_server();
+
+        for &(from, expected_recipients) in &[(1, &[1, 2, 3][..]), (3, &[1, 3][..]), (4, &[4][..])] {
+            let ping = Ping { from, position: Position(0.0, 0.0) };
+
+            let mut recipients = server.players
+                .keys()
+                .cloned()
+                .filter(|&player_id| server.is_ping_recipient(&ping, player_id))
+                .collect::<Vec<_>>();
+
+            recipients.sort();
+            assert_eq!(recipients, expected_recipients);
+        }
+
+        // Only a burst of pings is taken at once
+        let sender = create_sender();
+        server.add_player(sender.clone(), false, None, None);
+
+        for _ in 0..10 {
+            server.process_player_command(Command::Ping { sender: sender.clone(), position: Position(0.0, 0.0) });
+        }
+
+        assert_eq!(server.pings.len(), PINGS_BURST as usize);
+    }
+
+    #[test]
+    fn chat_burst() {
+        let mut server = create_chat_server();
+
+        for i in 0..10 {
+            server.add_chat_message(1, ChatChannel::All, &format!("spam {}", i));
+        }
+
+        // Another player is limited on their own
+        server.add_chat_message(2, ChatChannel::All, "not spam");
+
+        assert_eq!(server.chat_messages.len(), MESSAGES_BURST as usize + 1);
+        assert_eq!(server.chat_messages.last().map(|message| message.from), Some(2));
+    }
+
+    /// Runs a fresh connection through connect and hello as the server loop does
+    fn say_hello(server: &mut Server, sender: &Sender, token: Option<&str>) {
+        let (tx, rx) = channel();
+
+        tx.send(Command::Connect { sender: sender.clone() }).unwrap();
+        tx.send(Command::Hello { sender: sender.clone(), token: token.map(|token| token.to_string()), name: None, profile: None }).unwrap();
+
+        server.process(&rx);
+    }
+
+    #[test]
+    fn hello_requires_password() {
+        let mut settings = Settings::new();
+        settings.password = Some("secret".to_string());
+
+        let mut server = Server::new(settings);
+        let sender = create_sender();
+
+        say_hello(&mut server, &sender, None);
+        say_hello(&mut server, &sender, Some("secrets"));
+        say_hello(&mut server, &sender, Some(""));
+
+        assert!(server.players.is_empty());
+        assert!(server.connections.is_empty());
+
+        say_hello(&mut server, &sender, Some("secret"));
+        assert_eq!(server.players.len(), 1);
+    }
+
+    #[test]
+    fn invite_token_is_used_once() {
+        let mut settings = Settings::new();
+        settings.invite_tokens = vec!["first".to_string(), "second".to_string()];
+
+        let mut server = Server::new(settings);
+        let sender = create_sender();
+        let player_id = sender.token().0 as PlayerId;
+
+        say_hello(&mut server, &sender, Some("first"));
+        assert_eq!(server.players.len(), 1);
+        assert_eq!(server.settings.invite_tokens, vec!["second".to_string()]);
+
+        // Every test sender has the same token, so the player leaves before the next hello
+        server.remove_player(player_id);
+
+        say_hello(&mut server, &sender, Some("first"));
+        assert!(server.players.is_empty());
+
+        say_hello(&mut server, &sender, Some("second"));
+        assert_eq!(server.players.len(), 1);
+        assert!(server.settings.invite_tokens.is_empty());
+    }
+
+    #[test]
+    fn session_token_reconnects_player() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        let player_id = sender.token().0 as PlayerId;
+        let other_player_id = player_id + 1;
+
+        say_hello(&mut server, &sender, None);
+        add_other_player(&mut server, &sender, other_player_id);
+
+        // A random map may have too few planets for both, ending the match at once
+        server.waypoints.clear();
+
+        for (i, &owner) in [player_id, other_player_id].iter().enumerate() {
+            let mut planet = Waypoint::new(i as Id, WaypointType::Planet, Position(i as f64 * 100.0, 0.0));
+            planet.set_owner(Some(owner));
+            server.waypoints.insert(i as Id, planet);
+        }
+
+        for player in server.players.values_mut() {
+            player.set_ready_state();
+        }
+
+        server.update(0.1);
+        assert!(server.is_playing());
+
+        let session_token = server.players[&player_id].session_token().clone();
+        server.players.get_mut(&player_id).unwrap().set_gold(123.0);
+
+        let (tx, rx) = channel();
+        tx.send(Command::Disconnect { sender: sender.clone() }).unwrap();
+        server.process(&rx);
+
+        assert!(!server.connections.contains_key(&sender.token().0));
+
+        // New players are refused during the match, a wrong session token is one of them
+        say_hello(&mut server, &sender, Some("not a session"));
+        assert!(!server.connections.contains_key(&sender.token().0));
+
+        say_hello(&mut server, &sender, Some(&session_token));
+
+        assert_eq!(server.connections.get(&sender.token().0), Some(&player_id));
+        assert_eq!(server.players.len(), 2);
+        assert_eq!(server.players[&player_id].session_token(), &session_token);
+        assert_eq!(server.players[&player_id].gold(), 123.0);
+    }
+
+    /// Gives every player a planet of their own, so that nobody is eliminated at once, and starts the match
+    fn start_match(server: &mut Server) {
+        let player_ids = server.players.keys().cloned().collect::<Vec<_>>();
+        server.waypoints.clear();
+
+        for (i, &player_id) in player_ids.iter().enumerate() {
+            let mut planet = Waypoint::new(i as Id, WaypointType::Planet, Position(i as f64 * 100.0, 0.0));
+            planet.set_owner(Some(player_id));
+            server.waypoints.insert(i as Id, planet);
+        }
+
+        for player in server.players.values_mut() {
+            player.set_ready_state();
+        }
+
+        server.update(0.1);
+        assert!(server.is_playing());
+    }
+
+    /// Every test sender has the same token, so it is bound to the player before each command
+    fn vote_pause(server: &mut Server, sender: &Sender, player_id: PlayerId, paused: bool) {
+        server.connections.insert(sender.token().0, player_id);
+        server.process_player_command(Command::Pause { sender: sender.clone(), paused });
+    }
+
+    #[test]
+    fn pause_vote_majority() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        for player_id in 1..6 {
+            add_other_player(&mut server, &sender, player_id);
+        }
+
+        start_match(&mut server);
+
+        // Eliminated players do not vote, so two of the remaining three are a majority
+        for &player_id in &[4, 5] {
+            server.players.get_mut(&player_id).unwrap().set_loose_state();
+        }
+
+        vote_pause(&mut server, &sender, 1, true);
+        vote_pause(&mut server, &sender, 1, true);
+        assert!(!server.is_paused);
+
+        vote_pause(&mut server, &sender, 2, true);
+        assert!(server.is_paused);
+        assert!(server.pause_votes.is_empty());
+
+        // Votes for the current state are ignored
+        vote_pause(&mut server, &sender, 3, true);
+        assert!(server.pause_votes.is_empty());
+
+        vote_pause(&mut server, &sender, 3, false);
+        assert_eq!(server.pause_votes.len(), 1);
+
+        // A leaving player takes their vote away
+        server.remove_player(3);
+        assert!(server.pause_votes.is_empty());
+
+        vote_pause(&mut server, &sender, 1, false);
+        assert!(server.is_paused);
+
+        vote_pause(&mut server, &sender, 2, false);
+        assert!(!server.is_paused);
+    }
+
+    #[test]
+    fn pause_and_speed_only_in_match() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        add_other_player(&mut server, &sender, 1);
+
+        // The host may pause without a vote, but not in the lobby
+        server.process_player_command(Command::Pause { sender: sender.clone(), paused: true });
+        server.process_player_command(Command::Speed { sender: sender.clone(), speed: 2.0 });
+        assert!(!server.is_paused);
+        assert_eq!(server.speed, 1.0);
+
+        vote_pause(&mut server, &sender, 1, true);
+        assert!(server.pause_votes.is_empty());
+
+        assert_eq!(server.execute_admin_line("pause", true), "Pause and speed can be changed only during the match");
+
+        start_match(&mut server);
+        assert!(!server.is_paused);
+
+        server.connections.insert(sender.token().0, sender.token().0 as PlayerId);
+        server.process_player_command(Command::Pause { sender: sender.clone(), paused: true });
+        assert!(server.is_paused);
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut server = create_server();
+        let sender = create_sender();
+
+        server.add_player(sender.clone(), false, None, None);
+        start_match(&mut server);
+
+        server.process_player_command(Command::Speed { sender: sender.clone(), speed: 100.0 });
+        assert_eq!(server.speed, 8.0);
+
+        server.process_player_command(Command::Speed { sender: sender.clone(), speed: 0.0 });
+        assert_eq!(server.speed, 0.25);
+
+        assert_eq!(server.execute_admin_line("speed 2", true), "Speed is 2x");
+        assert_eq!(server.execute_admin_line("speed -3", true), "Speed is 0.25x");
     }
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+#[derive(Copy, Clone)]
+pub enum VictoryCondition {
+    /// Own every planet
+    Conquest,
+    /// Have the highest score when the time limit in seconds runs out
+    Score { time_limit: f64 },
+    /// Hold the waypoint nearest to the map center for the given seconds in total
+    KingOfTheHill { hold_time: f64 },
+    /// Accumulate the given amount of gold
+    Economic { gold_target: f64 }
+}
+
+impl VictoryCondition {
+    pub fn from_name(name: &str, target: Option<f64>) -> Option<VictoryCondition> {
+        match name {
+            "conquest" => Some(VictoryCondition::Conquest),
+            "score" => Some(VictoryCondition::Score { time_limit: target.unwrap_or(600.0) }),
+            "hill" => Some(VictoryCondition::KingOfTheHill { hold_time: target.unwrap_or(120.0) }),
+            "economic" => Some(VictoryCondition::Economic { gold_target: target.unwrap_or(500.0) }),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            VictoryCondition::Conquest => "conquest",
+            VictoryCondition::Score { .. } => "score",
+            VictoryCondition::KingOfTheHill { .. } => "hill",
+            VictoryCondition::Economic { .. } => "economic"
+        }
+    }
+
+    pub fn target(&self) -> Option<f64> {
+        match *self {
+            VictoryCondition::Conquest => None,
+            VictoryCondition::Score { time_limit } => Some(time_limit),
+            VictoryCondition::KingOfTheHill { hold_time } => Some(hold_time),
+            VictoryCondition::Economic { gold_target } => Some(gold_target)
+        }
+    }
+}
+
+pub struct Settings {
+    pub map_path: Option<String>,
+    pub rules_path: Option<String>,
+    pub hyperlanes: bool,
+    pub black_hole_lane_cost: f64,
+    pub hostile_lane_cost: f64,
+    pub victory: VictoryCondition,
+    pub diplomacy: bool,
+    /// Hide enemy squads out of vision range of player's squads and waypoints
+    pub fog_of_war: bool,
+    /// Allies see everything each other see under fog of war
+    pub shared_vision: bool,
+    /// Shared password required to join, if any
+    pub password: Option<String>,
+    /// Single use tokens allowing to join, accepted in addition to the password
+    pub invite_tokens: Vec<String>,
+    /// Token to join with admin rights
+    pub admin_token: Option<String>,
+    /// Read admin commands from stdin
+    pub admin_console: bool,
+    /// Directory where remote admins may dump the match state
+    pub dump_dir: Option<String>,
+    /// Directory remote admins may reload rules files from
+    pub rules_dir: Option<String>,
+    /// File keeping statistics and ratings of named players between matches
+    pub stats_path: Option<String>
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings {
+            map_path: None,
+            rules_path: None,
+            hyperlanes: false,
+            black_hole_lane_cost: 0.0,
+            hostile_lane_cost: 0.0,
+            victory: VictoryCondition::Conquest,
+            diplomacy: false,
+            fog_of_war: false,
+            shared_vision: false,
+            password: None,
+            invite_tokens: vec![],
+            admin_token: None,
+            admin_console: false,
+            dump_dir: None,
+            rules_dir: None,
+            stats_path: None
+        }
+    }
+}
@@ -1,11 +1,17 @@
-use common::{Id, PlayerId, Position};
+use std::collections::VecDeque;
+
+use common::{Id, PlayerId, Position, SquadClass};
 
 pub struct Squad {
     id: Id,
     owner: PlayerId,
+    class: SquadClass,
     state: SquadState,
     position: Position,
-    life: f64
+    life: f64,
+    route: VecDeque<Id>,
+    /// Last waypoint of the route given by the player
+    destination: Option<Id>
 }
 
 #[derive(Copy, Clone)]
@@ -20,13 +26,16 @@ pub enum SquadState {
 }
 
 impl Squad {
-    pub fn new(id: Id, owner: PlayerId, position: Position) -> Squad {
+    pub fn new(id: Id, owner: PlayerId, class: SquadClass, position: Position, life: f64) -> Squad {
         Squad {
             id: id,
             owner: owner,
+            class: class,
             state: SquadState::InSpace,
             position: position,
-            life: 10_f64
+            life: life,
+            route: VecDeque::new(),
+            destination: None
         }
     }
 
@@ -38,6 +47,10 @@ impl Squad {
         self.owner
     }
 
+    pub fn class(&self) -> SquadClass {
+        self.class
+    }
+
     pub fn state(&self) -> SquadState {
         self.state
     }
@@ -66,6 +79,23 @@ impl Squad {
         self.state = SquadState::Moving { destination: position };
     }
 
+    pub fn set_route(&mut self, route: VecDeque<Id>) {
+        self.destination = route.back().cloned();
+        self.route = route;
+    }
+
+    /// Waypoint the squad is flying to, none once it has arrived
+    pub fn destination(&self) -> Option<Id> {
+        match self.state {
+            SquadState::Moving { .. } => self.destination,
+            _ => None
+        }
+    }
+
+    pub fn next_route_waypoint(&mut self) -> Option<Id> {
+        self.route.pop_front()
+    }
+
     pub fn is_on_orbit(&self, orbit_waypoint_id: Id) -> bool {
         match self.state {
             SquadState::OnOrbit { waypoint_id } => waypoint_id == orbit_waypoint_id,
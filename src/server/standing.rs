@@ -0,0 +1,14 @@
+use common::PlayerId;
+use server::stats::MatchStats;
+
+pub struct Standing {
+    pub player_id: PlayerId,
+    pub is_winner: bool,
+    pub score: f64,
+    pub planets_count: usize,
+    pub squads_count: usize,
+    pub stats: MatchStats,
+    /// Rating after the match, known only for players with a stable name when stats are kept
+    pub rating: Option<f64>,
+    pub rating_change: Option<f64>
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+use constant_time_eq::constant_time_eq;
+use rand::random;
+use rustc_serialize::json::Object;
+use sha2::{Digest, Sha256};
+
+use common::{ParseCommandError, ParseCommandResult};
+use common::utils::json;
+use server::json as server_json;
+
+/// Rating of a player who has not finished any match yet
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Largest rating change of a single match
+const RATING_K_FACTOR: f64 = 32.0;
+
+#[derive(Debug)]
+pub enum LoadStatsError {
+    Io(io::Error),
+    Parse(ParseCommandError)
+}
+
+pub type LoadStatsResult<T> = Result<T, LoadStatsError>;
+
+/// Counters of a player collected during the current match
+#[derive(Copy, Clone)]
+pub struct MatchStats {
+    pub squads_built: u64,
+    pub squads_destroyed: u64,
+    pub planets_captured: u64
+}
+
+impl MatchStats {
+    pub fn new() -> MatchStats {
+        MatchStats {
+            squads_built: 0,
+            squads_destroyed: 0,
+            planets_captured: 0
+        }
+    }
+}
+
+/// Totals of every rated match played under the name
+#[derive(Clone)]
+pub struct PlayerRecord {
+    pub name: String,
+    /// Random salt of the token hash, unique for each record
+    pub token_salt: String,
+    /// Hash of the secret proving the name belongs to the player, the secret itself is not stored
+    pub token_hash: String,
+    pub wins: u64,
+    pub losses: u64,
+    pub squads_built: u64,
+    pub squads_destroyed: u64,
+    pub planets_captured: u64,
+    /// Seconds spent in finished matches
+    pub play_time: f64,
+    pub rating: f64
+}
+
+impl PlayerRecord {
+    fn new(name: &str, token: &str) -> PlayerRecord {
+        let token_salt = create_salt();
+
+        PlayerRecord {
+            name: name.to_string(),
+            token_hash: hash_token(&token_salt, token),
+            token_salt,
+            wins: 0,
+            losses: 0,
+            squads_built: 0,
+            squads_destroyed: 0,
+            planets_captured: 0,
+            play_time: 0.0,
+            rating: INITIAL_RATING
+        }
+    }
+
+    fn is_token_valid(&self, token: &str) -> bool {
+        // Comparison time must not tell how much of the hash matches
+        constant_time_eq(hash_token(&self.token_salt, token).as_bytes(), self.token_hash.as_bytes())
+    }
+}
+
+pub struct MatchResult {
+    pub name: String,
+    pub token: String,
+    pub is_winner: bool,
+    pub stats: MatchStats
+}
+
+/// Player records kept in a JSON file between server runs
+pub struct StatsStore {
+    path: String,
+    records: HashMap<String, PlayerRecord>
+}
+
+impl StatsStore {
+    /// Missing file is an empty store, it is created on the first save
+    pub fn load(path: &str) -> LoadStatsResult<StatsStore> {
+        let mut raw = String::new();
+
+        let records = match File::open(path).and_then(|mut file| file.read_to_string(&mut raw)) {
+            Ok(_) => Self::parse(&raw).map_err(LoadStatsError::Parse)?,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(LoadStatsError::Io(error))
+        };
+
+        Ok(StatsStore {
+            path: path.to_string(),
+            records
+        })
+    }
+
+    /// Writes a temporary file first, so a crash never leaves the store half written
+    pub fn save(&self) -> io::Result<()> {
+        let records = self.records.values().collect::<Vec<_>>();
+        let raw = server_json::format_stats(&records);
+
+        let temporary_path = format!("{}.tmp", self.path);
+
+        File::create(&temporary_path)
+            .and_then(|mut file| file.write_all(raw.as_bytes()))?;
+
+        fs::rename(&temporary_path, &self.path)
+    }
+
+    /// Name is free to use if nobody has played under it yet or the token matches
+    pub fn is_name_available(&self, name: &str, token: &str) -> bool {
+        self.records.get(name).map_or(true, |record| record.is_token_valid(token))
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.records.contains_key(name)
+    }
+
+    pub fn rating(&self, name: &str) -> f64 {
+        self.records.get(name).map_or(INITIAL_RATING, |record| record.rating)
+    }
+
+    /// Adds the match to records of its players and returns their new ratings by name
+    pub fn record_match(&mut self, results: &[MatchResult], duration: f64) -> HashMap<String, f64> {
+        let results = results
+            .iter()
+            .filter(|result| self.is_name_available(&result.name, &result.token))
+            .collect::<Vec<_>>();
+
+        let outcomes = results
+            .iter()
+            .map(|result| (self.rating(&result.name), result.is_winner))
+            .collect::<Vec<_>>();
+
+        let rating_changes = get_rating_changes(&outcomes);
+
+        let mut ratings = HashMap::new();
+
+        for (result, rating_change) in results.into_iter().zip(rating_changes.into_iter()) {
+            let record = self.records
+                .entry(result.name.clone())
+                .or_insert_with(|| PlayerRecord::new(&result.name, &result.token));
+
+            if result.is_winner {
+                record.wins += 1;
+            } else {
+                record.losses += 1;
+            }
+
+            record.squads_built += result.stats.squads_built;
+            record.squads_destroyed += result.stats.squads_destroyed;
+            record.planets_captured += result.stats.planets_captured;
+            record.play_time += duration;
+            record.rating += rating_change;
+
+            ratings.insert(record.name.clone(), record.rating);
+        }
+
+        ratings
+    }
+
+    /// Best rated players first
+    pub fn leaderboard(&self, count: usize) -> Vec<&PlayerRecord> {
+        let mut records = self.records.values().collect::<Vec<_>>();
+
+        records.sort_by(|a, b| {
+            b.rating.partial_cmp(&a.rating).unwrap_or(::std::cmp::Ordering::Equal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        records.truncate(count);
+        records
+    }
+
+    fn parse(raw: &str) -> ParseCommandResult<HashMap<String, PlayerRecord>> {
+        let json = json::parse_json(raw)?;
+        let params = json::parse_json_as_object(&json)?;
+
+        let mut records = HashMap::new();
+        for record_json in json::parse_array_from_json_object(params, "players")? {
+            let record = parse_record(json::parse_json_as_object(record_json)?)?;
+            records.insert(record.name.clone(), record);
+        }
+
+        Ok(records)
+    }
+}
+
+/// Elo for matches of many players: every winner plays a virtual game against every loser.
+/// Changes are divided by the number of other players, so match size does not inflate them,
+/// and what winners gain is exactly what losers lose.
+fn get_rating_changes(outcomes: &[(f64, bool)]) -> Vec<f64> {
+    if outcomes.len() < 2 {
+        return vec![0.0; outcomes.len()];
+    }
+
+    let other_players_count = (outcomes.len() - 1) as f64;
+
+    outcomes
+        .iter()
+        .map(|&(rating, is_winner)| {
+            let score = if is_winner { 1.0 } else { 0.0 };
+
+            let total_change = outcomes
+                .iter()
+                .filter(|&&(_, other_is_winner)| other_is_winner != is_winner)
+                .map(|&(opponent_rating, _)| {
+                    let expected_score = 1.0 / (1.0 + 10_f64.powf((opponent_rating - rating) / 400.0));
+                    RATING_K_FACTOR * (score - expected_score)
+                })
+                .sum::<f64>();
+
+            total_change / other_players_count
+        })
+        .collect()
+}
+
+pub fn create_salt() -> String {
+    format!("{:016x}{:016x}", random::<u64>(), random::<u64>())
+}
+
+/// SHA-256 of the salted token as a lowercase hex string
+pub fn hash_token(salt: &str, token: &str) -> String {
+    let mut hasher = Sha256::default();
+    hasher.input(salt.as_bytes());
+    hasher.input(token.as_bytes());
+
+    hasher.result()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn parse_record(object: &Object) -> ParseCommandResult<PlayerRecord> {
+    // Files written before tokens were hashed keep them as is, they are hashed on load
+    let (token_salt, token_hash) = match object.get("token_hash") {
+        Some(_) => (
+            json::parse_string_from_json_object(object, "token_salt")?.to_string(),
+            json::parse_string_from_json_object(object, "token_hash")?.to_string()
+        ),
+
+        None => {
+            let token_salt = create_salt();
+            let token = json::parse_string_from_json_object(object, "token")?;
+            let token_hash = hash_token(&token_salt, token);
+
+            (token_salt, token_hash)
+        }
+    };
+
+    Ok(PlayerRecord {
+        name: json::parse_string_from_json_object(object, "name")?.to_string(),
+        token_salt,
+        token_hash,
+        wins: json::parse_u64_from_json_object(object, "wins")?,
+        losses: json::parse_u64_from_json_object(object, "losses")?,
+        squads_built: json::parse_u64_from_json_object(object, "squads_built")?,
+        squads_destroyed: json::parse_u64_from_json_object(object, "squads_destroyed")?,
+        planets_captured: json::parse_u64_from_json_object(object, "planets_captured")?,
+        play_time: json::parse_f64_from_json_object(object, "play_time")?,
+        rating: json::parse_f64_from_json_object(object, "rating")?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_rating_changes, hash_token, PlayerRecord, INITIAL_RATING, RATING_K_FACTOR};
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_zero_sum(changes: &[f64]) {
+        let sum = changes.iter().sum::<f64>();
+        assert!(sum.abs() < EPSILON, "rating changes sum to {}", sum);
+    }
+
+    #[test]
+    fn equal_duel() {
+        let changes = get_rating_changes(&[(INITIAL_RATING, true), (INITIAL_RATING, false)]);
+
+        assert!((changes[0] - RATING_K_FACTOR / 2.0).abs() < EPSILON);
+        assert!((changes[1] + RATING_K_FACTOR / 2.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn underdog_gains_more() {
+        let favorite_wins = get_rating_changes(&[(1700.0, true), (1300.0, false)]);
+        let underdog_wins = get_rating_changes(&[(1300.0, true), (1700.0, false)]);
+
+        assert!(favorite_wins[0] > 0.0);
+        assert!(underdog_wins[0] > favorite_wins[0]);
+        assert_zero_sum(&favorite_wins);
+        assert_zero_sum(&underdog_wins);
+    }
+
+    #[test]
+    fn many_players() {
+        let outcomes = [(1500.0, true), (1600.0, false), (1400.0, false), (1550.0, false)];
+        let changes = get_rating_changes(&outcomes);
+
+        assert_eq!(changes.len(), outcomes.len());
+        assert!(changes[0] > 0.0);
+        assert!(changes[1..].iter().all(|&change| change < 0.0));
+        assert!(changes[0] < RATING_K_FACTOR);
+        assert_zero_sum(&changes);
+    }
+
+    #[test]
+    fn teams() {
+        let outcomes = [(1500.0, true), (1450.0, true), (1600.0, false), (1500.0, false), (1380.0, false)];
+        let changes = get_rating_changes(&outcomes);
+
+        assert!(changes[..2].iter().all(|&change| change > 0.0));
+        assert!(changes[2..].iter().all(|&change| change < 0.0));
+        assert_zero_sum(&changes);
+    }
+
+    #[test]
+    fn no_losers() {
+        assert_eq!(get_rating_changes(&[(1500.0, true), (1600.0, true)]), vec![0.0, 0.0]);
+        assert_eq!(get_rating_changes(&[(1500.0, true)]), vec![0.0]);
+        assert!(get_rating_changes(&[]).is_empty());
+    }
+
+    #[test]
+    fn token_is_hashed() {
+        let record = PlayerRecord::new("Alice", "secret");
+        let other_record = PlayerRecord::new("Bob", "secret");
+
+        assert!(!record.token_hash.contains("secret"));
+        assert_ne!(record.token_hash, other_record.token_hash);
+        assert!(record.is_token_valid("secret"));
+        assert!(!record.is_token_valid("guess"));
+        assert!(!record.is_token_valid(""));
+    }
+
+    #[test]
+    fn token_hash_is_sha256() {
+        // Hashes of stats files written before must stay valid
+        assert_eq!(hash_token("ab", "c"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash_token("", ""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+}
\ No newline at end of file
@@ -12,7 +12,9 @@ pub struct Waypoint {
     id: Id,
     waypoint_type: WaypointType,
     owner: Option<PlayerId>,
-    position: Position
+    position: Position,
+    capturer: Option<PlayerId>,
+    capture_progress: f64
 }
 
 impl Waypoint {
@@ -21,7 +23,9 @@ impl Waypoint {
             id,
             waypoint_type,
             position,
-            owner: None
+            owner: None,
+            capturer: None,
+            capture_progress: 0.0
         }
     }
 
@@ -44,4 +48,17 @@ impl Waypoint {
     pub fn set_owner(&mut self, owner: Option<PlayerId>) {
         self.owner = owner;
     }
-}
\ No newline at end of file
+
+    pub fn capturer(&self) -> Option<PlayerId> {
+        self.capturer
+    }
+
+    pub fn capture_progress(&self) -> f64 {
+        self.capture_progress
+    }
+
+    pub fn set_capture(&mut self, capturer: Option<PlayerId>, capture_progress: f64) {
+        self.capturer = capturer;
+        self.capture_progress = capture_progress;
+    }
+}